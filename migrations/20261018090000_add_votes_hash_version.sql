@@ -0,0 +1,2 @@
+-- Rows created before the canonical encoding keep version 1.
+ALTER TABLE votes ADD COLUMN hash_version INTEGER NOT NULL DEFAULT 1;
@@ -0,0 +1,37 @@
+-- pub struct AuditEvent {
+--     pub id: String,
+--     pub entity: String,
+--     pub entity_id: String,
+--     pub action: String,
+--     pub actor: String,
+--     pub before: Option<Value>,
+--     pub after: Option<Value>,
+--     pub hash: String,
+--     pub previous_hash: String,
+--     pub created_at: chrono::DateTime<chrono::Utc>,
+-- }
+CREATE TABLE audit_events (
+  seq INTEGER PRIMARY KEY AUTOINCREMENT,
+  id UUID NOT NULL UNIQUE,
+  entity VARCHAR(50) NOT NULL,
+  entity_id UUID NOT NULL,
+  action VARCHAR(50) NOT NULL,
+  actor TEXT NOT NULL,
+  before TEXT NULL,
+  after TEXT NULL,
+  hash TEXT NOT NULL UNIQUE,
+  previous_hash TEXT NOT NULL,
+  created_at TIMESTAMP NOT NULL
+);
+
+CREATE INDEX idx_audit_events_entity ON audit_events (entity, entity_id);
+
+CREATE TRIGGER audit_events_no_update BEFORE UPDATE ON audit_events
+BEGIN
+  SELECT RAISE(ABORT, 'audit_events is append-only');
+END;
+
+CREATE TRIGGER audit_events_no_delete BEFORE DELETE ON audit_events
+BEGIN
+  SELECT RAISE(ABORT, 'audit_events is append-only');
+END;
@@ -0,0 +1,31 @@
+-- pub struct BallotKey {
+--     pub id: String,
+--     pub position: CandidaturePosition,
+--     pub year: i32,
+--     private_key: RsaPrivateKey,
+-- }
+CREATE TABLE ballot_keys (
+  id UUID PRIMARY KEY,
+  position VARCHAR(20) NOT NULL,
+  year INTEGER NOT NULL,
+  private_key TEXT NOT NULL,
+  created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE UNIQUE INDEX idx_ballot_keys_position_year ON ballot_keys (position, year);
+
+CREATE TABLE ballot_token_issuances (
+  id UUID PRIMARY KEY,
+  voter_id UUID NOT NULL REFERENCES voters(id),
+  position VARCHAR(20) NOT NULL,
+  year INTEGER NOT NULL,
+  key_id UUID NOT NULL REFERENCES ballot_keys(id),
+  created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE UNIQUE INDEX idx_ballot_token_issuances_voter_position_year ON ballot_token_issuances (voter_id, position, year);
+
+-- Anonymous ballots carry the spent token id instead of a voter id.
+ALTER TABLE votes ADD COLUMN ballot_token TEXT NULL;
+
+CREATE UNIQUE INDEX votes_ballot_token ON votes (ballot_token);
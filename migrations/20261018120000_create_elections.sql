@@ -0,0 +1,68 @@
+-- pub struct Election {
+--     pub id: String,
+--     pub name: String,
+--     pub year: i32,
+--     pub tally_mode: TallyMode,
+--     pub status: ElectionStatus,
+--     pub public_key: Option<String>,
+-- }
+CREATE TABLE elections (
+  id UUID PRIMARY KEY,
+  name TEXT NOT NULL,
+  year INTEGER NOT NULL,
+  tally_mode VARCHAR(20) NOT NULL,
+  status VARCHAR(20) NOT NULL,
+  public_key TEXT NULL,
+  created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+  updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX idx_elections_year ON elections (year);
+
+-- pub struct Trustee {
+--     pub id: String,
+--     pub election_id: String,
+--     pub name: String,
+--     pub public_key: String,
+-- }
+CREATE TABLE trustees (
+  id UUID PRIMARY KEY,
+  election_id UUID NOT NULL REFERENCES elections(id),
+  name TEXT NOT NULL,
+  public_key TEXT NOT NULL,
+  created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+  updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE UNIQUE INDEX idx_trustees_election_name ON trustees (election_id, name);
+
+-- Running homomorphic sum of the encrypted ballots, per candidature.
+CREATE TABLE encrypted_tallies (
+  election_id UUID NOT NULL REFERENCES elections(id),
+  candidature_id UUID NOT NULL REFERENCES candidatures(id),
+  c1 TEXT NOT NULL,
+  c2 TEXT NOT NULL,
+  PRIMARY KEY (election_id, candidature_id)
+);
+
+CREATE TABLE partial_decryptions (
+  trustee_id UUID NOT NULL REFERENCES trustees(id),
+  election_id UUID NOT NULL REFERENCES elections(id),
+  candidature_id UUID NOT NULL REFERENCES candidatures(id),
+  share TEXT NOT NULL,
+  challenge TEXT NOT NULL,
+  response TEXT NOT NULL,
+  created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY (trustee_id, candidature_id)
+);
+
+-- Plaintext totals, written only once the trustees' shares are combined.
+CREATE TABLE election_results (
+  election_id UUID NOT NULL REFERENCES elections(id),
+  candidature_id UUID NOT NULL REFERENCES candidatures(id),
+  votes INTEGER NOT NULL,
+  PRIMARY KEY (election_id, candidature_id)
+);
+
+ALTER TABLE votes ADD COLUMN election_id UUID NULL REFERENCES elections(id);
+ALTER TABLE votes ADD COLUMN encrypted_ballot TEXT NULL;
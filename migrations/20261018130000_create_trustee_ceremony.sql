@@ -0,0 +1,32 @@
+-- Threshold elections: any `threshold` of the `trustee_count` trustees can
+-- decrypt. Elections without a threshold keep the n-of-n key sum.
+ALTER TABLE elections ADD COLUMN threshold INTEGER NULL;
+ALTER TABLE elections ADD COLUMN trustee_count INTEGER NULL;
+
+-- `share_index` is the trustee's evaluation point in the key ceremony and
+-- `verification_key` the public half of the key share it ends up with.
+ALTER TABLE trustees ADD COLUMN share_index INTEGER NULL;
+ALTER TABLE trustees ADD COLUMN verification_key TEXT NULL;
+
+CREATE UNIQUE INDEX idx_trustees_election_share_index ON trustees (election_id, share_index);
+
+-- Feldman commitments to each dealer's polynomial coefficients.
+CREATE TABLE ceremony_commitments (
+  dealer_id UUID NOT NULL REFERENCES trustees(id),
+  election_id UUID NOT NULL REFERENCES elections(id),
+  degree INTEGER NOT NULL,
+  commitment TEXT NOT NULL,
+  created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY (dealer_id, degree)
+);
+
+-- Polynomial evaluations, each encrypted to the recipient's public key.
+CREATE TABLE ceremony_shares (
+  dealer_id UUID NOT NULL REFERENCES trustees(id),
+  recipient_id UUID NOT NULL REFERENCES trustees(id),
+  election_id UUID NOT NULL REFERENCES elections(id),
+  ephemeral TEXT NOT NULL,
+  ciphertext TEXT NOT NULL,
+  created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY (dealer_id, recipient_id)
+);
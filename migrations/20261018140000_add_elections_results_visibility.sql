@@ -0,0 +1,2 @@
+-- One of live, hidden_until_close or hidden_until_certified.
+ALTER TABLE elections ADD COLUMN results_visibility VARCHAR(30) NOT NULL DEFAULT 'live';
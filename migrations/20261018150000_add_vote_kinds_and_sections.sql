@@ -0,0 +1,12 @@
+ALTER TABLE voters ADD COLUMN district TEXT NULL;
+ALTER TABLE voters ADD COLUMN section TEXT NULL;
+
+CREATE INDEX idx_voters_section ON voters (district, section);
+
+-- One of candidature, blank or null. Blank and null votes have no
+-- candidature_id.
+ALTER TABLE votes ADD COLUMN kind VARCHAR(20) NOT NULL DEFAULT 'candidature';
+ALTER TABLE votes ADD COLUMN district TEXT NULL;
+ALTER TABLE votes ADD COLUMN section TEXT NULL;
+
+CREATE INDEX idx_votes_section ON votes (election_id, district, section);
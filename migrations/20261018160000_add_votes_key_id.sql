@@ -0,0 +1,2 @@
+-- Fingerprint of the key that signed the vote's hash; NULL on older rows.
+ALTER TABLE votes ADD COLUMN key_id VARCHAR(16) NULL;
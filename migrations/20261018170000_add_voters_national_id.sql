@@ -0,0 +1,5 @@
+-- Civil registry number used to deduplicate imported voter rolls. Voters
+-- created before it existed have none.
+ALTER TABLE voters ADD COLUMN national_id VARCHAR(20) NULL;
+
+CREATE UNIQUE INDEX idx_voters_national_id ON voters (national_id) WHERE national_id IS NOT NULL;
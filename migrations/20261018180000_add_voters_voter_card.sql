@@ -0,0 +1,4 @@
+-- Título de eleitor, unique like the CPF in national_id.
+ALTER TABLE voters ADD COLUMN voter_card VARCHAR(12) NULL;
+
+CREATE UNIQUE INDEX idx_voters_voter_card ON voters (voter_card) WHERE voter_card IS NOT NULL;
@@ -0,0 +1,8 @@
+-- Birth dates were stored as DD/MM/YYYY text; store them as ISO dates so
+-- they decode as DATE.
+UPDATE voters
+SET birth_date = substr(birth_date, 7, 4) || '-' || substr(birth_date, 4, 2) || '-' || substr(birth_date, 1, 2)
+WHERE birth_date LIKE '__/__/____';
+
+ALTER TABLE elections ADD COLUMN election_date DATE NULL;
+ALTER TABLE elections ADD COLUMN minimum_voting_age INTEGER NOT NULL DEFAULT 16;
@@ -0,0 +1,14 @@
+ALTER TABLE candidates ADD COLUMN birth_date DATE NULL;
+ALTER TABLE candidates ADD COLUMN party_id UUID NULL REFERENCES parties(id);
+
+ALTER TABLE candidatures ADD COLUMN election_id UUID NULL REFERENCES elections(id);
+-- Electoral district the candidature runs in; NULL for nationwide and
+-- statewide positions.
+ALTER TABLE candidatures ADD COLUMN district VARCHAR(10) NULL;
+
+-- Codes repeat across districts, e.g. councilors of different cities.
+DROP INDEX idx_candidatures_year_code_position;
+CREATE UNIQUE INDEX idx_candidatures_year_code_position_district
+  ON candidatures (year, code, position, COALESCE(district, ''));
+
+CREATE INDEX idx_candidatures_candidate_year ON candidatures (candidate_id, year);
@@ -0,0 +1,3 @@
+ALTER TABLE parties ADD COLUMN number INTEGER NULL;
+
+CREATE UNIQUE INDEX idx_parties_number ON parties (number) WHERE number IS NOT NULL;
@@ -0,0 +1,31 @@
+-- pub struct Federation {
+--     pub id: String,
+--     pub election_id: String,
+--     pub name: String,
+--     pub acronym: String,
+--     pub kind: FederationKind,
+--     pub district: Option<String>,
+--     pub party_ids: Vec<String>,
+-- }
+CREATE TABLE federations (
+  id UUID PRIMARY KEY,
+  election_id UUID NOT NULL REFERENCES elections(id),
+  name TEXT NOT NULL,
+  acronym VARCHAR(20) NOT NULL,
+  kind VARCHAR(20) NOT NULL,
+  district VARCHAR(10) NULL,
+  created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+  updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX idx_federations_election ON federations (election_id);
+
+CREATE TABLE federation_parties (
+  federation_id UUID NOT NULL REFERENCES federations(id),
+  election_id UUID NOT NULL REFERENCES elections(id),
+  party_id UUID NOT NULL REFERENCES parties(id),
+  PRIMARY KEY (federation_id, party_id)
+);
+
+-- A party joins at most one federation or coalition per election.
+CREATE UNIQUE INDEX idx_federation_parties_election_party ON federation_parties (election_id, party_id);
@@ -0,0 +1,5 @@
+-- Name shown on the voting machine, which may differ from the civil name.
+ALTER TABLE candidates ADD COLUMN ballot_name VARCHAR(30) NULL;
+ALTER TABLE candidates ADD COLUMN biography TEXT NULL;
+ALTER TABLE candidates ADD COLUMN proposals_url TEXT NULL;
+ALTER TABLE candidates ADD COLUMN photo_url TEXT NULL;
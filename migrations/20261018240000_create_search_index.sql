@@ -0,0 +1,76 @@
+-- Full-text index over candidates, parties and candidatures. unicode61 with
+-- remove_diacritics folds accents, so "joao" finds "João".
+CREATE VIRTUAL TABLE search_index USING fts5(
+  entity UNINDEXED,
+  entity_id UNINDEXED,
+  title,
+  body,
+  tokenize = 'unicode61 remove_diacritics 2'
+);
+
+CREATE TRIGGER search_index_candidates_insert AFTER INSERT ON candidates BEGIN
+  INSERT INTO search_index (entity, entity_id, title, body)
+  VALUES ('candidate', new.id, COALESCE(new.ballot_name, new.first_name || ' ' || new.last_name), new.first_name || ' ' || new.last_name);
+END;
+
+-- Candidature entries carry the candidate's names and are refreshed too.
+CREATE TRIGGER search_index_candidates_update AFTER UPDATE ON candidates BEGIN
+  DELETE FROM search_index WHERE entity = 'candidate' AND entity_id = old.id;
+  INSERT INTO search_index (entity, entity_id, title, body)
+  VALUES ('candidate', new.id, COALESCE(new.ballot_name, new.first_name || ' ' || new.last_name), new.first_name || ' ' || new.last_name);
+  DELETE FROM search_index
+  WHERE entity = 'candidature' AND entity_id IN (SELECT id FROM candidatures WHERE candidate_id = new.id);
+  INSERT INTO search_index (entity, entity_id, title, body)
+  SELECT 'candidature', cu.id, cu.code,
+    COALESCE(new.ballot_name, '') || ' ' || new.first_name || ' ' || new.last_name || ' ' || p.acronym || ' ' || cu.position || ' ' || cu.year
+  FROM candidatures cu
+  JOIN parties p ON p.id = cu.party_id
+  WHERE cu.candidate_id = new.id;
+END;
+
+CREATE TRIGGER search_index_candidates_delete AFTER DELETE ON candidates BEGIN
+  DELETE FROM search_index WHERE entity = 'candidate' AND entity_id = old.id;
+END;
+
+CREATE TRIGGER search_index_parties_insert AFTER INSERT ON parties BEGIN
+  INSERT INTO search_index (entity, entity_id, title, body)
+  VALUES ('party', new.id, new.acronym, new.name || ' ' || COALESCE(new.number, ''));
+END;
+
+CREATE TRIGGER search_index_parties_update AFTER UPDATE ON parties BEGIN
+  DELETE FROM search_index WHERE entity = 'party' AND entity_id = old.id;
+  INSERT INTO search_index (entity, entity_id, title, body)
+  VALUES ('party', new.id, new.acronym, new.name || ' ' || COALESCE(new.number, ''));
+END;
+
+CREATE TRIGGER search_index_parties_delete AFTER DELETE ON parties BEGIN
+  DELETE FROM search_index WHERE entity = 'party' AND entity_id = old.id;
+END;
+
+-- Candidatures are found by code, or by the candidate and party running.
+CREATE TRIGGER search_index_candidatures_insert AFTER INSERT ON candidatures BEGIN
+  INSERT INTO search_index (entity, entity_id, title, body)
+  SELECT 'candidature', new.id, new.code,
+    COALESCE(c.ballot_name, '') || ' ' || c.first_name || ' ' || c.last_name || ' ' || p.acronym || ' ' || new.position || ' ' || new.year
+  FROM candidates c, parties p
+  WHERE c.id = new.candidate_id AND p.id = new.party_id;
+END;
+
+CREATE TRIGGER search_index_candidatures_delete AFTER DELETE ON candidatures BEGIN
+  DELETE FROM search_index WHERE entity = 'candidature' AND entity_id = old.id;
+END;
+
+INSERT INTO search_index (entity, entity_id, title, body)
+SELECT 'candidate', id, COALESCE(ballot_name, first_name || ' ' || last_name), first_name || ' ' || last_name
+FROM candidates;
+
+INSERT INTO search_index (entity, entity_id, title, body)
+SELECT 'party', id, acronym, name || ' ' || COALESCE(number, '')
+FROM parties;
+
+INSERT INTO search_index (entity, entity_id, title, body)
+SELECT 'candidature', cu.id, cu.code,
+  COALESCE(c.ballot_name, '') || ' ' || c.first_name || ' ' || c.last_name || ' ' || p.acronym || ' ' || cu.position || ' ' || cu.year
+FROM candidatures cu
+JOIN candidates c ON c.id = cu.candidate_id
+JOIN parties p ON p.id = cu.party_id;
@@ -0,0 +1,116 @@
+-- Rebuilds candidatures and votes with the constraints SQLite cannot add to
+-- an existing table: NOT NULL references, CHECKs on positions, kinds and
+-- years, and one credential per vote. `bbox migrate` runs migrations with
+-- foreign keys off and checks them all afterwards, as rebuilding a
+-- referenced table requires.
+
+-- Renaming fails while a trigger refers to a missing table; recreated below.
+DROP TRIGGER search_index_candidates_update;
+
+CREATE TABLE new_candidatures (
+  id UUID PRIMARY KEY,
+  party_id UUID NOT NULL REFERENCES parties(id),
+  candidate_id UUID NOT NULL REFERENCES candidates(id),
+  image_url TEXT NOT NULL,
+  code VARCHAR(10) NOT NULL,
+  year INTEGER NOT NULL CHECK (year BETWEEN 1900 AND 2200),
+  position VARCHAR(20) NOT NULL CHECK (position IN (
+    'Presidente', 'Vice-Presidente', 'Governador', 'Vice-Governador', 'Senador',
+    'Deputado Federal', 'Deputado Estadual', 'Prefeito', 'Vice-Prefeito',
+    'Vereador', 'Ministro', 'Secretário'
+  )),
+  created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+  updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+  election_id UUID NULL REFERENCES elections(id),
+  district VARCHAR(10) NULL
+);
+
+INSERT INTO new_candidatures (id, party_id, candidate_id, image_url, code, year, position, created_at, updated_at, election_id, district)
+SELECT id, party_id, candidate_id, image_url, code, year, position, created_at, updated_at, election_id, district
+FROM candidatures;
+
+DROP TABLE candidatures;
+ALTER TABLE new_candidatures RENAME TO candidatures;
+
+CREATE UNIQUE INDEX idx_candidatures_year_code_position_district
+  ON candidatures (year, code, position, COALESCE(district, ''));
+CREATE INDEX idx_candidatures_candidate_year ON candidatures (candidate_id, year);
+
+CREATE TRIGGER search_index_candidatures_insert AFTER INSERT ON candidatures BEGIN
+  INSERT INTO search_index (entity, entity_id, title, body)
+  SELECT 'candidature', new.id, new.code,
+    COALESCE(c.ballot_name, '') || ' ' || c.first_name || ' ' || c.last_name || ' ' || p.acronym || ' ' || new.position || ' ' || new.year
+  FROM candidates c, parties p
+  WHERE c.id = new.candidate_id AND p.id = new.party_id;
+END;
+
+CREATE TRIGGER search_index_candidatures_delete AFTER DELETE ON candidatures BEGIN
+  DELETE FROM search_index WHERE entity = 'candidature' AND entity_id = old.id;
+END;
+
+CREATE TRIGGER search_index_candidates_update AFTER UPDATE ON candidates BEGIN
+  DELETE FROM search_index WHERE entity = 'candidate' AND entity_id = old.id;
+  INSERT INTO search_index (entity, entity_id, title, body)
+  VALUES ('candidate', new.id, COALESCE(new.ballot_name, new.first_name || ' ' || new.last_name), new.first_name || ' ' || new.last_name);
+  DELETE FROM search_index
+  WHERE entity = 'candidature' AND entity_id IN (SELECT id FROM candidatures WHERE candidate_id = new.id);
+  INSERT INTO search_index (entity, entity_id, title, body)
+  SELECT 'candidature', cu.id, cu.code,
+    COALESCE(new.ballot_name, '') || ' ' || new.first_name || ' ' || new.last_name || ' ' || p.acronym || ' ' || cu.position || ' ' || cu.year
+  FROM candidatures cu
+  JOIN parties p ON p.id = cu.party_id
+  WHERE cu.candidate_id = new.id;
+END;
+
+-- voter_id and ballot_token are exclusive: a vote is cast either by an
+-- identified voter or with an anonymous token. Blank, null and encrypted
+-- votes have no candidature. The genesis vote has neither.
+CREATE TABLE new_votes (
+  id UUID PRIMARY KEY,
+  voter_id UUID NULL REFERENCES voters(id),
+  candidature_id UUID NULL REFERENCES candidatures(id),
+  candidature_position VARCHAR(255) NOT NULL CHECK (candidature_position IN (
+    'Presidente', 'Vice-Presidente', 'Governador', 'Vice-Governador', 'Senador',
+    'Deputado Federal', 'Deputado Estadual', 'Prefeito', 'Vice-Prefeito',
+    'Vereador', 'Ministro', 'Secretário', 'GENESIS'
+  )),
+  year INTEGER NOT NULL CHECK (year BETWEEN 1900 AND 2200),
+  hash TEXT NOT NULL,
+  previous_hash TEXT NOT NULL REFERENCES votes(hash),
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+  hash_version INTEGER NOT NULL DEFAULT 1,
+  ballot_token TEXT NULL,
+  election_id UUID NULL REFERENCES elections(id),
+  encrypted_ballot TEXT NULL,
+  kind VARCHAR(20) NOT NULL DEFAULT 'candidature' CHECK (kind IN ('candidature', 'blank', 'null')),
+  district TEXT NULL,
+  section TEXT NULL,
+  key_id VARCHAR(16) NULL,
+  CHECK (
+    candidature_position = 'GENESIS' OR
+    (voter_id IS NULL) <> (ballot_token IS NULL)
+  ),
+  CHECK (
+    candidature_position = 'GENESIS' OR
+    kind <> 'candidature' OR
+    candidature_id IS NOT NULL OR
+    encrypted_ballot IS NOT NULL
+  ),
+  CHECK (kind = 'candidature' OR candidature_id IS NULL)
+);
+
+INSERT INTO new_votes (id, voter_id, candidature_id, candidature_position, year, hash, previous_hash, created_at, updated_at, hash_version, ballot_token, election_id, encrypted_ballot, kind, district, section, key_id)
+SELECT id, voter_id, candidature_id, candidature_position, year, hash, previous_hash, created_at, updated_at, hash_version, ballot_token, election_id, encrypted_ballot, kind, district, section, key_id
+FROM votes;
+
+DROP TABLE votes;
+ALTER TABLE new_votes RENAME TO votes;
+
+CREATE UNIQUE INDEX votes_hash ON votes (hash);
+CREATE UNIQUE INDEX votes_ballot_token ON votes (ballot_token);
+CREATE INDEX idx_votes_section ON votes (election_id, district, section);
+-- Votes outside an election are scoped by year, as `Vote::build` checks.
+CREATE UNIQUE INDEX idx_votes_voter_position_election
+  ON votes (voter_id, candidature_position, COALESCE(election_id, year))
+  WHERE voter_id IS NOT NULL;
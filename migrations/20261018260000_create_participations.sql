@@ -0,0 +1,27 @@
+-- A runoff (segundo turno) is registered as an election of its own.
+ALTER TABLE elections ADD COLUMN round INTEGER NOT NULL DEFAULT 1 CHECK (round IN (1, 2));
+
+-- One row per voter that cast a ballot, or took an anonymous ballot token,
+-- for a position. Written in the transaction that records the vote, so the
+-- unique index rejects a concurrent second vote.
+CREATE TABLE participations (
+  voter_id UUID NOT NULL REFERENCES voters(id),
+  position VARCHAR(20) NOT NULL,
+  election_id UUID NULL REFERENCES elections(id),
+  year INTEGER NOT NULL,
+  round INTEGER NOT NULL DEFAULT 1 CHECK (round IN (1, 2)),
+  created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Outside an election, participation is scoped by year.
+CREATE UNIQUE INDEX idx_participations_voter_position_election_round
+  ON participations (voter_id, position, COALESCE(election_id, year), round);
+
+INSERT OR IGNORE INTO participations (voter_id, position, election_id, year, round, created_at)
+SELECT voter_id, candidature_position, election_id, year, 1, created_at
+FROM votes
+WHERE voter_id IS NOT NULL;
+
+INSERT OR IGNORE INTO participations (voter_id, position, election_id, year, round, created_at)
+SELECT voter_id, position, NULL, year, 1, created_at
+FROM ballot_token_issuances;
@@ -0,0 +1,5 @@
+-- Schnorr proof that the trustee knows the secret of its public key. Trustees
+-- registered before have none and must be registered again before an n-of-n
+-- election opens.
+ALTER TABLE trustees ADD COLUMN proof_challenge TEXT NULL;
+ALTER TABLE trustees ADD COLUMN proof_response TEXT NULL;
@@ -0,0 +1,35 @@
+-- Ballot token participations were backfilled without an election. Give them
+-- the election that was current when the token was issued: the most recently
+-- created election of the year at that time. Votes cast outside an election
+-- find none and stay scoped by year.
+UPDATE OR IGNORE participations
+SET election_id = (
+  SELECT e.id
+  FROM elections e
+  WHERE e.year = participations.year AND datetime(e.created_at) <= datetime(participations.created_at)
+  ORDER BY e.created_at DESC, e.id DESC
+  LIMIT 1
+)
+WHERE election_id IS NULL;
+
+UPDATE OR IGNORE participations
+SET round = (SELECT e.round FROM elections e WHERE e.id = participations.election_id)
+WHERE election_id IS NOT NULL;
+
+-- A participation outside an election counts for every election of its year,
+-- which the unique index cannot express. `participation::record` reports the
+-- error as `AlreadyVoted`.
+CREATE TRIGGER participations_one_per_year BEFORE INSERT ON participations
+WHEN EXISTS (
+  SELECT 1
+  FROM participations p
+  WHERE
+    p.voter_id = NEW.voter_id AND
+    p.position = NEW.position AND
+    p.year = NEW.year AND
+    p.round = NEW.round AND
+    (p.election_id IS NULL OR NEW.election_id IS NULL)
+)
+BEGIN
+  SELECT RAISE(ABORT, 'voter already participated in this year');
+END;
@@ -0,0 +1,8 @@
+-- The genesis vote is seeded by `Vote::ensure_genesis`, dated the year the
+-- database is set up instead of 2024. Drop the one `create_votes` inserted
+-- while no vote links to it yet, so it is seeded again; chains that already
+-- started keep theirs.
+DELETE FROM votes
+WHERE
+  id = '00000000-0000-0000-0000-000000000000' AND
+  NOT EXISTS (SELECT 1 FROM votes WHERE id <> '00000000-0000-0000-0000-000000000000');
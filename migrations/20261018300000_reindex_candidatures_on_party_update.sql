@@ -0,0 +1,27 @@
+-- Candidature entries carry the party's acronym, so renaming a party
+-- refreshes them too, as updating a candidate does.
+DROP TRIGGER search_index_parties_update;
+
+CREATE TRIGGER search_index_parties_update AFTER UPDATE ON parties BEGIN
+  DELETE FROM search_index WHERE entity = 'party' AND entity_id = old.id;
+  INSERT INTO search_index (entity, entity_id, title, body)
+  VALUES ('party', new.id, new.acronym, new.name || ' ' || COALESCE(new.number, ''));
+  DELETE FROM search_index
+  WHERE entity = 'candidature' AND entity_id IN (SELECT id FROM candidatures WHERE party_id = new.id);
+  INSERT INTO search_index (entity, entity_id, title, body)
+  SELECT 'candidature', cu.id, cu.code,
+    COALESCE(c.ballot_name, '') || ' ' || c.first_name || ' ' || c.last_name || ' ' || new.acronym || ' ' || cu.position || ' ' || cu.year
+  FROM candidatures cu
+  JOIN candidates c ON c.id = cu.candidate_id
+  WHERE cu.party_id = new.id;
+END;
+
+-- Entries left stale by parties renamed before this migration.
+DELETE FROM search_index WHERE entity = 'candidature';
+
+INSERT INTO search_index (entity, entity_id, title, body)
+SELECT 'candidature', cu.id, cu.code,
+  COALESCE(c.ballot_name, '') || ' ' || c.first_name || ' ' || c.last_name || ' ' || p.acronym || ' ' || cu.position || ' ' || cu.year
+FROM candidatures cu
+JOIN candidates c ON c.id = cu.candidate_id
+JOIN parties p ON p.id = cu.party_id;
@@ -0,0 +1,40 @@
+-- Ballot keys and token issuances belong to an election, so a key of the
+-- first round cannot sign runoff ballots and a voter can take a token in
+-- each round.
+ALTER TABLE ballot_keys ADD COLUMN election_id UUID NULL REFERENCES elections(id);
+
+-- Keys were generated for the election current at the time, or ahead of
+-- the first election of their year.
+UPDATE ballot_keys
+SET election_id = COALESCE(
+  (
+    SELECT e.id
+    FROM elections e
+    WHERE e.year = ballot_keys.year AND datetime(e.created_at) <= datetime(ballot_keys.created_at)
+    ORDER BY e.created_at DESC, e.id DESC
+    LIMIT 1
+  ),
+  (
+    SELECT e.id
+    FROM elections e
+    WHERE e.year = ballot_keys.year
+    ORDER BY e.created_at ASC, e.id ASC
+    LIMIT 1
+  )
+);
+
+DROP INDEX idx_ballot_keys_position_year;
+
+-- Keys of years without an election stay scoped by year.
+CREATE UNIQUE INDEX idx_ballot_keys_position_election
+  ON ballot_keys (position, COALESCE(election_id, year));
+
+ALTER TABLE ballot_token_issuances ADD COLUMN election_id UUID NULL REFERENCES elections(id);
+
+UPDATE ballot_token_issuances
+SET election_id = (SELECT k.election_id FROM ballot_keys k WHERE k.id = ballot_token_issuances.key_id);
+
+DROP INDEX idx_ballot_token_issuances_voter_position_year;
+
+CREATE UNIQUE INDEX idx_ballot_token_issuances_voter_position_election
+  ON ballot_token_issuances (voter_id, position, COALESCE(election_id, year));
@@ -0,0 +1,5 @@
+-- A candidate runs once per election. The check in `Candidature::create`
+-- runs before its transaction, so this is what stops two concurrent
+-- registrations; without an election the candidature is scoped by year.
+CREATE UNIQUE INDEX idx_candidatures_candidate_election
+  ON candidatures (candidate_id, year, COALESCE(election_id, ''));
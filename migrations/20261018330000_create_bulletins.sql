@@ -0,0 +1,12 @@
+-- Bulletins are counted and signed once, when the election closes, and
+-- served as stored: a bulletin is a record of the count at closing, not a
+-- fresh count, and its signature must not change between downloads.
+CREATE TABLE bulletins (
+  election_id UUID NOT NULL REFERENCES elections(id),
+  district TEXT NOT NULL,
+  section TEXT NOT NULL,
+  -- The signed bulletin as JSON, exactly as issued.
+  document TEXT NOT NULL,
+  created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY (election_id, district, section)
+);
@@ -0,0 +1,11 @@
+-- The ledger export is signed once per head of the chain rather than once
+-- per vote: the head's digest covers every entry before it. Heads are kept
+-- as signed, which also records every state of the chain ever published.
+CREATE TABLE ledger_heads (
+  digest TEXT NOT NULL,
+  public_key TEXT NOT NULL,
+  entries INTEGER NOT NULL,
+  signature TEXT NOT NULL,
+  created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY (digest, public_key)
+);
@@ -0,0 +1,103 @@
+-- PostgreSQL schema of the tables behind `Repository`: the registry of
+-- parties, candidates, candidatures and voters, the vote chain and the
+-- audit log. Mirrors the SQLite schema as of the same migration.
+CREATE TABLE parties (
+  id TEXT PRIMARY KEY,
+  name TEXT NOT NULL,
+  description TEXT NOT NULL,
+  acronym VARCHAR(10) NOT NULL,
+  number INTEGER NULL,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+  updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE UNIQUE INDEX idx_parties_number ON parties (number) WHERE number IS NOT NULL;
+
+CREATE TABLE candidates (
+  id TEXT PRIMARY KEY,
+  first_name TEXT NOT NULL,
+  last_name TEXT NOT NULL,
+  birth_date DATE NULL,
+  party_id TEXT NULL REFERENCES parties (id),
+  ballot_name TEXT NULL,
+  biography TEXT NULL,
+  proposals_url TEXT NULL,
+  photo_url TEXT NULL,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+  updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE TABLE candidatures (
+  id TEXT PRIMARY KEY,
+  party_id TEXT NOT NULL REFERENCES parties (id),
+  candidate_id TEXT NOT NULL REFERENCES candidates (id),
+  image_url TEXT NOT NULL,
+  code VARCHAR(10) NOT NULL,
+  position VARCHAR(50) NOT NULL,
+  year INTEGER NOT NULL,
+  election_id TEXT NULL,
+  district TEXT NULL,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+  updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE UNIQUE INDEX idx_candidatures_code ON candidatures (year, code, position, COALESCE(district, ''));
+
+CREATE TABLE voters (
+  id TEXT PRIMARY KEY,
+  first_name TEXT NOT NULL,
+  last_name TEXT NOT NULL,
+  mother_name TEXT NOT NULL,
+  father_name TEXT NOT NULL,
+  birth_date DATE NOT NULL,
+  district TEXT NULL,
+  section TEXT NULL,
+  national_id TEXT NULL,
+  voter_card TEXT NULL,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+  updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE UNIQUE INDEX idx_voters_national_id ON voters (national_id) WHERE national_id IS NOT NULL;
+CREATE UNIQUE INDEX idx_voters_voter_card ON voters (voter_card) WHERE voter_card IS NOT NULL;
+
+CREATE TABLE votes (
+  id TEXT PRIMARY KEY,
+  voter_id TEXT NULL REFERENCES voters (id),
+  candidature_id TEXT NULL REFERENCES candidatures (id),
+  candidature_position VARCHAR(50) NOT NULL,
+  hash TEXT NOT NULL UNIQUE,
+  previous_hash TEXT NOT NULL,
+  hash_version INTEGER NOT NULL DEFAULT 1,
+  year INTEGER NOT NULL,
+  ballot_token TEXT NULL UNIQUE,
+  election_id TEXT NULL,
+  encrypted_ballot TEXT NULL,
+  kind VARCHAR(20) NOT NULL DEFAULT 'candidature',
+  district TEXT NULL,
+  section TEXT NULL,
+  key_id TEXT NULL,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+  updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX idx_votes_created_at ON votes (created_at);
+
+INSERT INTO votes (id, candidature_position, hash, previous_hash, year)
+VALUES ('00000000-0000-0000-0000-000000000000', 'GENESIS', 'GENESIS', 'GENESIS', 2024);
+
+CREATE TABLE audit_events (
+  seq BIGSERIAL PRIMARY KEY,
+  id TEXT NOT NULL UNIQUE,
+  entity VARCHAR(50) NOT NULL,
+  entity_id TEXT NOT NULL,
+  action VARCHAR(50) NOT NULL,
+  actor TEXT NOT NULL,
+  before TEXT NULL,
+  after TEXT NULL,
+  hash TEXT NOT NULL UNIQUE,
+  previous_hash TEXT NOT NULL,
+  created_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX idx_audit_events_entity ON audit_events (entity, entity_id);
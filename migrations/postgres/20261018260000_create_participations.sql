@@ -0,0 +1,19 @@
+-- See migrations/20261018260000_create_participations.sql. Elections only
+-- live in SQLite, so every participation here is of the first round.
+CREATE TABLE participations (
+  voter_id TEXT NOT NULL REFERENCES voters (id),
+  position VARCHAR(50) NOT NULL,
+  election_id TEXT NULL,
+  year INTEGER NOT NULL,
+  round INTEGER NOT NULL DEFAULT 1 CHECK (round IN (1, 2)),
+  created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE UNIQUE INDEX idx_participations_voter_position_election_round
+  ON participations (voter_id, position, COALESCE(election_id, year::TEXT), round);
+
+INSERT INTO participations (voter_id, position, election_id, year, created_at)
+SELECT voter_id, candidature_position, election_id, year, created_at
+FROM votes
+WHERE voter_id IS NOT NULL
+ON CONFLICT DO NOTHING;
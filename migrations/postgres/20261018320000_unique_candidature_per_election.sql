@@ -0,0 +1,4 @@
+-- A candidate runs once per election, as in the SQLite schema; without an
+-- election the candidature is scoped by year.
+CREATE UNIQUE INDEX idx_candidatures_candidate_election
+  ON candidatures (candidate_id, year, COALESCE(election_id, ''));
@@ -0,0 +1,10 @@
+-- Running homomorphic sum of the encrypted ballots, per candidature, as in
+-- migrations/20261018120000_create_elections.sql. Elections only live in
+-- SQLite, so election_id carries no foreign key here.
+CREATE TABLE encrypted_tallies (
+  election_id TEXT NOT NULL,
+  candidature_id TEXT NOT NULL REFERENCES candidatures (id),
+  c1 TEXT NOT NULL,
+  c2 TEXT NOT NULL,
+  PRIMARY KEY (election_id, candidature_id)
+);
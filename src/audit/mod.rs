@@ -0,0 +1,359 @@
+use std::env;
+
+use anyhow::anyhow;
+use chrono::{SecondsFormat, SubsecRound};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::{SqliteConnection, SqlitePool};
+use uuid::Uuid;
+
+use crate::encoding::{self, CanonicalEncoder};
+
+const GENESIS_HASH: &str = "GENESIS";
+const DOMAIN: &str = "bbox/audit/v1";
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AuditEvent {
+    pub id: String,
+    pub entity: String,
+    pub entity_id: String,
+    pub action: String,
+    pub actor: String,
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+    pub hash: String,
+    pub previous_hash: String,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl AuditEvent {
+    fn encode(&self) -> Vec<u8> {
+        let before = self.before.as_ref().map(Value::to_string);
+        let after = self.after.as_ref().map(Value::to_string);
+        let created_at = self.created_at.to_rfc3339_opts(SecondsFormat::Nanos, true);
+
+        let mut encoder = CanonicalEncoder::new(DOMAIN);
+        encoder
+            .field("id", &self.id)
+            .field("entity", &self.entity)
+            .field("entity_id", &self.entity_id)
+            .field("action", &self.action)
+            .field("actor", &self.actor)
+            .field("before", before.as_deref().unwrap_or(""))
+            .field("after", after.as_deref().unwrap_or(""))
+            .field("previous_hash", &self.previous_hash)
+            .field("created_at", &created_at);
+        encoder.finish()
+    }
+
+    /// Builds and signs the event that follows `previous_hash`, or the first
+    /// event of the chain when there is none. Timestamps are truncated to
+    /// microseconds, the finest precision every storage backend keeps.
+    pub(crate) fn build(
+        entity: &str,
+        entity_id: &str,
+        action: &str,
+        actor: &str,
+        before: Option<Value>,
+        after: Option<Value>,
+        previous_hash: Option<String>,
+    ) -> AuditEvent {
+        let mut event = AuditEvent {
+            id: Uuid::now_v7().to_string(),
+            entity: entity.to_string(),
+            entity_id: entity_id.to_string(),
+            action: action.to_string(),
+            actor: actor.to_string(),
+            before,
+            after,
+            hash: String::new(),
+            previous_hash: previous_hash.unwrap_or_else(|| GENESIS_HASH.to_string()),
+            created_at: chrono::Utc::now().trunc_subsecs(6),
+        };
+
+        let secret_key = env::var("SECRET_KEY").expect("SECRET_KEY must be set");
+        event.hash = encoding::sign(&secret_key, &event.encode());
+        event
+    }
+
+    /// Appends an event to the chain. Callers pass the transaction that
+    /// performs the mutation, so the event is written if and only if the
+    /// change itself is committed.
+    pub async fn record(
+        conn: &mut SqliteConnection,
+        entity: &str,
+        entity_id: &str,
+        action: &str,
+        actor: &str,
+        before: Option<Value>,
+        after: Option<Value>,
+    ) -> Result<AuditEvent, sqlx::Error> {
+        let previous_hash = sqlx::query_scalar!(
+            r#"
+            SELECT
+                hash
+            FROM
+                audit_events
+            ORDER BY
+                seq DESC
+            LIMIT 1
+            "#
+        )
+        .fetch_optional(&mut *conn)
+        .await?;
+
+        let event = AuditEvent::build(
+            entity,
+            entity_id,
+            action,
+            actor,
+            before,
+            after,
+            previous_hash,
+        );
+
+        let before = event.before.as_ref().map(Value::to_string);
+        let after = event.after.as_ref().map(Value::to_string);
+        sqlx::query!(
+            r#"
+            INSERT INTO audit_events (id, entity, entity_id, action, actor, before, after, hash, previous_hash, created_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#,
+            event.id,
+            event.entity,
+            event.entity_id,
+            event.action,
+            event.actor,
+            before,
+            after,
+            event.hash,
+            event.previous_hash,
+            event.created_at,
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        Ok(event)
+    }
+
+    pub async fn list(
+        conn: &SqlitePool,
+        entity: Option<String>,
+        entity_id: Option<String>,
+        limit: i64,
+    ) -> Result<Vec<AuditEvent>, sqlx::Error> {
+        let rows = sqlx::query_as!(
+            AuditEventRecord,
+            r#"
+            SELECT
+                id AS "id!: String",
+                entity,
+                entity_id AS "entity_id!: String",
+                action,
+                actor,
+                before,
+                after,
+                hash,
+                previous_hash,
+                created_at AS "created_at: chrono::DateTime<chrono::Utc>"
+            FROM
+                audit_events
+            WHERE
+                (?1 IS NULL OR entity = ?1) AND
+                (?2 IS NULL OR entity_id = ?2)
+            ORDER BY
+                seq DESC
+            LIMIT ?3
+            "#,
+            entity,
+            entity_id,
+            limit
+        )
+        .fetch_all(conn)
+        .await?;
+
+        Ok(rows.into_iter().map(AuditEvent::from).collect())
+    }
+
+    /// Re-computes every hash from the first event onwards and checks that
+    /// each event links to its predecessor. Returns the number of events.
+    pub async fn verify_chain(conn: &SqlitePool) -> Result<usize, anyhow::Error> {
+        let records = sqlx::query_as!(
+            AuditEventRecord,
+            r#"
+            SELECT
+                id AS "id!: String",
+                entity,
+                entity_id AS "entity_id!: String",
+                action,
+                actor,
+                before,
+                after,
+                hash,
+                previous_hash,
+                created_at AS "created_at: chrono::DateTime<chrono::Utc>"
+            FROM
+                audit_events
+            ORDER BY
+                seq ASC
+            "#
+        )
+        .fetch_all(conn)
+        .await?;
+        let count = records.len();
+
+        let secret_key = env::var("SECRET_KEY").expect("SECRET_KEY must be set");
+        let mut previous_hash = GENESIS_HASH.to_string();
+
+        for event in records.into_iter().map(AuditEvent::from) {
+            if event.previous_hash != previous_hash {
+                return Err(anyhow!("audit event {} breaks the chain", event.id));
+            }
+            if encoding::sign(&secret_key, &event.encode()) != event.hash {
+                return Err(anyhow!("audit event {} has an invalid hash", event.id));
+            }
+            previous_hash = event.hash;
+        }
+
+        Ok(count)
+    }
+}
+
+/// An `audit_events` row, with the snapshots still as JSON text.
+struct AuditEventRecord {
+    id: String,
+    entity: String,
+    entity_id: String,
+    action: String,
+    actor: String,
+    before: Option<String>,
+    after: Option<String>,
+    hash: String,
+    previous_hash: String,
+    created_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl From<AuditEventRecord> for AuditEvent {
+    fn from(record: AuditEventRecord) -> AuditEvent {
+        let snapshot =
+            |value: Option<String>| value.and_then(|value| serde_json::from_str(&value).ok());
+
+        AuditEvent {
+            id: record.id,
+            entity: record.entity,
+            entity_id: record.entity_id,
+            action: record.action,
+            actor: record.actor,
+            before: snapshot(record.before),
+            after: snapshot(record.after),
+            hash: record.hash,
+            previous_hash: record.previous_hash,
+            created_at: record.created_at,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::testing::test_pool;
+
+    /// Appends `count` events and returns them in order.
+    async fn append(conn: &SqlitePool, count: usize) -> Vec<AuditEvent> {
+        let mut events = Vec::new();
+        for index in 0..count {
+            let mut tx = conn.begin().await.unwrap();
+            let event = AuditEvent::record(
+                &mut tx,
+                "party",
+                &Uuid::now_v7().to_string(),
+                "update",
+                "test",
+                Some(json!({ "number": index })),
+                Some(json!({ "number": index + 1 })),
+            )
+            .await
+            .unwrap();
+            tx.commit().await.unwrap();
+            events.push(event);
+        }
+        events
+    }
+
+    /// Edits the table behind the application's back, as someone with
+    /// access to the database file could.
+    async fn tamper(conn: &SqlitePool, statement: &str, id: &str) {
+        sqlx::query("DROP TRIGGER audit_events_no_update")
+            .execute(conn)
+            .await
+            .unwrap();
+        sqlx::query("DROP TRIGGER audit_events_no_delete")
+            .execute(conn)
+            .await
+            .unwrap();
+        sqlx::query(statement).bind(id).execute(conn).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn appended_events_link_to_their_predecessor() {
+        let conn = test_pool().await;
+        let before = AuditEvent::verify_chain(&conn).await.unwrap();
+        let events = append(&conn, 3).await;
+
+        for pair in events.windows(2) {
+            assert_eq!(pair[1].previous_hash, pair[0].hash);
+        }
+        assert_eq!(AuditEvent::verify_chain(&conn).await.unwrap(), before + 3);
+    }
+
+    #[tokio::test]
+    async fn the_log_cannot_be_rewritten_through_the_api() {
+        let conn = test_pool().await;
+        let events = append(&conn, 1).await;
+
+        let update = sqlx::query("UPDATE audit_events SET actor = 'mallory' WHERE id = ?")
+            .bind(&events[0].id)
+            .execute(&conn)
+            .await;
+        assert!(update.is_err());
+        let delete = sqlx::query("DELETE FROM audit_events WHERE id = ?")
+            .bind(&events[0].id)
+            .execute(&conn)
+            .await;
+        assert!(delete.is_err());
+    }
+
+    #[tokio::test]
+    async fn detects_edited_events() {
+        let conn = test_pool().await;
+        let events = append(&conn, 3).await;
+
+        tamper(
+            &conn,
+            r#"UPDATE audit_events SET after = '{"number":99}' WHERE id = ?"#,
+            &events[1].id,
+        )
+        .await;
+        let error = AuditEvent::verify_chain(&conn).await.unwrap_err();
+        assert!(error.to_string().contains(&events[1].id));
+        assert!(error.to_string().contains("invalid hash"));
+    }
+
+    #[tokio::test]
+    async fn detects_removed_events() {
+        let conn = test_pool().await;
+        let events = append(&conn, 3).await;
+
+        tamper(
+            &conn,
+            "DELETE FROM audit_events WHERE id = ?",
+            &events[1].id,
+        )
+        .await;
+        let error = AuditEvent::verify_chain(&conn).await.unwrap_err();
+        assert!(error.to_string().contains(&events[2].id));
+        assert!(error.to_string().contains("breaks the chain"));
+    }
+}
@@ -0,0 +1,715 @@
+use std::env;
+
+use anyhow::anyhow;
+use chacha20poly1305::{
+    aead::{Aead, AeadCore, KeyInit, Payload},
+    ChaCha20Poly1305, Nonce,
+};
+use chrono::Datelike;
+use rsa::{
+    hazmat::{rsa_decrypt_and_check, rsa_encrypt},
+    pkcs8::{DecodePrivateKey, EncodePrivateKey, LineEnding},
+    traits::PublicKeyParts,
+    BigUint, RsaPrivateKey,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use sqlx::SqlitePool;
+use uuid::Uuid;
+
+use crate::{
+    encoding::CanonicalEncoder, votes::participation, AlreadyVoted, AuditEvent,
+    CandidaturePosition, Election, ElectionStatus, Vote, Voter,
+};
+
+const KEY_BITS: usize = 2048;
+const MIN_NONCE_BYTES: usize = 16;
+const FDH_DOMAIN: &[u8] = b"bbox/ballot-token/v1";
+const SEALING_DOMAIN: &str = "bbox/ballot-key/v1";
+const PEM_PREFIX: &str = "-----BEGIN";
+
+/// RSA key that blind-signs ballot tokens. There is one key per position and
+/// election, so a token obtained for one position cannot be spent on another,
+/// nor a token of the first round on the runoff.
+pub struct BallotKey {
+    pub id: String,
+    pub position: CandidaturePosition,
+    pub year: i32,
+    /// Absent on keys generated in a year without an election.
+    pub election_id: Option<String>,
+    private_key: RsaPrivateKey,
+}
+
+/// An unblinded token as submitted with an anonymous ballot. `signature` is
+/// the RSA signature over the full-domain hash of `nonce`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BallotToken {
+    pub key_id: String,
+    pub nonce: String,
+    pub signature: String,
+}
+
+/// Expands `nonce` to the size of the modulus with counter-mode SHA-256, so
+/// the signed value covers the whole domain rather than 256 bits of it.
+pub fn full_domain_hash(nonce: &[u8], modulus: &BigUint) -> BigUint {
+    let size = modulus.bits().div_ceil(8);
+    let mut bytes = Vec::with_capacity(size + 32);
+    let mut counter: u32 = 0;
+
+    while bytes.len() < size {
+        let mut hasher = Sha256::new();
+        hasher.update(FDH_DOMAIN);
+        hasher.update(nonce);
+        hasher.update(counter.to_be_bytes());
+        bytes.extend_from_slice(&hasher.finalize());
+        counter += 1;
+    }
+    bytes.truncate(size);
+
+    BigUint::from_bytes_be(&bytes) % modulus
+}
+
+/// Cipher for ballot keys at rest, keyed by `BALLOT_KEY_SECRET`. It is kept
+/// out of the database so a copy of the votes alone cannot mint tokens.
+fn sealing_cipher() -> Result<ChaCha20Poly1305, anyhow::Error> {
+    let secret =
+        env::var("BALLOT_KEY_SECRET").map_err(|_| anyhow!("BALLOT_KEY_SECRET must be set"))?;
+    let secret: [u8; 32] = hex::decode(secret)?
+        .try_into()
+        .map_err(|_| anyhow!("BALLOT_KEY_SECRET must be 32 bytes of hex"))?;
+    Ok(ChaCha20Poly1305::new(&secret.into()))
+}
+
+/// Binds a sealed key to its row, so it cannot be moved to another position
+/// or year.
+fn sealing_context(id: &str, position: &CandidaturePosition, year: i32) -> Vec<u8> {
+    let mut encoder = CanonicalEncoder::new(SEALING_DOMAIN);
+    encoder
+        .field("id", id)
+        .field("position", &position.to_string())
+        .field("year", &year.to_string());
+    encoder.finish()
+}
+
+/// Encrypts a PKCS#8 PEM as `<nonce>:<ciphertext>` in hex.
+fn seal(
+    id: &str,
+    position: &CandidaturePosition,
+    year: i32,
+    pem: &str,
+) -> Result<String, anyhow::Error> {
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut rand::thread_rng());
+    let context = sealing_context(id, position, year);
+    let ciphertext = sealing_cipher()?
+        .encrypt(
+            &nonce,
+            Payload {
+                msg: pem.as_bytes(),
+                aad: &context,
+            },
+        )
+        .map_err(|_| anyhow!("could not seal ballot key"))?;
+
+    Ok(format!(
+        "{}:{}",
+        hex::encode(nonce),
+        hex::encode(ciphertext)
+    ))
+}
+
+fn unseal(
+    id: &str,
+    position: &CandidaturePosition,
+    year: i32,
+    sealed: &str,
+) -> Result<RsaPrivateKey, anyhow::Error> {
+    if sealed.starts_with(PEM_PREFIX) {
+        return Err(anyhow!(
+            "ballot key {} is stored unencrypted, run bbox migrate",
+            id
+        ));
+    }
+    let (nonce, ciphertext) = sealed
+        .split_once(':')
+        .ok_or_else(|| anyhow!("malformed ballot key {}", id))?;
+    let nonce = hex::decode(nonce)?;
+    if nonce.len() != 12 {
+        return Err(anyhow!("malformed ballot key {}", id));
+    }
+    let context = sealing_context(id, position, year);
+    let pem = sealing_cipher()?
+        .decrypt(
+            Nonce::from_slice(&nonce),
+            Payload {
+                msg: &hex::decode(ciphertext)?,
+                aad: &context,
+            },
+        )
+        .map_err(|_| anyhow!("could not unseal ballot key {}", id))?;
+
+    Ok(RsaPrivateKey::from_pkcs8_pem(std::str::from_utf8(&pem)?)?)
+}
+
+impl BallotKey {
+    pub async fn find(conn: &SqlitePool, id: &str) -> Result<Option<BallotKey>, anyhow::Error> {
+        let row = sqlx::query!(
+            r#"
+            SELECT
+                id AS "id!: String",
+                position,
+                year AS "year: i32",
+                election_id AS "election_id: String",
+                private_key
+            FROM
+                ballot_keys
+            WHERE
+                id = ?
+            "#,
+            id
+        )
+        .fetch_optional(conn)
+        .await?;
+
+        row.map(|row| {
+            let position = CandidaturePosition::from(row.position);
+            Ok(BallotKey {
+                private_key: unseal(&row.id, &position, row.year, &row.private_key)?,
+                id: row.id,
+                position,
+                year: row.year,
+                election_id: row.election_id,
+            })
+        })
+        .transpose()
+    }
+
+    /// Generates the key for a position in an election, or returns the
+    /// existing one. Only admins create keys; voters can only fetch them.
+    pub async fn create(
+        conn: &SqlitePool,
+        position: CandidaturePosition,
+        election: &Election,
+        actor: &str,
+    ) -> Result<BallotKey, anyhow::Error> {
+        if let Some(key) = BallotKey::find_by_position(conn, &position, &election.id).await? {
+            return Ok(key);
+        }
+        let year = election.year;
+
+        let private_key =
+            tokio::task::spawn_blocking(|| RsaPrivateKey::new(&mut rand::thread_rng(), KEY_BITS))
+                .await??;
+        let id = Uuid::now_v7().to_string();
+        let sealed = seal(
+            &id,
+            &position,
+            year,
+            &private_key.to_pkcs8_pem(LineEnding::LF)?,
+        )?;
+
+        // Another request may have generated the key concurrently; the unique
+        // index keeps the first one and we read back whichever won.
+        let mut tx = conn.begin().await?;
+        let position_name = position.to_string();
+        let inserted = sqlx::query!(
+            r#"
+            INSERT OR IGNORE INTO ballot_keys (id, position, year, election_id, private_key)
+            VALUES (?, ?, ?, ?, ?)
+            "#,
+            id,
+            position_name,
+            year,
+            election.id,
+            sealed,
+        )
+        .execute(&mut *tx)
+        .await?;
+        if inserted.rows_affected() > 0 {
+            let key = BallotKey {
+                id: id.clone(),
+                position: position.clone(),
+                year,
+                election_id: Some(election.id.clone()),
+                private_key,
+            };
+            AuditEvent::record(
+                &mut tx,
+                "ballot_key",
+                &id,
+                "create",
+                actor,
+                None,
+                Some(key.public_key()),
+            )
+            .await?;
+        }
+        tx.commit().await?;
+
+        BallotKey::find_by_position(conn, &position, &election.id)
+            .await?
+            .ok_or_else(|| anyhow!("ballot key not found"))
+    }
+
+    /// Encrypts the keys stored as plain PEM before keys were sealed. Returns
+    /// how many were sealed; it is a no-op once every key is.
+    pub async fn seal_stored(conn: &SqlitePool) -> Result<usize, anyhow::Error> {
+        let pattern = format!("{}%", PEM_PREFIX);
+        let rows = sqlx::query!(
+            r#"
+            SELECT
+                id AS "id!: String",
+                position,
+                year AS "year: i32",
+                private_key
+            FROM
+                ballot_keys
+            WHERE
+                private_key LIKE ?
+            "#,
+            pattern
+        )
+        .fetch_all(conn)
+        .await?;
+
+        for row in rows.iter() {
+            let position = CandidaturePosition::from(row.position.clone());
+            let sealed = seal(&row.id, &position, row.year, &row.private_key)?;
+            sqlx::query!(
+                r#"
+                UPDATE ballot_keys SET private_key = ? WHERE id = ?
+                "#,
+                sealed,
+                row.id,
+            )
+            .execute(conn)
+            .await?;
+        }
+
+        Ok(rows.len())
+    }
+
+    pub async fn find_by_position(
+        conn: &SqlitePool,
+        position: &CandidaturePosition,
+        election_id: &str,
+    ) -> Result<Option<BallotKey>, anyhow::Error> {
+        let position = position.to_string();
+        let id = sqlx::query_scalar!(
+            r#"
+            SELECT
+                id AS "id!: String"
+            FROM
+                ballot_keys
+            WHERE
+                position = ? AND
+                election_id = ?
+            "#,
+            position,
+            election_id
+        )
+        .fetch_optional(conn)
+        .await?;
+
+        match id {
+            Some(id) => BallotKey::find(conn, &id).await,
+            None => Ok(None),
+        }
+    }
+
+    pub fn public_key(&self) -> Value {
+        json!({
+            "id": self.id,
+            "position": self.position.to_string(),
+            "year": self.year,
+            "election_id": self.election_id,
+            "n": hex::encode(self.private_key.n().to_bytes_be()),
+            "e": hex::encode(self.private_key.e().to_bytes_be()),
+        })
+    }
+
+    /// Signs a blinded message. The key never sees the nonce it is signing.
+    pub fn blind_sign(&self, blinded_message: &str) -> Result<String, anyhow::Error> {
+        let blinded = BigUint::from_bytes_be(&hex::decode(blinded_message)?);
+        if &blinded >= self.private_key.n() {
+            return Err(anyhow!("blinded message is out of range"));
+        }
+        let signature =
+            rsa_decrypt_and_check(&self.private_key, Some(&mut rand::thread_rng()), &blinded)?;
+        Ok(hex::encode(signature.to_bytes_be()))
+    }
+
+    pub fn verify(&self, token: &BallotToken) -> Result<(), anyhow::Error> {
+        let nonce = hex::decode(&token.nonce)?;
+        if nonce.len() < MIN_NONCE_BYTES {
+            return Err(anyhow!("ballot token nonce is too short"));
+        }
+        let signature = BigUint::from_bytes_be(&hex::decode(&token.signature)?);
+        if &signature >= self.private_key.n() {
+            return Err(anyhow!("invalid ballot token signature"));
+        }
+
+        let expected = full_domain_hash(&nonce, self.private_key.n());
+        if rsa_encrypt(&self.private_key.to_public_key(), &signature)? != expected {
+            return Err(anyhow!("invalid ballot token signature"));
+        }
+
+        Ok(())
+    }
+}
+
+impl BallotToken {
+    /// Identifier stored with the vote to mark the token as spent. It is the
+    /// hash of the decoded nonce, so the nonce itself is never persisted and
+    /// re-encoding it (e.g. upper-case hex) does not yield a fresh token.
+    pub fn id(&self) -> String {
+        let nonce = hex::decode(&self.nonce).unwrap_or_else(|_| self.nonce.clone().into_bytes());
+        hex::encode(Sha256::digest(nonce))
+    }
+
+    /// Issues a blind signature to an identified voter, at most once per
+    /// position in the open election. The issuance is the voter's
+    /// participation record; the ballot cast later with the token carries no
+    /// voter id.
+    pub async fn issue(
+        conn: &SqlitePool,
+        voter_id: &str,
+        position: CandidaturePosition,
+        blinded_message: &str,
+    ) -> Result<Value, anyhow::Error> {
+        let year = chrono::Utc::now().year();
+
+        let voter = Voter::find(conn, voter_id)
+            .await?
+            .ok_or_else(|| anyhow!("voter not found"))?;
+        let election = Election::current(conn, year)
+            .await?
+            .filter(|election| election.status == ElectionStatus::Open)
+            .ok_or_else(|| anyhow!("election is not open"))?;
+        election.ensure_voting_age(voter.birth_date)?;
+        if Vote::has_participated(conn, voter_id, &position, Some(&election), year).await? {
+            return Err(AlreadyVoted.into());
+        }
+
+        let key = BallotKey::find_by_position(conn, &position, &election.id)
+            .await?
+            .ok_or_else(|| anyhow!("no ballot key for this position yet"))?;
+
+        let mut tx = conn.begin().await?;
+        participation::record(&mut tx, voter_id, &position, Some(&election.id), year).await?;
+        let id = Uuid::now_v7().to_string();
+        let position = position.to_string();
+        let issued = sqlx::query!(
+            r#"
+            INSERT INTO ballot_token_issuances (id, voter_id, position, year, key_id, election_id)
+            VALUES (?, ?, ?, ?, ?, ?)
+            "#,
+            id,
+            voter_id,
+            position,
+            year,
+            key.id,
+            election.id,
+        )
+        .execute(&mut *tx)
+        .await;
+        if issued.is_err() {
+            return Err(anyhow!(
+                "ballot token already issued for this position in this election"
+            ));
+        }
+
+        let blind_signature = key.blind_sign(blinded_message)?;
+        tx.commit().await?;
+
+        Ok(json!({
+            "key_id": key.id,
+            "blind_signature": blind_signature,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+    use rand::RngCore;
+    use rsa::traits::PrivateKeyParts;
+
+    use super::*;
+    use crate::{
+        testing::{self, test_pool},
+        CandidatureNotFound, SqliteRepository, TallyMode, VoteCredential, VoteKind,
+    };
+
+    // Smaller than `KEY_BITS`, which only slows the tests down.
+    const TEST_KEY_BITS: usize = 1024;
+
+    fn test_key() -> BallotKey {
+        BallotKey {
+            id: Uuid::now_v7().to_string(),
+            position: CandidaturePosition::President,
+            year: 2026,
+            election_id: None,
+            private_key: RsaPrivateKey::new(&mut rand::thread_rng(), TEST_KEY_BITS).unwrap(),
+        }
+    }
+
+    fn random_nonce() -> Vec<u8> {
+        let mut nonce = vec![0; 32];
+        rand::thread_rng().fill_bytes(&mut nonce);
+        nonce
+    }
+
+    /// What a voter does: blinds the hash of a nonce with a random factor,
+    /// has it signed, and divides the factor back out.
+    fn blind(key: &BallotKey, nonce: &[u8]) -> (String, BigUint) {
+        let n = key.private_key.n();
+        let e = key.private_key.e();
+        // r^-1 = r^(φ(n) - 1) mod n, as r is coprime with n.
+        let one = BigUint::from(1u32);
+        let phi = key
+            .private_key
+            .primes()
+            .iter()
+            .fold(one.clone(), |phi, prime| phi * (prime - &one));
+        let factor = BigUint::from_bytes_be(&random_nonce()) % n;
+        let inverse = factor.modpow(&(phi - &one), n);
+
+        let blinded = full_domain_hash(nonce, n) * factor.modpow(e, n) % n;
+        (hex::encode(blinded.to_bytes_be()), inverse)
+    }
+
+    fn unblind(
+        key: &BallotKey,
+        nonce: &[u8],
+        blind_signature: &str,
+        inverse: &BigUint,
+    ) -> BallotToken {
+        let n = key.private_key.n();
+        let signature =
+            BigUint::from_bytes_be(&hex::decode(blind_signature).unwrap()) * inverse % n;
+
+        BallotToken {
+            key_id: key.id.clone(),
+            nonce: hex::encode(nonce),
+            signature: hex::encode(signature.to_bytes_be()),
+        }
+    }
+
+    fn obtain_token(key: &BallotKey, nonce: &[u8]) -> BallotToken {
+        let (blinded, inverse) = blind(key, nonce);
+        let blind_signature = key.blind_sign(&blinded).unwrap();
+        unblind(key, nonce, &blind_signature, &inverse)
+    }
+
+    #[test]
+    fn unblinded_signatures_verify() {
+        let key = test_key();
+        let token = obtain_token(&key, &random_nonce());
+        key.verify(&token).unwrap();
+    }
+
+    #[test]
+    fn signatures_do_not_transfer_to_other_nonces_or_keys() {
+        let key = test_key();
+        let token = obtain_token(&key, &random_nonce());
+
+        let other_nonce = BallotToken {
+            nonce: hex::encode(random_nonce()),
+            ..token.clone()
+        };
+        assert!(key.verify(&other_nonce).is_err());
+        assert!(test_key().verify(&token).is_err());
+    }
+
+    #[test]
+    fn rejects_short_nonces_and_out_of_range_values() {
+        let key = test_key();
+        let token = obtain_token(&key, &[7; MIN_NONCE_BYTES - 1]);
+        assert!(key.verify(&token).is_err());
+
+        let n = hex::encode(key.private_key.n().to_bytes_be());
+        assert!(key.blind_sign(&n).is_err());
+        let token = BallotToken {
+            signature: n,
+            ..obtain_token(&key, &random_nonce())
+        };
+        assert!(key.verify(&token).is_err());
+    }
+
+    #[test]
+    fn full_domain_hash_spans_the_modulus() {
+        let key = test_key();
+        let n = key.private_key.n();
+        let nonce = random_nonce();
+        let hash = full_domain_hash(&nonce, n);
+        assert!(&hash < n);
+        assert!(hash.bits() > TEST_KEY_BITS - 64);
+        assert_eq!(hash, full_domain_hash(&nonce, n));
+        assert_ne!(hash, full_domain_hash(&random_nonce(), n));
+    }
+
+    #[test]
+    fn token_ids_ignore_the_nonce_encoding() {
+        let token = BallotToken {
+            key_id: String::new(),
+            nonce: "abcdef0123456789abcdef0123456789".to_string(),
+            signature: String::new(),
+        };
+        let upper = BallotToken {
+            nonce: token.nonce.to_uppercase(),
+            ..token.clone()
+        };
+        assert_eq!(token.id(), upper.id());
+    }
+
+    #[test]
+    fn sealed_keys_only_open_for_their_row() {
+        env::set_var("BALLOT_KEY_SECRET", hex::encode([42u8; 32]));
+        let key = test_key();
+        let pem = key.private_key.to_pkcs8_pem(LineEnding::LF).unwrap();
+        let sealed = seal(&key.id, &key.position, key.year, &pem).unwrap();
+        assert!(!sealed.contains(PEM_PREFIX));
+
+        let opened = unseal(&key.id, &key.position, key.year, &sealed).unwrap();
+        assert_eq!(opened.n(), key.private_key.n());
+        assert!(unseal(&key.id, &CandidaturePosition::Governor, key.year, &sealed).is_err());
+        assert!(unseal(&key.id, &key.position, key.year + 1, &sealed).is_err());
+        assert!(unseal(&key.id, &key.position, key.year, &pem).is_err());
+    }
+
+    /// Stores a test-sized key for the position in the election, as
+    /// `BallotKey::create` would with a full-sized one.
+    async fn store_key(
+        conn: &SqlitePool,
+        position: CandidaturePosition,
+        election: &Election,
+    ) -> BallotKey {
+        env::set_var("BALLOT_KEY_SECRET", hex::encode([42u8; 32]));
+        let key = BallotKey {
+            position,
+            year: election.year,
+            election_id: Some(election.id.clone()),
+            ..test_key()
+        };
+        let pem = key.private_key.to_pkcs8_pem(LineEnding::LF).unwrap();
+        let sealed = seal(&key.id, &key.position, key.year, &pem).unwrap();
+        sqlx::query(
+            "INSERT INTO ballot_keys (id, position, year, election_id, private_key) VALUES (?, ?, ?, ?, ?)",
+        )
+        .bind(&key.id)
+        .bind(key.position.to_string())
+        .bind(key.year)
+        .bind(&key.election_id)
+        .bind(sealed)
+        .execute(conn)
+        .await
+        .unwrap();
+        key
+    }
+
+    async fn issue(
+        conn: &SqlitePool,
+        key: &BallotKey,
+        voter: &Voter,
+    ) -> Result<BallotToken, anyhow::Error> {
+        let nonce = random_nonce();
+        let (blinded, inverse) = blind(key, &nonce);
+        let signed = BallotToken::issue(conn, &voter.id, key.position.clone(), &blinded).await?;
+        let blind_signature = signed["blind_signature"].as_str().unwrap();
+        Ok(unblind(key, &nonce, blind_signature, &inverse))
+    }
+
+    async fn cast(
+        conn: &SqlitePool,
+        token: BallotToken,
+        position: CandidaturePosition,
+        code: &str,
+    ) -> Result<Vote, anyhow::Error> {
+        Vote::build(
+            conn,
+            &SqliteRepository::new(conn.clone()),
+            VoteCredential::BallotToken(token),
+            VoteKind::Candidature,
+            Some(code.to_string()),
+            position,
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn issues_tokens_only_while_the_election_is_open() {
+        let conn = test_pool().await;
+        let year = chrono::Utc::now().year();
+        let voter = testing::voter(&conn, None).await;
+        let election = Election::build("Eleições".to_string(), year, TallyMode::Plain);
+        election.create(&conn, testing::ACTOR).await.unwrap();
+        let key = store_key(&conn, CandidaturePosition::President, &election).await;
+
+        let error = issue(&conn, &key, &voter).await.unwrap_err();
+        assert_eq!(error.to_string(), "election is not open");
+
+        let election = election.open(&conn, testing::ACTOR).await.unwrap();
+        election.close(&conn, testing::ACTOR).await.unwrap();
+        let error = issue(&conn, &key, &voter).await.unwrap_err();
+        assert_eq!(error.to_string(), "election is not open");
+    }
+
+    #[tokio::test]
+    async fn tokens_are_only_spent_in_their_election() {
+        let conn = test_pool().await;
+        let year = chrono::Utc::now().year();
+        let party = testing::party(&conn, 55).await;
+        let candidate =
+            testing::candidate(&conn, &party, NaiveDate::from_ymd_opt(1960, 1, 1).unwrap()).await;
+        let voter = testing::voter(&conn, None).await;
+
+        let first_round = testing::open_election(&conn, year, 1).await;
+        testing::candidature(&candidate, "55", CandidaturePosition::President, year)
+            .create(&conn, testing::ACTOR)
+            .await
+            .unwrap();
+        let first_key = store_key(&conn, CandidaturePosition::President, &first_round).await;
+        let token = issue(&conn, &first_key, &voter).await.unwrap();
+        first_round.close(&conn, testing::ACTOR).await.unwrap();
+
+        let runoff = testing::open_election(&conn, year, 2).await;
+        let error = cast(&conn, token, CandidaturePosition::President, "55")
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "ballot token was not issued for this position in this election"
+        );
+
+        // The voter takes a fresh token for the runoff, signed by its key.
+        let runoff_key = store_key(&conn, CandidaturePosition::President, &runoff).await;
+        let token = issue(&conn, &runoff_key, &voter).await.unwrap();
+        cast(&conn, token, CandidaturePosition::President, "55")
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn token_ballots_do_not_select_district_candidatures() {
+        let conn = test_pool().await;
+        let year = chrono::Utc::now().year();
+        let party = testing::party(&conn, 55).await;
+        let candidate =
+            testing::candidate(&conn, &party, NaiveDate::from_ymd_opt(1980, 1, 1).unwrap()).await;
+        let election = testing::open_election(&conn, year, 1).await;
+        let mut candidature =
+            testing::candidature(&candidate, "55123", CandidaturePosition::Councilor, year);
+        candidature.district = Some("001".to_string());
+        candidature.create(&conn, testing::ACTOR).await.unwrap();
+        let key = store_key(&conn, CandidaturePosition::Councilor, &election).await;
+
+        let token = issue(&conn, &key, &testing::voter(&conn, Some("002")).await)
+            .await
+            .unwrap();
+        let error = cast(&conn, token, CandidaturePosition::Councilor, "55123")
+            .await
+            .unwrap_err();
+        assert!(error.downcast_ref::<CandidatureNotFound>().is_some());
+    }
+}
@@ -0,0 +1,72 @@
+//! Standalone verifier for a ledger exported with `bbox ledger export` or
+//! `GET /api/v1/ledger/export`. It needs no database or secrets, only the
+//! public key result bulletins are signed with.
+
+use std::path::PathBuf;
+
+use bbox::{LedgerEntry, ResultRow};
+use clap::Parser;
+
+#[derive(Debug, Parser)]
+#[command(name = "bbox-verify")]
+struct Args {
+    /// Ledger in JSON Lines
+    ledger: PathBuf,
+    /// Hex Ed25519 key the ledger must be signed with, as published in bulletins
+    #[arg(long)]
+    public_key: String,
+    /// Published results (jsonl export) to check against the recount
+    #[arg(long)]
+    results: Option<PathBuf>,
+}
+
+fn run(args: Args) -> Result<bool, anyhow::Error> {
+    let (entries, head) = LedgerEntry::from_jsonl(&std::fs::read_to_string(&args.ledger)?)?;
+    let audit = LedgerEntry::audit(&entries, &head, &args.public_key)?;
+
+    println!("chain ok: {} entries", audit.entries);
+    println!("digests and head signature ok");
+    println!("signing keys: {}", audit.key_ids.join(", "));
+    for tally in audit.tallies.iter() {
+        println!(
+            "{}\t{}\t{}\t{}\t{}",
+            tally.election_id.as_deref().unwrap_or("-"),
+            tally.position,
+            tally.kind,
+            tally.candidature_code.as_deref().unwrap_or("-"),
+            tally.votes
+        );
+    }
+    for (election_id, ballots) in audit.encrypted_ballots.iter() {
+        println!("{}\tencrypted ballots\t{}", election_id, ballots);
+    }
+
+    let Some(results) = args.results else {
+        return Ok(true);
+    };
+    let results = std::fs::read_to_string(results)?
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(serde_json::from_str)
+        .collect::<Result<Vec<ResultRow>, _>>()?;
+
+    let differences = audit.compare(&results);
+    for difference in differences.iter() {
+        println!("mismatch: {}", difference);
+    }
+    if differences.is_empty() {
+        println!("results match the recount");
+    }
+    Ok(differences.is_empty())
+}
+
+fn main() {
+    match run(Args::parse()) {
+        Ok(true) => {}
+        Ok(false) => std::process::exit(2),
+        Err(reason) => {
+            eprintln!("error: {}", reason);
+            std::process::exit(1);
+        }
+    }
+}
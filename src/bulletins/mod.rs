@@ -0,0 +1,513 @@
+mod pdf;
+
+use std::{collections::BTreeMap, env, fmt::Write};
+
+use anyhow::anyhow;
+use chrono::SecondsFormat;
+use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::SqlitePool;
+
+use crate::{
+    encoding::CanonicalEncoder, CandidaturePosition, Election, ElectionStatus, PageRequest,
+    TallyMode, Vote, VoteFilter, VoteKind,
+};
+
+const DOMAIN: &str = "bbox/bulletin/v1";
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct BulletinLine {
+    pub code: String,
+    pub candidate: String,
+    pub party: String,
+    pub votes: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct BulletinPosition {
+    pub position: CandidaturePosition,
+    pub candidatures: Vec<BulletinLine>,
+    pub blank: i64,
+    pub null: i64,
+}
+
+/// Result bulletin (boletim de urna) of one polling section, signed with the
+/// Ed25519 key in `BULLETIN_SIGNING_KEY` when the election closes and stored
+/// as issued. Counts come from the votes cast in the section; ballots cast
+/// with a token carry no section and are left out.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Bulletin {
+    pub election_id: String,
+    pub election_name: String,
+    pub year: i32,
+    pub district: String,
+    pub section: String,
+    pub registered_voters: i64,
+    pub turnout: i64,
+    pub positions: Vec<BulletinPosition>,
+    pub generated_at: chrono::DateTime<chrono::Utc>,
+    pub public_key: String,
+    pub signature: String,
+}
+
+impl BulletinPosition {
+    /// The position's counts in `positions`, kept in the order of their
+    /// names, starting at zero.
+    fn entry(
+        positions: &mut BTreeMap<String, BulletinPosition>,
+        position: CandidaturePosition,
+    ) -> &mut BulletinPosition {
+        positions
+            .entry(position.to_string())
+            .or_insert_with(|| BulletinPosition {
+                position,
+                candidatures: Vec::new(),
+                blank: 0,
+                null: 0,
+            })
+    }
+
+    pub fn total(&self) -> i64 {
+        self.candidatures.iter().map(|line| line.votes).sum::<i64>() + self.blank + self.null
+    }
+}
+
+pub(crate) fn signing_key() -> Result<SigningKey, anyhow::Error> {
+    let seed = env::var("BULLETIN_SIGNING_KEY")
+        .map_err(|_| anyhow!("BULLETIN_SIGNING_KEY must be set"))?;
+    let seed: [u8; 32] = hex::decode(seed)?
+        .try_into()
+        .map_err(|_| anyhow!("BULLETIN_SIGNING_KEY must be 32 bytes of hex"))?;
+    Ok(SigningKey::from_bytes(&seed))
+}
+
+impl Bulletin {
+    /// Issues the bulletin of every polling section with registered voters
+    /// or votes in a closed plain election. `Election::close` calls this;
+    /// sections already issued keep their bulletin.
+    pub async fn issue_all(
+        conn: &SqlitePool,
+        election: &Election,
+    ) -> Result<Vec<Bulletin>, anyhow::Error> {
+        let sections = sqlx::query!(
+            r#"
+            SELECT
+                district AS "district!: String",
+                section AS "section!: String"
+            FROM
+                voters
+            WHERE
+                district IS NOT NULL AND
+                section IS NOT NULL
+            UNION
+            SELECT
+                district,
+                section
+            FROM
+                votes
+            WHERE
+                election_id = ? AND
+                district IS NOT NULL AND
+                section IS NOT NULL
+            ORDER BY
+                1 ASC,
+                2 ASC
+            "#,
+            election.id
+        )
+        .fetch_all(conn)
+        .await?;
+
+        let mut bulletins = Vec::new();
+        for row in sections {
+            bulletins.push(Bulletin::issue(conn, election, &row.district, &row.section).await?);
+        }
+        Ok(bulletins)
+    }
+
+    /// The stored bulletin of a section, counted and signed first if it was
+    /// never issued.
+    pub async fn issue(
+        conn: &SqlitePool,
+        election: &Election,
+        district: &str,
+        section: &str,
+    ) -> Result<Bulletin, anyhow::Error> {
+        if let Some(bulletin) = Bulletin::find(conn, &election.id, district, section).await? {
+            return Ok(bulletin);
+        }
+
+        let signing_key = signing_key()?;
+        let mut bulletin = Bulletin::count(conn, election, district, section).await?;
+        bulletin.public_key = hex::encode(signing_key.verifying_key().as_bytes());
+        bulletin.signature = hex::encode(signing_key.sign(&bulletin.encode()).to_bytes());
+
+        // A concurrent issue may have stored its bulletin first; that one
+        // is kept and returned.
+        let document = serde_json::to_string(&bulletin)?;
+        sqlx::query!(
+            r#"
+            INSERT OR IGNORE INTO bulletins (election_id, district, section, document)
+            VALUES (?, ?, ?, ?)
+            "#,
+            election.id,
+            district,
+            section,
+            document
+        )
+        .execute(conn)
+        .await?;
+
+        Bulletin::find(conn, &election.id, district, section)
+            .await?
+            .ok_or_else(|| anyhow!("bulletin not found"))
+    }
+
+    /// The bulletin of a section as issued, if it was.
+    pub async fn find(
+        conn: &SqlitePool,
+        election_id: &str,
+        district: &str,
+        section: &str,
+    ) -> Result<Option<Bulletin>, anyhow::Error> {
+        let document = sqlx::query_scalar!(
+            r#"
+            SELECT
+                document
+            FROM
+                bulletins
+            WHERE
+                election_id = ? AND
+                district = ? AND
+                section = ?
+            "#,
+            election_id,
+            district,
+            section
+        )
+        .fetch_optional(conn)
+        .await?;
+
+        Ok(document
+            .map(|document| serde_json::from_str(&document))
+            .transpose()?)
+    }
+
+    /// The unsigned bulletin, also used to recount a signed one. Candidature
+    /// votes are those `Vote::list` counts for the section.
+    pub async fn count(
+        conn: &SqlitePool,
+        election: &Election,
+        district: &str,
+        section: &str,
+    ) -> Result<Bulletin, anyhow::Error> {
+        if election.tally_mode == TallyMode::Encrypted {
+            return Err(anyhow!("encrypted elections have no per-section counts"));
+        }
+        if !matches!(
+            election.status,
+            ElectionStatus::Closed | ElectionStatus::Certified
+        ) {
+            return Err(anyhow!("election is not closed"));
+        }
+
+        let registered_voters = sqlx::query_scalar!(
+            r#"SELECT COUNT(id) AS "count!: i64" FROM voters WHERE district = ? AND section = ?"#,
+            district,
+            section
+        )
+        .fetch_one(conn)
+        .await?;
+
+        // Participations record both the voters who cast a ballot and those
+        // who took a ballot token, which turned out even though the ballot
+        // itself cannot be traced back to the section.
+        let turnout = sqlx::query_scalar!(
+            r#"
+            SELECT
+                COUNT(DISTINCT p.voter_id) AS "turnout!: i64"
+            FROM
+                participations p
+            JOIN
+                voters v ON v.id = p.voter_id
+            WHERE
+                p.election_id = ? AND
+                p.round = ? AND
+                v.district = ? AND
+                v.section = ?
+            "#,
+            election.id,
+            election.round,
+            district,
+            section
+        )
+        .fetch_one(conn)
+        .await?;
+
+        let filter = VoteFilter {
+            election: Some(election.id.clone()),
+            district: Some(district.to_string()),
+            section: Some(section.to_string()),
+            ..VoteFilter::default()
+        };
+        let mut positions: BTreeMap<String, BulletinPosition> = BTreeMap::new();
+
+        let mut page = PageRequest::default();
+        loop {
+            let votes = Vote::list(conn, &filter, &page).await?;
+            for item in votes.items {
+                let text = |value: &Value| value.as_str().unwrap_or_default().to_string();
+                let position = CandidaturePosition::from(text(&item["candidature"]["position"]));
+                BulletinPosition::entry(&mut positions, position)
+                    .candidatures
+                    .push(BulletinLine {
+                        code: text(&item["candidature"]["code"]),
+                        candidate: format!(
+                            "{} {}",
+                            text(&item["candidate"]["first_name"]),
+                            text(&item["candidate"]["last_name"])
+                        ),
+                        party: text(&item["party"]["acronym"]),
+                        votes: item["votes"].as_i64().unwrap_or_default(),
+                    });
+            }
+            match votes.next_cursor {
+                Some(cursor) => page.cursor = Some(cursor),
+                None => break,
+            }
+        }
+        for (candidature_position, kind, votes) in Vote::count_blank_and_null(conn, &filter).await?
+        {
+            let current = BulletinPosition::entry(&mut positions, candidature_position);
+            match kind {
+                VoteKind::Blank => current.blank += votes,
+                _ => current.null += votes,
+            }
+        }
+
+        let mut positions: Vec<BulletinPosition> = positions.into_values().collect();
+        for position in positions.iter_mut() {
+            position
+                .candidatures
+                .sort_by(|a, b| b.votes.cmp(&a.votes).then_with(|| a.code.cmp(&b.code)));
+        }
+
+        Ok(Bulletin {
+            election_id: election.id.clone(),
+            election_name: election.name.clone(),
+            year: election.year,
+            district: district.to_string(),
+            section: section.to_string(),
+            registered_voters,
+            turnout,
+            positions,
+            generated_at: chrono::Utc::now(),
+            public_key: String::new(),
+            signature: String::new(),
+        })
+    }
+
+    /// Everything the signature covers, i.e. all fields but the signature.
+    fn encode(&self) -> Vec<u8> {
+        let year = self.year.to_string();
+        let registered_voters = self.registered_voters.to_string();
+        let turnout = self.turnout.to_string();
+        let generated_at = self
+            .generated_at
+            .to_rfc3339_opts(SecondsFormat::Nanos, true);
+
+        let mut encoder = CanonicalEncoder::new(DOMAIN);
+        encoder
+            .field("election_id", &self.election_id)
+            .field("election_name", &self.election_name)
+            .field("year", &year)
+            .field("district", &self.district)
+            .field("section", &self.section)
+            .field("registered_voters", &registered_voters)
+            .field("turnout", &turnout)
+            .field("generated_at", &generated_at)
+            .field("public_key", &self.public_key);
+        for position in self.positions.iter() {
+            encoder
+                .field("position", &position.position.to_string())
+                .field("blank", &position.blank.to_string())
+                .field("null", &position.null.to_string());
+            for line in position.candidatures.iter() {
+                encoder
+                    .field("code", &line.code)
+                    .field("candidate", &line.candidate)
+                    .field("party", &line.party)
+                    .field("votes", &line.votes.to_string());
+            }
+        }
+        encoder.finish()
+    }
+
+    /// Checks the signature, and that it was made with `expected_public_key`
+    /// when one is given.
+    pub fn verify(&self, expected_public_key: Option<&str>) -> Result<(), anyhow::Error> {
+        if let Some(expected) = expected_public_key {
+            if !expected.eq_ignore_ascii_case(&self.public_key) {
+                return Err(anyhow!("bulletin was signed with a different key"));
+            }
+        }
+
+        let public_key: [u8; 32] = hex::decode(&self.public_key)?
+            .try_into()
+            .map_err(|_| anyhow!("invalid public key length"))?;
+        let signature: [u8; 64] = hex::decode(&self.signature)?
+            .try_into()
+            .map_err(|_| anyhow!("invalid signature length"))?;
+
+        VerifyingKey::from_bytes(&public_key)?
+            .verify_strict(&self.encode(), &Signature::from_bytes(&signature))
+            .map_err(|_| anyhow!("invalid bulletin signature"))
+    }
+
+    /// Whether a recount from the database agrees with this bulletin.
+    pub fn matches(&self, recount: &Bulletin) -> bool {
+        self.election_id == recount.election_id
+            && self.district == recount.district
+            && self.section == recount.section
+            && self.registered_voters == recount.registered_voters
+            && self.turnout == recount.turnout
+            && self.positions == recount.positions
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        let _ = writeln!(text, "BOLETIM DE URNA");
+        let _ = writeln!(text, "Eleição: {} ({})", self.election_name, self.year);
+        let _ = writeln!(text, "Zona: {}  Seção: {}", self.district, self.section);
+        let _ = writeln!(text, "Eleitores aptos: {}", self.registered_voters);
+        let _ = writeln!(text, "Comparecimento: {}", self.turnout);
+        let _ = writeln!(
+            text,
+            "Emitido em: {}",
+            self.generated_at.to_rfc3339_opts(SecondsFormat::Secs, true)
+        );
+
+        for position in self.positions.iter() {
+            let _ = writeln!(text);
+            let _ = writeln!(text, "{}", position.position);
+            for line in position.candidatures.iter() {
+                let _ = writeln!(
+                    text,
+                    "  {:<10} {:<40} {:>8}",
+                    line.code,
+                    format!("{} ({})", line.candidate, line.party),
+                    line.votes
+                );
+            }
+            let _ = writeln!(text, "  {:<51} {:>8}", "Brancos", position.blank);
+            let _ = writeln!(text, "  {:<51} {:>8}", "Nulos", position.null);
+            let _ = writeln!(text, "  {:<51} {:>8}", "Total", position.total());
+        }
+
+        let _ = writeln!(text);
+        let _ = writeln!(text, "Chave pública: {}", self.public_key);
+        let _ = writeln!(text, "Assinatura: {}", self.signature);
+        text
+    }
+
+    /// The printable bulletin: `to_text` laid out on A4 pages.
+    pub fn to_pdf(&self) -> Vec<u8> {
+        pdf::render(&self.to_text())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Datelike, NaiveDate};
+
+    use super::*;
+    use crate::{
+        testing::{self, test_pool},
+        SqliteRepository, VoteCredential, Voter,
+    };
+
+    async fn cast(conn: &SqlitePool, voter: &Voter, kind: VoteKind, code: Option<&str>) {
+        let mut vote = Vote::build(
+            conn,
+            &SqliteRepository::new(conn.clone()),
+            VoteCredential::Voter(voter.id.clone()),
+            kind,
+            code.map(str::to_string),
+            CandidaturePosition::President,
+        )
+        .await
+        .unwrap();
+        vote.create(conn).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn issues_bulletins_once_when_the_election_closes() {
+        let conn = test_pool().await;
+        let year = chrono::Utc::now().year();
+        let party = testing::party(&conn, 55).await;
+        let candidate =
+            testing::candidate(&conn, &party, NaiveDate::from_ymd_opt(1960, 1, 1).unwrap()).await;
+
+        let election = testing::open_election(&conn, year, 1).await;
+        testing::candidature(&candidate, "55", CandidaturePosition::President, year)
+            .create(&conn, testing::ACTOR)
+            .await
+            .unwrap();
+        for kind in [
+            VoteKind::Candidature,
+            VoteKind::Candidature,
+            VoteKind::Blank,
+        ] {
+            let voter = testing::voter(&conn, Some("001")).await;
+            let code = (kind == VoteKind::Candidature).then_some("55");
+            cast(&conn, &voter, kind, code).await;
+        }
+        testing::voter(&conn, Some("001")).await;
+        let election = election.close(&conn, testing::ACTOR).await.unwrap();
+
+        let bulletin = Bulletin::find(&conn, &election.id, "001", "0001")
+            .await
+            .unwrap()
+            .unwrap();
+        bulletin.verify(None).unwrap();
+        assert_eq!((bulletin.registered_voters, bulletin.turnout), (4, 3));
+        let position = &bulletin.positions[0];
+        assert_eq!(position.candidatures[0].votes, 2);
+        assert_eq!((position.blank, position.null, position.total()), (1, 0, 3));
+
+        let issued = Bulletin::issue(&conn, &election, "001", "0001")
+            .await
+            .unwrap();
+        assert_eq!(issued.signature, bulletin.signature);
+        assert_eq!(issued.generated_at, bulletin.generated_at);
+        let recount = Bulletin::count(&conn, &election, "001", "0001")
+            .await
+            .unwrap();
+        assert!(bulletin.matches(&recount));
+    }
+
+    #[test]
+    fn renders_a_pdf_with_a_valid_cross_reference() {
+        let text = "BOLETIM DE URNA\nEleição: (teste)\n".repeat(100);
+        let pdf = pdf::render(&text);
+        assert!(pdf.starts_with(b"%PDF-1.4\n"));
+        assert!(pdf.ends_with(b"%%EOF\n"));
+
+        let tail = String::from_utf8_lossy(&pdf[pdf.len() - 40..]).to_string();
+        let xref: usize = tail.lines().rev().nth(1).unwrap().parse().unwrap();
+        let table = String::from_utf8_lossy(&pdf[xref..]).to_string();
+        let offsets: Vec<usize> = table
+            .lines()
+            .skip(3)
+            .take_while(|line| line.ends_with(" n "))
+            .map(|line| line[..10].parse().unwrap())
+            .collect();
+        // Catalog, page tree, font, and a page and its content for each of
+        // the four pages 200 lines take.
+        assert_eq!(offsets.len(), 3 + 2 * 4);
+        for (index, offset) in offsets.iter().enumerate() {
+            assert!(pdf[*offset..].starts_with(format!("{} 0 obj\n", index + 1).as_bytes()));
+        }
+    }
+}
@@ -0,0 +1,121 @@
+//! A minimal PDF writer for printing bulletins: monospaced text on A4
+//! pages, with the standard Courier font so nothing has to be embedded.
+
+use std::fmt::Write;
+
+const PAGE_WIDTH: u32 = 595;
+const PAGE_HEIGHT: u32 = 842;
+const MARGIN: u32 = 50;
+const FONT_SIZE: u32 = 9;
+const LEADING: u32 = 12;
+/// Courier glyphs are 0.6 em wide, so 86 columns fit between the margins.
+const COLUMNS: usize = 86;
+const LINES_PER_PAGE: usize = ((PAGE_HEIGHT - 2 * MARGIN) / LEADING) as usize;
+
+/// Lays `text` out line by line, wrapping lines wider than the page.
+pub(crate) fn render(text: &str) -> Vec<u8> {
+    let lines: Vec<String> = text
+        .lines()
+        .flat_map(|line| {
+            let chars: Vec<char> = line.chars().collect();
+            if chars.is_empty() {
+                return vec![String::new()];
+            }
+            chars
+                .chunks(COLUMNS)
+                .map(|chunk| chunk.iter().collect())
+                .collect()
+        })
+        .collect();
+    let pages: Vec<&[String]> = match lines.is_empty() {
+        true => vec![&[]],
+        false => lines.chunks(LINES_PER_PAGE).collect(),
+    };
+
+    // 1 is the catalog, 2 the page tree and 3 the font; each page is
+    // followed by its content stream.
+    let page_ids: Vec<usize> = (0..pages.len()).map(|page| 4 + 2 * page).collect();
+    let mut objects: Vec<Vec<u8>> = vec![
+        b"<< /Type /Catalog /Pages 2 0 R >>".to_vec(),
+        format!(
+            "<< /Type /Pages /Kids [{}] /Count {} >>",
+            page_ids
+                .iter()
+                .map(|id| format!("{} 0 R", id))
+                .collect::<Vec<_>>()
+                .join(" "),
+            pages.len()
+        )
+        .into_bytes(),
+        b"<< /Type /Font /Subtype /Type1 /BaseFont /Courier /Encoding /WinAnsiEncoding >>".to_vec(),
+    ];
+    for (page, id) in pages.iter().zip(page_ids.iter()) {
+        objects.push(
+            format!(
+                "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] \
+                 /Resources << /Font << /F1 3 0 R >> >> /Contents {} 0 R >>",
+                PAGE_WIDTH,
+                PAGE_HEIGHT,
+                id + 1
+            )
+            .into_bytes(),
+        );
+
+        let mut content = format!(
+            "BT /F1 {} Tf {} TL {} {} Td\n",
+            FONT_SIZE,
+            LEADING,
+            MARGIN,
+            PAGE_HEIGHT - MARGIN
+        )
+        .into_bytes();
+        for line in page.iter() {
+            content.push(b'(');
+            content.extend(encode(line));
+            content.extend(b") Tj T*\n");
+        }
+        content.extend(b"ET");
+
+        let mut stream = format!("<< /Length {} >>\nstream\n", content.len()).into_bytes();
+        stream.extend(content);
+        stream.extend(b"\nendstream");
+        objects.push(stream);
+    }
+
+    let mut pdf = b"%PDF-1.4\n".to_vec();
+    let mut offsets = Vec::new();
+    for (index, object) in objects.iter().enumerate() {
+        offsets.push(pdf.len());
+        pdf.extend(format!("{} 0 obj\n", index + 1).into_bytes());
+        pdf.extend(object);
+        pdf.extend(b"\nendobj\n");
+    }
+
+    let xref = pdf.len();
+    let mut trailer = format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1);
+    for offset in offsets {
+        let _ = writeln!(trailer, "{:010} 00000 n ", offset);
+    }
+    let _ = write!(
+        trailer,
+        "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+        objects.len() + 1,
+        xref
+    );
+    pdf.extend(trailer.into_bytes());
+    pdf
+}
+
+/// A line as a PDF string in WinAnsiEncoding, which agrees with Latin-1 on
+/// the accented letters of Portuguese. Anything outside it prints as `?`.
+fn encode(line: &str) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    for c in line.chars() {
+        match c {
+            '(' | ')' | '\\' => bytes.extend([b'\\', c as u8]),
+            ' '..='~' | '\u{a0}'..='\u{ff}' => bytes.push(c as u32 as u8),
+            _ => bytes.push(b'?'),
+        }
+    }
+    bytes
+}
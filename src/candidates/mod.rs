@@ -1,12 +1,25 @@
+use chrono::NaiveDate;
 use serde::{Deserialize, Serialize};
-use sqlx::SqlitePool;
+use sqlx::{SqliteExecutor, SqlitePool};
 use uuid::Uuid;
 
-#[derive(Debug, Serialize, Deserialize)]
+use crate::AuditEvent;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Candidate {
     pub id: String,
     pub first_name: String,
     pub last_name: String,
+    pub birth_date: Option<NaiveDate>,
+    /// Party the candidate is affiliated with; candidatures can only be
+    /// registered for it.
+    pub party_id: Option<String>,
+    /// Name shown on the voting machine and the candidature listing.
+    pub ballot_name: Option<String>,
+    pub biography: Option<String>,
+    /// Local URLs of uploads, see `media`.
+    pub proposals_url: Option<String>,
+    pub photo_url: Option<String>,
 }
 
 impl Candidate {
@@ -15,21 +28,150 @@ impl Candidate {
             id: Uuid::now_v7().to_string(),
             first_name,
             last_name,
+            birth_date: None,
+            party_id: None,
+            ballot_name: None,
+            biography: None,
+            proposals_url: None,
+            photo_url: None,
         }
     }
-    pub async fn create<'a>(&self, conn: &'a SqlitePool) -> Result<(), sqlx::Error> {
-        sqlx::query(
+    pub async fn create(&self, conn: &SqlitePool, actor: &str) -> Result<(), sqlx::Error> {
+        let mut tx = conn.begin().await?;
+        sqlx::query!(
             r#"
-            INSERT INTO candidates (id, first_name, last_name)
-            VALUES (?, ?, ?)
+            INSERT INTO candidates (id, first_name, last_name, birth_date, party_id, ballot_name, biography, proposals_url, photo_url)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
             "#,
+            self.id,
+            self.first_name,
+            self.last_name,
+            self.birth_date,
+            self.party_id,
+            self.ballot_name,
+            self.biography,
+            self.proposals_url,
+            self.photo_url,
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        AuditEvent::record(
+            &mut tx,
+            "candidate",
+            &self.id,
+            "create",
+            actor,
+            None,
+            serde_json::to_value(self).ok(),
+        )
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// Saves the profile fields and uploads; name, birth date and party are
+    /// fixed once registered.
+    /// The audit event's `before` is read in the same transaction, after
+    /// taking the write lock, so it is the row this update replaces.
+    pub async fn update(&self, conn: &SqlitePool, actor: &str) -> Result<(), sqlx::Error> {
+        let mut tx = conn.begin().await?;
+        // sqlx only issues a deferred BEGIN; a write that touches no row
+        // takes the lock all the same.
+        sqlx::query!("UPDATE candidates SET id = id WHERE 0")
+            .execute(&mut *tx)
+            .await?;
+        let before = Candidate::find(&mut *tx, &self.id)
+            .await?
+            .ok_or(sqlx::Error::RowNotFound)?;
+
+        sqlx::query!(
+            r#"
+            UPDATE candidates
+            SET ballot_name = ?, biography = ?, proposals_url = ?, photo_url = ?, updated_at = CURRENT_TIMESTAMP
+            WHERE id = ?
+            "#,
+            self.ballot_name,
+            self.biography,
+            self.proposals_url,
+            self.photo_url,
+            self.id,
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        AuditEvent::record(
+            &mut tx,
+            "candidate",
+            &self.id,
+            "update",
+            actor,
+            serde_json::to_value(&before).ok(),
+            serde_json::to_value(self).ok(),
         )
-        .bind(&self.id)
-        .bind(&self.first_name)
-        .bind(&self.last_name)
-        .execute(conn)
         .await?;
 
+        tx.commit().await?;
+
         Ok(())
     }
+
+    pub async fn find(
+        conn: impl SqliteExecutor<'_>,
+        id: &str,
+    ) -> Result<Option<Candidate>, sqlx::Error> {
+        sqlx::query_as!(
+            Candidate,
+            r#"
+            SELECT
+                id AS "id!: String",
+                first_name,
+                last_name,
+                birth_date,
+                party_id AS "party_id: String",
+                ballot_name,
+                biography,
+                proposals_url,
+                photo_url
+            FROM
+                candidates
+            WHERE
+                id = ?
+            "#,
+            id
+        )
+        .fetch_optional(conn)
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, test_pool};
+
+    #[tokio::test]
+    async fn audits_the_row_the_update_replaces() {
+        let conn = test_pool().await;
+        let party = testing::party(&conn, 55).await;
+        let candidate =
+            testing::candidate(&conn, &party, NaiveDate::from_ymd_opt(1960, 1, 1).unwrap()).await;
+
+        let stale = candidate.clone();
+        let mut edited = candidate.clone();
+        edited.biography = Some("Professora".to_string());
+        edited.update(&conn, testing::ACTOR).await.unwrap();
+
+        let mut uploaded = stale;
+        uploaded.photo_url = Some("/media/candidates/photo.webp".to_string());
+        uploaded.update(&conn, testing::ACTOR).await.unwrap();
+
+        let events = AuditEvent::list(&conn, None, Some(candidate.id.clone()), 1)
+            .await
+            .unwrap();
+        let before = events[0].before.as_ref().unwrap();
+        assert_eq!(before["biography"], "Professora");
+    }
 }
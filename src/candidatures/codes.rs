@@ -0,0 +1,104 @@
+use std::collections::HashSet;
+
+use sqlx::SqlitePool;
+
+use super::{Candidature, CandidatureError, CandidaturePosition};
+use crate::Party;
+
+impl CandidaturePosition {
+    /// Digits of the code typed on the voting machine: the party number
+    /// alone for executive offices, then one more digit for senators, two
+    /// for federal deputies and three for state deputies and councilors.
+    /// Ministers and secretaries are appointed and have no code.
+    pub fn code_length(&self) -> Option<usize> {
+        match self {
+            CandidaturePosition::President
+            | CandidaturePosition::VicePresident
+            | CandidaturePosition::Governor
+            | CandidaturePosition::ViceGovernor
+            | CandidaturePosition::Mayor
+            | CandidaturePosition::ViceMayor => Some(2),
+            CandidaturePosition::Senator => Some(3),
+            CandidaturePosition::FederalDeputy => Some(4),
+            CandidaturePosition::StateDeputy | CandidaturePosition::Councilor => Some(5),
+            CandidaturePosition::Minister | CandidaturePosition::Secretary => None,
+        }
+    }
+}
+
+async fn party_number(conn: &SqlitePool, party_id: &str) -> Result<String, CandidatureError> {
+    let party = Party::find(conn, party_id)
+        .await?
+        .ok_or(CandidatureError::PartyNotFound)?;
+    let number = party.number.ok_or(CandidatureError::PartyWithoutNumber)?;
+    Ok(number.to_string())
+}
+
+impl Candidature {
+    /// Checks that the code has the length of the position and starts with
+    /// the party number.
+    pub(super) fn ensure_code(&self, party: Option<&Party>) -> Result<(), CandidatureError> {
+        let length = self
+            .position
+            .code_length()
+            .ok_or(CandidatureError::NotElected)?;
+        let number = party
+            .ok_or(CandidatureError::PartyNotFound)?
+            .number
+            .ok_or(CandidatureError::PartyWithoutNumber)?
+            .to_string();
+        let valid = self.code.len() == length
+            && self.code.chars().all(|c| c.is_ascii_digit())
+            && self.code.starts_with(&number);
+        if !valid {
+            return Err(CandidatureError::InvalidCode);
+        }
+        Ok(())
+    }
+
+    /// The lowest code of the party that is still free for the position in
+    /// the district.
+    pub async fn next_code(
+        conn: &SqlitePool,
+        party_id: &str,
+        position: &CandidaturePosition,
+        year: i32,
+        district: Option<&str>,
+    ) -> Result<String, CandidatureError> {
+        let length = position.code_length().ok_or(CandidatureError::NotElected)?;
+        let number = party_number(conn, party_id).await?;
+
+        let position = position.to_string();
+        let taken: HashSet<String> = sqlx::query_scalar!(
+            r#"
+            SELECT
+                code
+            FROM
+                candidatures
+            WHERE
+                year = ? AND
+                position = ? AND
+                district IS ?
+            "#,
+            year,
+            position,
+            district
+        )
+        .fetch_all(conn)
+        .await?
+        .into_iter()
+        .collect();
+
+        let width = length - number.len();
+        if width == 0 {
+            return match taken.contains(&number) {
+                true => Err(CandidatureError::DuplicateCode),
+                false => Ok(number),
+            };
+        }
+        (1..10_u32.pow(width as u32))
+            .map(|suffix| format!("{}{:0width$}", number, suffix, width = width))
+            .find(|code| !taken.contains(code))
+            .ok_or(CandidatureError::CodesExhausted)
+    }
+}
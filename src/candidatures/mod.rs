@@ -1,10 +1,17 @@
-use chrono::Datelike;
+mod codes;
+
+use std::fmt;
+
+use anyhow::anyhow;
+use chrono::{Datelike, NaiveDate};
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use sqlx::{FromRow, Row, SqlitePool};
 use uuid::Uuid;
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+use crate::{AuditEvent, Candidate, Cursor, Election, Page, PageRequest, Party, SortOrder};
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
 pub enum CandidaturePosition {
     #[serde(rename = "Presidente")]
     President,
@@ -32,22 +39,23 @@ pub enum CandidaturePosition {
     Secretary,
 }
 
-impl ToString for CandidaturePosition {
-    fn to_string(&self) -> String {
-        match self {
-            CandidaturePosition::President => "Presidente".to_string(),
-            CandidaturePosition::VicePresident => "Vice-Presidente".to_string(),
-            CandidaturePosition::Governor => "Governador".to_string(),
-            CandidaturePosition::ViceGovernor => "Vice-Governador".to_string(),
-            CandidaturePosition::Senator => "Senador".to_string(),
-            CandidaturePosition::FederalDeputy => "Deputado Federal".to_string(),
-            CandidaturePosition::StateDeputy => "Deputado Estadual".to_string(),
-            CandidaturePosition::Mayor => "Prefeito".to_string(),
-            CandidaturePosition::ViceMayor => "Vice-Prefeito".to_string(),
-            CandidaturePosition::Councilor => "Vereador".to_string(),
-            CandidaturePosition::Minister => "Ministro".to_string(),
-            CandidaturePosition::Secretary => "Secretário".to_string(),
-        }
+impl fmt::Display for CandidaturePosition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let position = match self {
+            CandidaturePosition::President => "Presidente",
+            CandidaturePosition::VicePresident => "Vice-Presidente",
+            CandidaturePosition::Governor => "Governador",
+            CandidaturePosition::ViceGovernor => "Vice-Governador",
+            CandidaturePosition::Senator => "Senador",
+            CandidaturePosition::FederalDeputy => "Deputado Federal",
+            CandidaturePosition::StateDeputy => "Deputado Estadual",
+            CandidaturePosition::Mayor => "Prefeito",
+            CandidaturePosition::ViceMayor => "Vice-Prefeito",
+            CandidaturePosition::Councilor => "Vereador",
+            CandidaturePosition::Minister => "Ministro",
+            CandidaturePosition::Secretary => "Secretário",
+        };
+        write!(f, "{}", position)
     }
 }
 
@@ -71,6 +79,153 @@ impl From<String> for CandidaturePosition {
     }
 }
 
+impl CandidaturePosition {
+    /// Minimum age on election day to run for the position (CF art. 14,
+    /// § 3º, VI). Ministers and secretaries are appointed, not elected, and
+    /// share the 21 years of deputies.
+    pub fn minimum_age(&self) -> u32 {
+        match self {
+            CandidaturePosition::President
+            | CandidaturePosition::VicePresident
+            | CandidaturePosition::Senator => 35,
+            CandidaturePosition::Governor | CandidaturePosition::ViceGovernor => 30,
+            CandidaturePosition::FederalDeputy
+            | CandidaturePosition::StateDeputy
+            | CandidaturePosition::Mayor
+            | CandidaturePosition::ViceMayor
+            | CandidaturePosition::Minister
+            | CandidaturePosition::Secretary => 21,
+            CandidaturePosition::Councilor => 18,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum CandidatureSort {
+    #[serde(rename = "code")]
+    Code,
+    /// Ballot name, or the civil name when there is none.
+    #[serde(rename = "name")]
+    Name,
+    #[serde(rename = "party")]
+    Party,
+}
+
+impl CandidatureSort {
+    fn column(&self) -> &'static str {
+        match self {
+            CandidatureSort::Code => "sort_code",
+            CandidatureSort::Name => "sort_name",
+            CandidatureSort::Party => "sort_party",
+        }
+    }
+}
+
+/// Filters of `Candidature::list`. Without a year or election it lists the
+/// current year.
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct CandidatureFilter {
+    pub position: Option<CandidaturePosition>,
+    /// Party id or acronym.
+    pub party: Option<String>,
+    pub year: Option<i32>,
+    /// Candidatures a voter of the district can vote for, nationwide ones
+    /// included.
+    pub district: Option<String>,
+    pub election: Option<String>,
+    /// Part of the civil or ballot name.
+    pub search: Option<String>,
+    pub sort: Option<CandidatureSort>,
+}
+
+/// Why a candidature cannot be registered.
+#[derive(Debug)]
+pub enum CandidatureError {
+    CandidateNotFound,
+    /// The candidate already runs for a position in the same election.
+    AlreadyRunning,
+    MissingBirthDate,
+    Underage {
+        minimum_age: u32,
+        age: u32,
+    },
+    NotAffiliated,
+    /// Another candidature has the code for the position in the district.
+    DuplicateCode,
+    PartyNotFound,
+    PartyWithoutNumber,
+    /// The position is filled by appointment, not by vote.
+    NotElected,
+    /// The code does not start with the party number or has the wrong
+    /// number of digits for the position.
+    InvalidCode,
+    /// Every code of the party for the position is taken.
+    CodesExhausted,
+    Database(sqlx::Error),
+}
+
+impl fmt::Display for CandidatureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CandidatureError::CandidateNotFound => write!(f, "candidate not found"),
+            CandidatureError::AlreadyRunning => {
+                write!(f, "candidate already has a candidature in this election")
+            }
+            CandidatureError::MissingBirthDate => write!(f, "candidate has no birth date"),
+            CandidatureError::Underage { minimum_age, age } => write!(
+                f,
+                "candidate must be at least {} years old for the position, is {}",
+                minimum_age, age
+            ),
+            CandidatureError::NotAffiliated => {
+                write!(f, "candidate is not affiliated with the party")
+            }
+            CandidatureError::DuplicateCode => {
+                write!(f, "code is already taken for this position and district")
+            }
+            CandidatureError::PartyNotFound => write!(f, "party not found"),
+            CandidatureError::PartyWithoutNumber => write!(f, "party has no number"),
+            CandidatureError::NotElected => write!(f, "position is not elected"),
+            CandidatureError::InvalidCode => {
+                write!(f, "code does not match the party number and position")
+            }
+            CandidatureError::CodesExhausted => {
+                write!(f, "party has no free codes left for this position")
+            }
+            CandidatureError::Database(reason) => write!(f, "{}", reason),
+        }
+    }
+}
+
+impl std::error::Error for CandidatureError {}
+
+impl From<sqlx::Error> for CandidatureError {
+    fn from(reason: sqlx::Error) -> CandidatureError {
+        CandidatureError::Database(reason)
+    }
+}
+
+/// Unique index that allows a candidate one candidature per election.
+const CANDIDATE_ELECTION_INDEX: &str = "idx_candidatures_candidate_election";
+
+impl CandidatureError {
+    /// Maps the unique indexes an insert can trip to the rule they enforce,
+    /// for registrations that passed the checks concurrently.
+    pub(crate) fn from_insert(reason: sqlx::Error) -> CandidatureError {
+        match &reason {
+            sqlx::Error::Database(error) if error.is_unique_violation() => {
+                let index = error.constraint().unwrap_or_else(|| error.message());
+                if index.contains(CANDIDATE_ELECTION_INDEX) {
+                    CandidatureError::AlreadyRunning
+                } else {
+                    CandidatureError::DuplicateCode
+                }
+            }
+            _ => CandidatureError::Database(reason),
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, FromRow)]
 pub struct Candidature {
     pub id: String,
@@ -80,6 +235,10 @@ pub struct Candidature {
     pub code: String,
     pub year: i32,
     pub position: CandidaturePosition,
+    pub election_id: Option<String>,
+    /// Electoral district for positions elected locally; codes only need to
+    /// be unique within it.
+    pub district: Option<String>,
 }
 
 impl Candidature {
@@ -99,90 +258,551 @@ impl Candidature {
             position,
             image_url,
             year: current_year,
+            election_id: None,
+            district: None,
+        }
+    }
+
+    /// Checks the rules that only depend on the candidate, the party and the
+    /// election date, so every storage backend applies them the same way.
+    pub(crate) fn ensure_rules(
+        &self,
+        candidate: &Candidate,
+        party: Option<&Party>,
+        election_date: NaiveDate,
+    ) -> Result<(), CandidatureError> {
+        if candidate.party_id.as_deref() != Some(self.party_id.as_str()) {
+            return Err(CandidatureError::NotAffiliated);
+        }
+        self.ensure_code(party)?;
+
+        let birth_date = candidate
+            .birth_date
+            .ok_or(CandidatureError::MissingBirthDate)?;
+        let age = election_date.years_since(birth_date).unwrap_or(0);
+        let minimum_age = self.position.minimum_age();
+        if age < minimum_age {
+            return Err(CandidatureError::Underage { minimum_age, age });
+        }
+
+        Ok(())
+    }
+
+    /// The day ages are counted on: the date of the election, or today when
+    /// there is no election or it has no date yet.
+    pub(crate) fn election_date(election: Option<&Election>) -> NaiveDate {
+        election
+            .and_then(|election| election.election_date)
+            .unwrap_or_else(|| chrono::Utc::now().date_naive())
+    }
+
+    /// The election the candidature is registered for: the one it names or,
+    /// without an `election_id`, the current election of its year.
+    pub async fn election(&self, conn: &SqlitePool) -> Result<Option<Election>, sqlx::Error> {
+        match &self.election_id {
+            Some(election_id) => Election::find(conn, election_id).await,
+            None => Election::current(conn, self.year).await,
         }
     }
-    pub async fn create<'a>(&self, conn: &'a SqlitePool) -> Result<(), sqlx::Error> {
-        sqlx::query(
+
+    /// Checks the registration rules against the candidate and the other
+    /// candidatures.
+    async fn ensure_eligible(
+        &self,
+        conn: &SqlitePool,
+        election: Option<&Election>,
+    ) -> Result<(), CandidatureError> {
+        let candidate = Candidate::find(conn, &self.candidate_id)
+            .await?
+            .ok_or(CandidatureError::CandidateNotFound)?;
+        let party = Party::find(conn, &self.party_id).await?;
+        self.ensure_rules(
+            &candidate,
+            party.as_ref(),
+            Candidature::election_date(election),
+        )?;
+
+        let position = self.position.to_string();
+        let running = sqlx::query!(
             r#"
-            INSERT INTO candidatures (id, party_id, candidate_id, code, position, year, image_url)
-            VALUES (?, ?, ?, ?, ?, ?, ?)
+            SELECT
+                id AS "id!: String"
+            FROM
+                candidatures
+            WHERE
+                candidate_id = ? AND
+                year = ? AND
+                election_id IS ?
+            "#,
+            self.candidate_id,
+            self.year,
+            self.election_id
+        )
+        .fetch_optional(conn)
+        .await?;
+        if running.is_some() {
+            return Err(CandidatureError::AlreadyRunning);
+        }
+
+        let taken = sqlx::query!(
+            r#"
+            SELECT
+                id AS "id!: String"
+            FROM
+                candidatures
+            WHERE
+                year = ? AND
+                code = ? AND
+                position = ? AND
+                district IS ?
             "#,
+            self.year,
+            self.code,
+            position,
+            self.district
         )
-        .bind(&self.id)
-        .bind(&self.party_id)
-        .bind(&self.candidate_id)
-        .bind(&self.code)
-        .bind(&self.position.to_string())
-        .bind(&self.year)
-        .bind(&self.image_url)
-        .execute(conn)
+        .fetch_optional(conn)
         .await?;
+        if taken.is_some() {
+            return Err(CandidatureError::DuplicateCode);
+        }
 
         Ok(())
     }
 
-    pub async fn list<'a>(
-        conn: &'a SqlitePool,
-        position: CandidaturePosition,
-    ) -> Result<Vec<Value>, sqlx::Error> {
-        let current_year = chrono::Local::now().year();
-        let rows = sqlx::query(
+    /// Registers the candidature for the election `Candidature::election`
+    /// finds.
+    pub async fn create(&self, conn: &SqlitePool, actor: &str) -> Result<(), CandidatureError> {
+        let election = self.election(conn).await?;
+        self.create_for(conn, election.as_ref(), actor).await
+    }
+
+    /// Registers the candidature for `election`, whose date the age rules
+    /// are checked against.
+    pub async fn create_for(
+        &self,
+        conn: &SqlitePool,
+        election: Option<&Election>,
+        actor: &str,
+    ) -> Result<(), CandidatureError> {
+        // Two registrations can pass the checks at once; the unique indexes
+        // turn the second insert into the same error.
+        self.ensure_eligible(conn, election).await?;
+
+        let position = self.position.to_string();
+        let mut tx = conn.begin().await?;
+        sqlx::query!(
+            r#"
+            INSERT INTO candidatures (id, party_id, candidate_id, code, position, year, image_url, election_id, district)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#,
+            self.id,
+            self.party_id,
+            self.candidate_id,
+            self.code,
+            position,
+            self.year,
+            self.image_url,
+            self.election_id,
+            self.district,
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(CandidatureError::from_insert)?;
+
+        AuditEvent::record(
+            &mut tx,
+            "candidature",
+            &self.id,
+            "create",
+            actor,
+            None,
+            serde_json::to_value(self).ok(),
+        )
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// The candidature a code typed for the position selects in an election.
+    /// Codes are reused every cycle, so only candidatures of the election's
+    /// year, and of the election itself when they name one, are considered.
+    /// Codes of local positions repeat across districts; prefer the voter's
+    /// own district over a nationwide candidature. Without a district, as
+    /// for ballots cast with a token, only nationwide candidatures match.
+    pub async fn find_by_code(
+        conn: &SqlitePool,
+        code: &str,
+        position: &CandidaturePosition,
+        district: Option<&str>,
+        year: i32,
+        election_id: Option<&str>,
+    ) -> Result<Option<Candidature>, sqlx::Error> {
+        let position = position.to_string();
+        let record = sqlx::query_as!(
+            CandidatureRecord,
             r#"
             SELECT
-                cu.id,
-                cu.party_id,
-                cu.candidate_id,
-                cu.code,
-                cu.position,
-                cu.year,
-                cu.image_url,
-                p.name,
-                p.acronym,
-                p.name,
-                ca.first_name,
-                ca.last_name
+                id AS "id!: String",
+                party_id AS "party_id!: String",
+                candidate_id AS "candidate_id!: String",
+                code,
+                position,
+                year AS "year: i32",
+                image_url,
+                election_id AS "election_id: String",
+                district
             FROM
-                candidatures cu
-            JOIN
-                parties p ON p.id = cu.party_id
-            JOIN
-                candidates ca ON ca.id = cu.candidate_id
+                candidatures
+            WHERE
+                code = ?1 AND
+                position = ?2 AND
+                (district IS NULL OR district = ?3) AND
+                year = ?4 AND
+                (?5 IS NULL OR election_id IS NULL OR election_id = ?5)
+            ORDER BY
+                district IS NULL
+            "#,
+            code,
+            position,
+            district,
+            year,
+            election_id
+        )
+        .fetch_optional(conn)
+        .await?;
+
+        Ok(record.map(Candidature::from))
+    }
+
+    /// Every candidature running for the position in the year.
+    pub async fn list_running(
+        conn: &SqlitePool,
+        position: &CandidaturePosition,
+        year: i32,
+    ) -> Result<Vec<Candidature>, sqlx::Error> {
+        let position = position.to_string();
+        let records = sqlx::query_as!(
+            CandidatureRecord,
+            r#"
+            SELECT
+                id AS "id!: String",
+                party_id AS "party_id!: String",
+                candidate_id AS "candidate_id!: String",
+                code,
+                position,
+                year AS "year: i32",
+                image_url,
+                election_id AS "election_id: String",
+                district
+            FROM
+                candidatures
             WHERE
                 position = ? AND
                 year = ?
+            ORDER BY
+                code
             "#,
+            position,
+            year
         )
-        .bind(position.to_string())
-        .bind(current_year)
         .fetch_all(conn)
         .await?;
 
+        Ok(records.into_iter().map(Candidature::from).collect())
+    }
+
+    pub async fn list(
+        conn: &SqlitePool,
+        filter: &CandidatureFilter,
+        page: &PageRequest,
+    ) -> Result<Page<Value>, anyhow::Error> {
+        let cursor = page.cursor()?;
+        let year = match (&filter.year, &filter.election) {
+            (Some(year), _) => *year,
+            (None, Some(election_id)) => {
+                Election::find(conn, election_id)
+                    .await?
+                    .ok_or_else(|| anyhow!("election not found"))?
+                    .year
+            }
+            (None, None) => chrono::Local::now().year(),
+        };
+        let sort = filter.sort.unwrap_or(CandidatureSort::Code);
+        let (keyset, tail) = page.keyset(sort.column(), SortOrder::Asc, 7);
+
+        let rows = page
+            .bind_page(
+                sqlx::query(&format!(
+                    r#"
+                    SELECT
+                        *
+                    FROM (
+                        SELECT
+                            cu.id,
+                            cu.party_id,
+                            cu.candidate_id,
+                            cu.code,
+                            cu.position,
+                            cu.year,
+                            COALESCE(ca.photo_url, cu.image_url) AS image_url,
+                            p.name AS party_name,
+                            p.acronym AS party_acronym,
+                            ca.first_name,
+                            ca.last_name,
+                            f.name AS federation_name,
+                            f.acronym AS federation_acronym,
+                            ca.ballot_name,
+                            COALESCE(ca.ballot_name, ca.first_name || ' ' || ca.last_name) AS sort_name,
+                            p.acronym AS sort_party,
+                            cu.code AS sort_code
+                        FROM
+                            candidatures cu
+                        JOIN
+                            parties p ON p.id = cu.party_id
+                        JOIN
+                            candidates ca ON ca.id = cu.candidate_id
+                        LEFT JOIN
+                            federation_parties fp ON fp.party_id = cu.party_id AND
+                            fp.election_id = COALESCE(cu.election_id, (
+                                SELECT id FROM elections WHERE year = cu.year ORDER BY created_at DESC, id DESC LIMIT 1
+                            ))
+                        LEFT JOIN
+                            federations f ON f.id = fp.federation_id
+                        WHERE
+                            (?1 IS NULL OR cu.position = ?1) AND
+                            cu.year = ?2 AND
+                            (?3 IS NULL OR p.id = ?3 OR p.acronym = ?3) AND
+                            (?4 IS NULL OR cu.district IS NULL OR cu.district = ?4) AND
+                            (?5 IS NULL OR cu.election_id IS NULL OR cu.election_id = ?5) AND
+                            (?6 IS NULL OR
+                                ca.first_name || ' ' || ca.last_name LIKE '%' || ?6 || '%' OR
+                                ca.ballot_name LIKE '%' || ?6 || '%')
+                    )
+                    WHERE
+                        {}
+                    {}
+                    "#,
+                    keyset, tail
+                ))
+                .bind(filter.position.as_ref().map(|position| position.to_string()))
+                .bind(year)
+                .bind(&filter.party)
+                .bind(&filter.district)
+                .bind(&filter.election)
+                .bind(&filter.search),
+                cursor,
+            )
+            .fetch_all(conn)
+            .await?;
+
         let mut cs = Vec::new();
         for row in rows {
+            // The keyset makes this query dynamic, so it cannot be checked
+            // at compile time; columns are read by name instead.
+            let federation = row
+                .get::<Option<String>, _>("federation_acronym")
+                .map(|acronym| {
+                    json!({
+                        "name": row.get::<String, _>("federation_name"),
+                        "acronym": acronym,
+                    })
+                });
             let value = json!({
                 "candidature": {
-                    "id": row.get::<String, usize>(0),
-                    "party_id": row.get::<String, usize>(1),
-                    "candidate_id": row.get::<String, usize>(2),
-                    "code": row.get::<String, usize>(3),
-                    "position": row.get::<String, usize>(4),
-                    "year": row.get::<i32, _>(5),
-                    "image_url": row.get::<String, usize>(6),
+                    "id": row.get::<String, _>("id"),
+                    "party_id": row.get::<String, _>("party_id"),
+                    "candidate_id": row.get::<String, _>("candidate_id"),
+                    "code": row.get::<String, _>("code"),
+                    "position": row.get::<String, _>("position"),
+                    "year": row.get::<i32, _>("year"),
+                    "image_url": row.get::<String, _>("image_url"),
                 },
                 "party": {
-                    "name": row.get::<String, usize>(7),
-                    "acronym": row.get::<String, usize>(8),
+                    "name": row.get::<String, _>("party_name"),
+                    "acronym": row.get::<String, _>("party_acronym"),
                 },
                 "candidate": {
-                    "first_name": row.get::<String, usize>(9),
-                    "last_name": row.get::<String, usize>(10),
-
+                    "first_name": row.get::<String, _>("first_name"),
+                    "last_name": row.get::<String, _>("last_name"),
+                    "ballot_name": row.get::<Option<String>, _>("ballot_name"),
                 },
+                "federation": federation,
             });
+            let cursor = Cursor {
+                key: Value::String(row.get(sort.column())),
+                id: row.get("id"),
+            };
+
+            cs.push((value, cursor));
+        }
+
+        Ok(page.page(cs))
+    }
+}
+
+/// A `candidatures` row, with the position as stored.
+struct CandidatureRecord {
+    id: String,
+    party_id: String,
+    candidate_id: String,
+    code: String,
+    position: String,
+    year: i32,
+    image_url: String,
+    election_id: Option<String>,
+    district: Option<String>,
+}
+
+impl From<CandidatureRecord> for Candidature {
+    fn from(record: CandidatureRecord) -> Candidature {
+        Candidature {
+            id: record.id,
+            party_id: record.party_id,
+            candidate_id: record.candidate_id,
+            code: record.code,
+            position: CandidaturePosition::from(record.position),
+            year: record.year,
+            image_url: record.image_url,
+            election_id: record.election_id,
+            district: record.district,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Datelike, NaiveDate};
+
+    use super::*;
+    use crate::testing::{self, test_pool};
 
-            cs.push(value);
+    fn years_ago(years: i32) -> NaiveDate {
+        let today = chrono::Utc::now().date_naive();
+        today.with_year(today.year() - years).unwrap()
+    }
+
+    #[tokio::test]
+    async fn candidates_must_be_old_enough_for_the_position() {
+        let conn = test_pool().await;
+        let year = chrono::Utc::now().year();
+        let party = testing::party(&conn, 55).await;
+        let candidate = testing::candidate(&conn, &party, years_ago(30)).await;
+
+        let president =
+            testing::candidature(&candidate, "55", CandidaturePosition::President, year);
+        match president.create(&conn, testing::ACTOR).await {
+            Err(CandidatureError::Underage { minimum_age, age }) => {
+                assert_eq!((minimum_age, age), (35, 30));
+            }
+            result => panic!("expected Underage, got {:?}", result),
         }
 
-        Ok(cs)
+        let governor = testing::candidature(&candidate, "55", CandidaturePosition::Governor, year);
+        governor.create(&conn, testing::ACTOR).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn candidates_run_once_per_election() {
+        let conn = test_pool().await;
+        let year = chrono::Utc::now().year();
+        let party = testing::party(&conn, 55).await;
+        let candidate = testing::candidate(&conn, &party, years_ago(50)).await;
+
+        testing::candidature(&candidate, "55", CandidaturePosition::President, year)
+            .create(&conn, testing::ACTOR)
+            .await
+            .unwrap();
+        let senator = testing::candidature(&candidate, "555", CandidaturePosition::Senator, year);
+        assert!(matches!(
+            senator.create(&conn, testing::ACTOR).await,
+            Err(CandidatureError::AlreadyRunning)
+        ));
+
+        // Another cycle is another election.
+        testing::candidature(&candidate, "55", CandidaturePosition::President, year - 4)
+            .create(&conn, testing::ACTOR)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn codes_are_unique_per_position_and_district() {
+        let conn = test_pool().await;
+        let year = chrono::Utc::now().year();
+        let party = testing::party(&conn, 55).await;
+
+        let mut first = testing::candidature(
+            &testing::candidate(&conn, &party, years_ago(40)).await,
+            "55123",
+            CandidaturePosition::Councilor,
+            year,
+        );
+        first.district = Some("001".to_string());
+        first.create(&conn, testing::ACTOR).await.unwrap();
+
+        let mut second = testing::candidature(
+            &testing::candidate(&conn, &party, years_ago(40)).await,
+            "55123",
+            CandidaturePosition::Councilor,
+            year,
+        );
+        second.district = Some("001".to_string());
+        assert!(matches!(
+            second.create(&conn, testing::ACTOR).await,
+            Err(CandidatureError::DuplicateCode)
+        ));
+        second.district = Some("002".to_string());
+        second.create(&conn, testing::ACTOR).await.unwrap();
+    }
+
+    /// What a registration that passed the checks concurrently with another
+    /// one runs into.
+    #[tokio::test]
+    async fn maps_unique_violations_to_the_rule_they_enforce() {
+        let conn = test_pool().await;
+        let year = chrono::Utc::now().year();
+        let party = testing::party(&conn, 55).await;
+        let candidate = testing::candidate(&conn, &party, years_ago(50)).await;
+        let existing = testing::candidature(&candidate, "55", CandidaturePosition::President, year);
+        existing.create(&conn, testing::ACTOR).await.unwrap();
+
+        let insert = |id: String, candidate_id: String, code: &str| {
+            sqlx::query(
+                "INSERT INTO candidatures (id, party_id, candidate_id, code, position, year, image_url) VALUES (?, ?, ?, ?, ?, ?, '')",
+            )
+            .bind(id)
+            .bind(party.id.clone())
+            .bind(candidate_id)
+            .bind(code.to_string())
+            .bind(CandidaturePosition::Senator.to_string())
+            .bind(year)
+        };
+
+        let error = insert(Uuid::now_v7().to_string(), candidate.id.clone(), "555")
+            .execute(&conn)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            CandidatureError::from_insert(error),
+            CandidatureError::AlreadyRunning
+        ));
+
+        let other = testing::candidate(&conn, &party, years_ago(50)).await;
+        insert(Uuid::now_v7().to_string(), other.id.clone(), "555")
+            .execute(&conn)
+            .await
+            .unwrap();
+        let another = testing::candidate(&conn, &party, years_ago(50)).await;
+        let error = insert(Uuid::now_v7().to_string(), another.id.clone(), "555")
+            .execute(&conn)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            CandidatureError::from_insert(error),
+            CandidatureError::DuplicateCode
+        ));
     }
 }
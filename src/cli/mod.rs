@@ -0,0 +1,512 @@
+use std::{
+    fs::OpenOptions,
+    io::{self, Write},
+    os::unix::fs::OpenOptionsExt,
+    path::{Path, PathBuf},
+};
+
+use anyhow::anyhow;
+use bbox::{
+    elgamal, Bulletin, Candidate, Candidature, CandidaturePosition, Election, ExportFormat,
+    LedgerEntry, LedgerHead, Party, Repository, ResultRow, Trustee, Vote, VoteKind, Voter,
+    CURRENT_HASH_VERSION,
+};
+use chrono::{Datelike, NaiveDate, SubsecRound};
+use clap::{Parser, Subcommand};
+use curve25519_dalek::constants::RISTRETTO_BASEPOINT_POINT;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use uuid::Uuid;
+
+#[derive(Debug, Parser)]
+#[command(name = "bbox")]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Run the HTTP server (the default when no command is given)
+    Serve,
+    /// Apply pending schema and data migrations, creating the database if needed
+    Migrate,
+    /// Trustee operations for encrypted elections
+    #[command(subcommand)]
+    Trustee(TrusteeCommand),
+    /// Per-section result bulletins
+    #[command(subcommand)]
+    Report(ReportCommand),
+    /// Flat results for analysts
+    #[command(subcommand)]
+    Results(ResultsCommand),
+    /// Anonymized vote ledger for public audit
+    #[command(subcommand)]
+    Ledger(LedgerCommand),
+    /// Voter roll management
+    #[command(subcommand)]
+    Voters(VotersCommand),
+    /// Storage backend selected by DATABASE_URL, SQLite or PostgreSQL
+    #[command(subcommand)]
+    Repository(RepositoryCommand),
+}
+
+#[derive(Debug, Subcommand)]
+pub enum RepositoryCommand {
+    /// Write and read back a party, candidate, candidature, voter and vote,
+    /// then verify the vote chain. Meant for a scratch database
+    Check {
+        /// Two-digit number of the party created; must be free
+        #[arg(long, default_value_t = 99)]
+        party_number: i32,
+        /// Recorded as the actor in the audit log
+        #[arg(long, default_value = "cli")]
+        actor: String,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum VotersCommand {
+    /// Import a CSV voter roll; nothing is written unless every row is valid
+    Import {
+        #[arg(long)]
+        file: PathBuf,
+        /// Only validate and print the report
+        #[arg(long)]
+        dry_run: bool,
+        /// Recorded as the actor in the audit log
+        #[arg(long, default_value = "cli")]
+        actor: String,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum LedgerCommand {
+    /// Export the whole vote chain as JSON Lines, without voter identities
+    Export {
+        /// Write here instead of stdout
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ResultsCommand {
+    /// Export results as csv, jsonl or parquet, embargoed elections included
+    Export {
+        #[arg(long, default_value = "csv")]
+        format: ExportFormat,
+        #[arg(long)]
+        election: Option<String>,
+        #[arg(long)]
+        year: Option<i32>,
+        /// Write here instead of stdout
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ReportCommand {
+    /// Get the result bulletin of a polling section of a closed election, as
+    /// issued at closing; a section never issued is signed and stored now
+    Generate {
+        #[arg(long)]
+        election: String,
+        #[arg(long)]
+        district: String,
+        #[arg(long)]
+        section: String,
+        /// Write the signed JSON here instead of stdout
+        #[arg(long)]
+        out: Option<PathBuf>,
+        /// Also write the human-readable bulletin here
+        #[arg(long)]
+        text: Option<PathBuf>,
+        /// Also write the printable PDF here
+        #[arg(long)]
+        pdf: Option<PathBuf>,
+    },
+    /// Check a bulletin's signature and, with --recount, its counts against the database
+    Verify {
+        #[arg(long)]
+        file: PathBuf,
+        /// Hex Ed25519 key the bulletin must be signed with
+        #[arg(long)]
+        public_key: Option<String>,
+        #[arg(long)]
+        recount: bool,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum TrusteeCommand {
+    /// Generate a key pair, register its public half and write the secret to a key file
+    Keygen {
+        #[arg(long)]
+        election: String,
+        #[arg(long)]
+        name: String,
+        #[arg(long)]
+        out: PathBuf,
+    },
+    /// Deal this trustee's shares in a threshold election's key ceremony
+    Deal {
+        #[arg(long)]
+        key_file: PathBuf,
+    },
+    /// Verify the shares dealt to this trustee and store the combined key share in its key file
+    Finalize {
+        #[arg(long)]
+        key_file: PathBuf,
+    },
+    /// Publish this trustee's decryption shares for a closed election
+    Decrypt {
+        #[arg(long)]
+        key_file: PathBuf,
+    },
+}
+
+/// Contents of a trustee key file. The secret never reaches the database.
+/// `share_key` is only present once a threshold ceremony has been finalized.
+#[derive(Debug, Serialize, Deserialize)]
+struct TrusteeKeyFile {
+    trustee_id: String,
+    election_id: String,
+    name: String,
+    secret_key: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    share_key: Option<String>,
+}
+
+impl TrusteeKeyFile {
+    fn read(path: &Path) -> Result<TrusteeKeyFile, anyhow::Error> {
+        Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?)
+    }
+
+    fn write(&self, path: &Path, create: bool) -> Result<(), anyhow::Error> {
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create_new(create)
+            .truncate(!create)
+            .mode(0o600)
+            .open(path)?;
+        file.write_all(serde_json::to_string_pretty(self)?.as_bytes())?;
+        Ok(())
+    }
+
+    async fn trustee(&self, conn: &SqlitePool) -> Result<Trustee, anyhow::Error> {
+        Trustee::find(conn, &self.trustee_id)
+            .await?
+            .ok_or_else(|| anyhow!("trustee not found"))
+    }
+}
+
+pub async fn run_trustee(conn: &SqlitePool, command: TrusteeCommand) -> Result<(), anyhow::Error> {
+    match command {
+        TrusteeCommand::Keygen {
+            election,
+            name,
+            out,
+        } => {
+            let (secret, _) = elgamal::generate_keypair();
+            let trustee = Trustee::build(election, name, &secret);
+
+            let key_file = TrusteeKeyFile {
+                trustee_id: trustee.id.clone(),
+                election_id: trustee.election_id.clone(),
+                name: trustee.name.clone(),
+                secret_key: elgamal::encode_scalar(&secret),
+                share_key: None,
+            };
+            key_file.write(&out, true)?;
+
+            trustee
+                .create(conn, &format!("trustee:{}", trustee.name))
+                .await?;
+            let trustee = key_file.trustee(conn).await?;
+            println!("registered trustee {} ({})", trustee.name, trustee.id);
+            println!("public key: {}", trustee.public_key);
+            if let Some(share_index) = trustee.share_index {
+                println!("share index: {}", share_index);
+            }
+        }
+        TrusteeCommand::Deal { key_file } => {
+            let key_file = TrusteeKeyFile::read(&key_file)?;
+            let trustee = key_file.trustee(conn).await?;
+            let secret = elgamal::decode_scalar(&key_file.secret_key)?;
+
+            let dealt = trustee.deal(conn, &secret).await?;
+            println!("dealt {} shares", dealt);
+        }
+        TrusteeCommand::Finalize { key_file: path } => {
+            let mut key_file = TrusteeKeyFile::read(&path)?;
+            let trustee = key_file.trustee(conn).await?;
+            let secret = elgamal::decode_scalar(&key_file.secret_key)?;
+
+            let share_key = trustee.finalize(conn, &secret).await?;
+            key_file.share_key = Some(elgamal::encode_scalar(&share_key));
+            key_file.write(&path, false)?;
+            println!(
+                "verification key: {}",
+                elgamal::encode_point(&(share_key * RISTRETTO_BASEPOINT_POINT))
+            );
+        }
+        TrusteeCommand::Decrypt { key_file } => {
+            let key_file = TrusteeKeyFile::read(&key_file)?;
+            let trustee = key_file.trustee(conn).await?;
+            let secret = elgamal::decode_scalar(
+                key_file
+                    .share_key
+                    .as_deref()
+                    .unwrap_or(&key_file.secret_key),
+            )?;
+
+            let submitted = trustee.submit_decryptions(conn, &secret).await?;
+            println!("published {} decryption shares", submitted);
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn run_report(conn: &SqlitePool, command: ReportCommand) -> Result<(), anyhow::Error> {
+    match command {
+        ReportCommand::Generate {
+            election,
+            district,
+            section,
+            out,
+            text,
+            pdf,
+        } => {
+            let election = Election::find(conn, &election)
+                .await?
+                .ok_or_else(|| anyhow!("election not found"))?;
+            let bulletin = Bulletin::issue(conn, &election, &district, &section).await?;
+
+            let json = serde_json::to_string_pretty(&bulletin)?;
+            match out {
+                Some(out) => std::fs::write(out, json)?,
+                None => println!("{}", json),
+            }
+            if let Some(text) = text {
+                std::fs::write(text, bulletin.to_text())?;
+            }
+            if let Some(pdf) = pdf {
+                std::fs::write(pdf, bulletin.to_pdf())?;
+            }
+        }
+        ReportCommand::Verify {
+            file,
+            public_key,
+            recount,
+        } => {
+            let bulletin: Bulletin = serde_json::from_str(&std::fs::read_to_string(file)?)?;
+            bulletin.verify(public_key.as_deref())?;
+            println!("signature ok (key {})", bulletin.public_key);
+
+            if recount {
+                let election = Election::find(conn, &bulletin.election_id)
+                    .await?
+                    .ok_or_else(|| anyhow!("election not found"))?;
+                let counted =
+                    Bulletin::count(conn, &election, &bulletin.district, &bulletin.section).await?;
+                if !bulletin.matches(&counted) {
+                    return Err(anyhow!("bulletin does not match the recorded votes"));
+                }
+                println!("counts match the recorded votes");
+            }
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn run_results(conn: &SqlitePool, command: ResultsCommand) -> Result<(), anyhow::Error> {
+    match command {
+        ResultsCommand::Export {
+            format,
+            election,
+            year,
+            out,
+        } => {
+            let rows = ResultRow::list(conn, election.as_deref(), year, true).await?;
+            let export = ResultRow::export(&rows, &format)?;
+            match out {
+                Some(out) => std::fs::write(out, export)?,
+                None => io::stdout().write_all(&export)?,
+            }
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn run_ledger(conn: &SqlitePool, command: LedgerCommand) -> Result<(), anyhow::Error> {
+    match command {
+        LedgerCommand::Export { out } => {
+            let entries = LedgerEntry::list(conn).await?;
+            let head = LedgerHead::issue(conn, &entries).await?;
+            let export = LedgerEntry::to_jsonl(&entries, &head)?;
+            match out {
+                Some(out) => std::fs::write(out, export)?,
+                None => io::stdout().write_all(&export)?,
+            }
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn run_voters(conn: &SqlitePool, command: VotersCommand) -> Result<(), anyhow::Error> {
+    match command {
+        VotersCommand::Import {
+            file,
+            dry_run,
+            actor,
+        } => {
+            let report = Voter::import_csv(conn, &std::fs::read(file)?, &actor, dry_run).await?;
+            println!("{}", serde_json::to_string_pretty(&report)?);
+            if !report.errors.is_empty() {
+                return Err(anyhow!("{} errors, nothing imported", report.errors.len()));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// A random CPF with valid check digits.
+fn random_cpf() -> String {
+    let mut rng = rand::thread_rng();
+    let mut digits: Vec<u32> = (0..9).map(|_| rng.gen_range(0..10)).collect();
+    for length in [9, 10] {
+        let sum: u32 = digits
+            .iter()
+            .enumerate()
+            .map(|(i, digit)| digit * (length as u32 + 1 - i as u32))
+            .sum();
+        digits.push((sum * 10) % 11 % 10);
+    }
+    digits.iter().map(|digit| digit.to_string()).collect()
+}
+
+pub async fn run_repository(
+    repository: &dyn Repository,
+    command: RepositoryCommand,
+) -> Result<(), anyhow::Error> {
+    match command {
+        RepositoryCommand::Check {
+            party_number,
+            actor,
+        } => {
+            println!("backend: {}", repository.backend());
+            let year = chrono::Utc::now().year();
+
+            let mut party = Party::build(
+                "Partido de Verificação".to_string(),
+                "Created by bbox repository check".to_string(),
+                "PV".to_string(),
+            );
+            party.number = Some(party_number);
+            repository.create_party(&party, &actor).await?;
+            repository
+                .find_party(&party.id)
+                .await?
+                .ok_or_else(|| anyhow!("party not found after create"))?;
+            println!("party ok");
+
+            let mut candidate = Candidate::build("Ana".to_string(), "Souza".to_string());
+            candidate.birth_date = NaiveDate::from_ymd_opt(1970, 1, 1);
+            candidate.party_id = Some(party.id.clone());
+            repository.create_candidate(&candidate, &actor).await?;
+            repository
+                .find_candidate(&candidate.id)
+                .await?
+                .ok_or_else(|| anyhow!("candidate not found after create"))?;
+            println!("candidate ok");
+
+            let position = CandidaturePosition::Councilor;
+            let mut candidature = Candidature::build(
+                party.id.clone(),
+                candidate.id.clone(),
+                format!("{}001", party_number),
+                position.clone(),
+                String::new(),
+            );
+            candidature.district = Some("001".to_string());
+            repository
+                .create_candidature(&candidature, None, &actor)
+                .await?;
+            let found = repository
+                .find_candidature(&candidature.code, &position, Some("001"), year, None)
+                .await?;
+            if found.map(|found| found.id) != Some(candidature.id.clone()) {
+                return Err(anyhow!("candidature not found by code after create"));
+            }
+            let running = repository.list_candidatures(&position, year).await?;
+            if !running.iter().any(|running| running.id == candidature.id) {
+                return Err(anyhow!("candidature missing from the listing"));
+            }
+            println!("candidature ok");
+
+            let mut voter = Voter::build(
+                "Bruno".to_string(),
+                "Lima".to_string(),
+                "Carla".to_string(),
+                "Diego".to_string(),
+                NaiveDate::from_ymd_opt(1990, 1, 1).unwrap(),
+                Some("001".to_string()),
+                Some("0001".to_string()),
+            );
+            voter.national_id = Some(random_cpf());
+            repository.create_voter(&voter, &actor).await?;
+            repository
+                .find_voter(&voter.id)
+                .await?
+                .ok_or_else(|| anyhow!("voter not found after create"))?;
+            repository
+                .find_voter_by_national_id(voter.national_id.as_deref().unwrap_or_default())
+                .await?
+                .ok_or_else(|| anyhow!("voter not found by national id after create"))?;
+            println!("voter ok");
+
+            if repository.has_voted(&voter.id, &position, year).await? {
+                return Err(anyhow!("voter has voted before voting"));
+            }
+            let mut vote = Vote {
+                id: Uuid::now_v7().to_string(),
+                voter_id: Some(voter.id.clone()),
+                candidature_id: Some(candidature.id.clone()),
+                candidature_position: position.clone(),
+                hash: String::new(),
+                previous_hash: String::new(),
+                hash_version: CURRENT_HASH_VERSION,
+                year,
+                created_at: chrono::Utc::now().trunc_subsecs(6),
+                ballot_token: None,
+                election_id: None,
+                encrypted_ballot: None,
+                kind: VoteKind::Candidature,
+                district: voter.district.clone(),
+                section: voter.section.clone(),
+                key_id: None,
+            };
+            repository.create_vote(&mut vote).await?;
+            if !repository.has_voted(&voter.id, &position, year).await? {
+                return Err(anyhow!("vote not recorded"));
+            }
+            if repository.last_vote_hash().await? != vote.hash {
+                return Err(anyhow!("vote is not the tip of the chain"));
+            }
+            println!("vote ok");
+
+            let verified = Vote::verify(&repository.list_votes().await?)?;
+            println!("chain ok ({} votes)", verified);
+        }
+    }
+
+    Ok(())
+}
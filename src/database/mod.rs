@@ -0,0 +1,230 @@
+use std::{env, fmt::Display, fs, io, str::FromStr, time::Duration};
+
+use anyhow::anyhow;
+use serde::Deserialize;
+use sqlx::{
+    sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions, SqliteSynchronous},
+    SqlitePool,
+};
+
+const CONFIG_PATH: &str = "bbox.toml";
+
+/// Tuning of the SQLite pool, set in the `[database]` table of `bbox.toml`
+/// and overridden by the environment variables named below. The defaults
+/// favour concurrent writes on election day: WAL lets readers run alongside
+/// the single writer, and waiting writers retry for the busy timeout instead
+/// of failing with `database is locked`.
+#[derive(Debug, Clone)]
+pub struct DatabaseConfig {
+    /// `max_connections` or `DATABASE_MAX_CONNECTIONS`, 10 by default.
+    pub max_connections: u32,
+    /// `journal_mode` or `SQLITE_JOURNAL_MODE`, `WAL` by default.
+    pub journal_mode: SqliteJournalMode,
+    /// `synchronous` or `SQLITE_SYNCHRONOUS`, `NORMAL` by default, which is
+    /// durable in WAL mode except for the last transactions before a power
+    /// loss.
+    pub synchronous: SqliteSynchronous,
+    /// `busy_timeout_ms` or `SQLITE_BUSY_TIMEOUT_MS`, 5000 by default.
+    pub busy_timeout: Duration,
+    /// `foreign_keys` or `SQLITE_FOREIGN_KEYS`, `true` by default.
+    pub foreign_keys: bool,
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> DatabaseConfig {
+        DatabaseConfig {
+            max_connections: 10,
+            journal_mode: SqliteJournalMode::Wal,
+            synchronous: SqliteSynchronous::Normal,
+            busy_timeout: Duration::from_secs(5),
+            foreign_keys: true,
+        }
+    }
+}
+
+/// The `[database]` table of the config file. Every key is optional and
+/// named after its field in `DatabaseConfig`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct DatabaseFile {
+    max_connections: Option<u32>,
+    journal_mode: Option<String>,
+    synchronous: Option<String>,
+    busy_timeout_ms: Option<u64>,
+    foreign_keys: Option<bool>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ConfigFile {
+    database: DatabaseFile,
+}
+
+/// Reads `name`, falling back to `default` when it is unset.
+fn setting<T>(name: &str, default: T) -> Result<T, anyhow::Error>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    match env::var(name) {
+        Ok(value) => value
+            .parse()
+            .map_err(|reason| anyhow!("invalid {}: {}", name, reason)),
+        Err(_) => Ok(default),
+    }
+}
+
+/// Parses a string setting of the config file, falling back to `default`
+/// when it is absent.
+fn file_setting<T>(name: &str, value: Option<String>, default: T) -> Result<T, anyhow::Error>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    match value {
+        Some(value) => value
+            .parse()
+            .map_err(|reason| anyhow!("invalid database.{}: {}", name, reason)),
+        None => Ok(default),
+    }
+}
+
+impl DatabaseConfig {
+    /// Reads `bbox.toml`, or the file named by `BBOX_CONFIG`, then applies
+    /// the environment on top. Without `BBOX_CONFIG` the file is optional.
+    pub fn load() -> Result<DatabaseConfig, anyhow::Error> {
+        let (path, required) = match env::var("BBOX_CONFIG") {
+            Ok(path) => (path, true),
+            Err(_) => (CONFIG_PATH.to_string(), false),
+        };
+        let config = match fs::read_to_string(&path) {
+            Ok(contents) => DatabaseConfig::from_toml(&contents)
+                .map_err(|reason| anyhow!("invalid {}: {}", path, reason))?,
+            Err(reason) if reason.kind() == io::ErrorKind::NotFound && !required => {
+                DatabaseConfig::default()
+            }
+            Err(reason) => return Err(anyhow!("could not read {}: {}", path, reason)),
+        };
+
+        config.with_env()
+    }
+
+    /// Settings of the `[database]` table, defaults for the keys it omits.
+    pub fn from_toml(contents: &str) -> Result<DatabaseConfig, anyhow::Error> {
+        let file: ConfigFile = toml::from_str(contents)?;
+        let database = file.database;
+        let default = DatabaseConfig::default();
+
+        Ok(DatabaseConfig {
+            max_connections: database.max_connections.unwrap_or(default.max_connections),
+            journal_mode: file_setting(
+                "journal_mode",
+                database.journal_mode,
+                default.journal_mode,
+            )?,
+            synchronous: file_setting("synchronous", database.synchronous, default.synchronous)?,
+            busy_timeout: database
+                .busy_timeout_ms
+                .map(Duration::from_millis)
+                .unwrap_or(default.busy_timeout),
+            foreign_keys: database.foreign_keys.unwrap_or(default.foreign_keys),
+        })
+    }
+
+    /// Overrides each setting whose environment variable is set.
+    pub fn with_env(self) -> Result<DatabaseConfig, anyhow::Error> {
+        let max_connections = setting("DATABASE_MAX_CONNECTIONS", self.max_connections)?;
+        if max_connections == 0 {
+            return Err(anyhow!("DATABASE_MAX_CONNECTIONS must be at least 1"));
+        }
+
+        Ok(DatabaseConfig {
+            max_connections,
+            journal_mode: setting("SQLITE_JOURNAL_MODE", self.journal_mode)?,
+            synchronous: setting("SQLITE_SYNCHRONOUS", self.synchronous)?,
+            busy_timeout: Duration::from_millis(setting(
+                "SQLITE_BUSY_TIMEOUT_MS",
+                self.busy_timeout.as_millis() as u64,
+            )?),
+            foreign_keys: setting("SQLITE_FOREIGN_KEYS", self.foreign_keys)?,
+        })
+    }
+
+    pub fn connect_options(&self, database_url: &str) -> Result<SqliteConnectOptions, sqlx::Error> {
+        Ok(SqliteConnectOptions::from_str(database_url)?
+            .create_if_missing(true)
+            .journal_mode(self.journal_mode)
+            .synchronous(self.synchronous)
+            .busy_timeout(self.busy_timeout)
+            .foreign_keys(self.foreign_keys))
+    }
+
+    pub async fn connect(&self, database_url: &str) -> Result<SqlitePool, sqlx::Error> {
+        SqlitePoolOptions::new()
+            .max_connections(self.max_connections)
+            .connect_with(self.connect_options(database_url)?)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_the_database_table() {
+        let config = DatabaseConfig::from_toml(
+            r#"
+            [database]
+            max_connections = 4
+            journal_mode = "delete"
+            busy_timeout_ms = 250
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.max_connections, 4);
+        assert!(matches!(config.journal_mode, SqliteJournalMode::Delete));
+        assert!(matches!(config.synchronous, SqliteSynchronous::Normal));
+        assert_eq!(config.busy_timeout, Duration::from_millis(250));
+        assert!(config.foreign_keys);
+
+        let config = DatabaseConfig::from_toml("").unwrap();
+        assert_eq!(config.max_connections, 10);
+
+        let reason =
+            DatabaseConfig::from_toml("[database]\nsynchronous = \"sometimes\"").unwrap_err();
+        assert!(reason
+            .to_string()
+            .starts_with("invalid database.synchronous"));
+        assert!(DatabaseConfig::from_toml("[database]\npool_size = 4").is_err());
+    }
+
+    // The only test touching these variables, as the environment is shared
+    // by the tests running at once.
+    #[test]
+    fn environment_overrides_the_file() {
+        let config = DatabaseConfig::from_toml("[database]\nmax_connections = 4").unwrap();
+
+        env::set_var("DATABASE_MAX_CONNECTIONS", "2");
+        env::set_var("SQLITE_FOREIGN_KEYS", "false");
+        env::set_var("SQLITE_BUSY_TIMEOUT_MS", "100");
+        let overridden = config.clone().with_env().unwrap();
+        assert_eq!(overridden.max_connections, 2);
+        assert!(!overridden.foreign_keys);
+        assert_eq!(overridden.busy_timeout, Duration::from_millis(100));
+        assert!(matches!(overridden.journal_mode, SqliteJournalMode::Wal));
+
+        env::set_var("DATABASE_MAX_CONNECTIONS", "0");
+        assert!(config.clone().with_env().is_err());
+        env::set_var("DATABASE_MAX_CONNECTIONS", "many");
+        let reason = config.clone().with_env().unwrap_err();
+        assert!(reason
+            .to_string()
+            .starts_with("invalid DATABASE_MAX_CONNECTIONS"));
+
+        env::remove_var("DATABASE_MAX_CONNECTIONS");
+        env::remove_var("SQLITE_FOREIGN_KEYS");
+        env::remove_var("SQLITE_BUSY_TIMEOUT_MS");
+        assert_eq!(config.with_env().unwrap().max_connections, 4);
+    }
+}
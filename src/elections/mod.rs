@@ -0,0 +1,830 @@
+use std::{collections::BTreeMap, fmt};
+
+use anyhow::anyhow;
+use chrono::NaiveDate;
+use curve25519_dalek::{ristretto::RistrettoPoint, traits::Identity};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sqlx::SqlitePool;
+use uuid::Uuid;
+
+use crate::{
+    bulletins,
+    elgamal::{self, Ciphertext, EncodedCiphertext},
+    trustees::ceremony,
+    AuditEvent, Bulletin, Trustee,
+};
+
+/// Voting is optional from 16 in Brazil.
+pub const DEFAULT_MINIMUM_VOTING_AGE: i32 = 16;
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub enum TallyMode {
+    /// Votes reference their candidature and are counted directly.
+    #[serde(rename = "plain")]
+    Plain,
+    /// Votes carry an ElGamal ballot and only the trustees can reveal totals.
+    #[serde(rename = "encrypted")]
+    Encrypted,
+}
+
+impl fmt::Display for TallyMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mode = match self {
+            TallyMode::Plain => "plain",
+            TallyMode::Encrypted => "encrypted",
+        };
+        write!(f, "{}", mode)
+    }
+}
+
+impl From<String> for TallyMode {
+    fn from(mode: String) -> TallyMode {
+        match mode.as_str() {
+            "encrypted" => TallyMode::Encrypted,
+            _ => TallyMode::Plain,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub enum ElectionStatus {
+    /// Trustees can still be registered; no votes are accepted.
+    #[serde(rename = "setup")]
+    Setup,
+    #[serde(rename = "open")]
+    Open,
+    #[serde(rename = "closed")]
+    Closed,
+    /// Results have been checked and signed off; nothing changes after this.
+    #[serde(rename = "certified")]
+    Certified,
+}
+
+impl fmt::Display for ElectionStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let status = match self {
+            ElectionStatus::Setup => "setup",
+            ElectionStatus::Open => "open",
+            ElectionStatus::Closed => "closed",
+            ElectionStatus::Certified => "certified",
+        };
+        write!(f, "{}", status)
+    }
+}
+
+impl From<String> for ElectionStatus {
+    fn from(status: String) -> ElectionStatus {
+        match status.as_str() {
+            "open" => ElectionStatus::Open,
+            "closed" => ElectionStatus::Closed,
+            "certified" => ElectionStatus::Certified,
+            _ => ElectionStatus::Setup,
+        }
+    }
+}
+
+/// When the public may see counts. Admins always can.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Default)]
+pub enum ResultsVisibility {
+    #[default]
+    #[serde(rename = "live")]
+    Live,
+    #[serde(rename = "hidden_until_close")]
+    HiddenUntilClose,
+    #[serde(rename = "hidden_until_certified")]
+    HiddenUntilCertified,
+}
+
+impl fmt::Display for ResultsVisibility {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let visibility = match self {
+            ResultsVisibility::Live => "live",
+            ResultsVisibility::HiddenUntilClose => "hidden_until_close",
+            ResultsVisibility::HiddenUntilCertified => "hidden_until_certified",
+        };
+        write!(f, "{}", visibility)
+    }
+}
+
+impl From<String> for ResultsVisibility {
+    fn from(visibility: String) -> ResultsVisibility {
+        match visibility.as_str() {
+            "hidden_until_close" => ResultsVisibility::HiddenUntilClose,
+            "hidden_until_certified" => ResultsVisibility::HiddenUntilCertified,
+            _ => ResultsVisibility::Live,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Election {
+    pub id: String,
+    pub name: String,
+    pub year: i32,
+    pub tally_mode: TallyMode,
+    pub status: ElectionStatus,
+    pub public_key: Option<String>,
+    pub threshold: Option<i32>,
+    pub trustee_count: Option<i32>,
+    pub results_visibility: ResultsVisibility,
+    /// Day the vote takes place; voter age is checked as of this date, or
+    /// as of today when it is not set.
+    pub election_date: Option<NaiveDate>,
+    pub minimum_voting_age: i32,
+    /// 1, or 2 for a runoff (segundo turno), which is registered as an
+    /// election of its own.
+    pub round: i32,
+}
+
+impl Election {
+    pub fn build(name: String, year: i32, tally_mode: TallyMode) -> Election {
+        Election {
+            id: Uuid::now_v7().to_string(),
+            name,
+            year,
+            tally_mode,
+            status: ElectionStatus::Setup,
+            public_key: None,
+            threshold: None,
+            trustee_count: None,
+            results_visibility: ResultsVisibility::Live,
+            election_date: None,
+            minimum_voting_age: DEFAULT_MINIMUM_VOTING_AGE,
+            round: 1,
+        }
+    }
+
+    pub async fn create(&self, conn: &SqlitePool, actor: &str) -> Result<(), anyhow::Error> {
+        if self.minimum_voting_age < 0 {
+            return Err(anyhow!("minimum voting age must not be negative"));
+        }
+        if !(1..=2).contains(&self.round) {
+            return Err(anyhow!("round must be 1 or 2"));
+        }
+        match (self.threshold, self.trustee_count) {
+            (None, None) => {}
+            (Some(threshold), Some(trustee_count)) => {
+                if self.tally_mode != TallyMode::Encrypted {
+                    return Err(anyhow!("only encrypted elections have a threshold"));
+                }
+                if threshold < 1 || threshold > trustee_count {
+                    return Err(anyhow!("threshold must be between 1 and the trustee count"));
+                }
+            }
+            _ => return Err(anyhow!("threshold and trustee count go together")),
+        }
+
+        let tally_mode = self.tally_mode.to_string();
+        let status = self.status.to_string();
+        let results_visibility = self.results_visibility.to_string();
+        let mut tx = conn.begin().await?;
+        sqlx::query!(
+            r#"
+            INSERT INTO elections (id, name, year, tally_mode, status, public_key, threshold, trustee_count, results_visibility, election_date, minimum_voting_age, round)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#,
+            self.id,
+            self.name,
+            self.year,
+            tally_mode,
+            status,
+            self.public_key,
+            self.threshold,
+            self.trustee_count,
+            results_visibility,
+            self.election_date,
+            self.minimum_voting_age,
+            self.round,
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        AuditEvent::record(
+            &mut tx,
+            "election",
+            &self.id,
+            "create",
+            actor,
+            None,
+            serde_json::to_value(self).ok(),
+        )
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    pub async fn find(conn: &SqlitePool, id: &str) -> Result<Option<Election>, sqlx::Error> {
+        let record = sqlx::query_as!(
+            ElectionRecord,
+            r#"
+            SELECT
+                id AS "id!: String",
+                name,
+                year AS "year: i32",
+                tally_mode,
+                status,
+                public_key,
+                threshold AS "threshold: i32",
+                trustee_count AS "trustee_count: i32",
+                results_visibility,
+                election_date,
+                minimum_voting_age AS "minimum_voting_age: i32",
+                round AS "round: i32"
+            FROM
+                elections
+            WHERE
+                id = ?
+            "#,
+            id
+        )
+        .fetch_optional(conn)
+        .await?;
+
+        Ok(record.map(Election::from))
+    }
+
+    /// The most recently created election of a year, which is the one votes
+    /// for that year are cast in.
+    pub async fn current(conn: &SqlitePool, year: i32) -> Result<Option<Election>, sqlx::Error> {
+        let record = sqlx::query_as!(
+            ElectionRecord,
+            r#"
+            SELECT
+                id AS "id!: String",
+                name,
+                year AS "year: i32",
+                tally_mode,
+                status,
+                public_key,
+                threshold AS "threshold: i32",
+                trustee_count AS "trustee_count: i32",
+                results_visibility,
+                election_date,
+                minimum_voting_age AS "minimum_voting_age: i32",
+                round AS "round: i32"
+            FROM
+                elections
+            WHERE
+                year = ?
+            ORDER BY
+                created_at DESC,
+                id DESC
+            LIMIT 1
+            "#,
+            year
+        )
+        .fetch_optional(conn)
+        .await?;
+
+        Ok(record.map(Election::from))
+    }
+
+    pub async fn list(conn: &SqlitePool) -> Result<Vec<Election>, sqlx::Error> {
+        let records = sqlx::query_as!(
+            ElectionRecord,
+            r#"
+            SELECT
+                id AS "id!: String",
+                name,
+                year AS "year: i32",
+                tally_mode,
+                status,
+                public_key,
+                threshold AS "threshold: i32",
+                trustee_count AS "trustee_count: i32",
+                results_visibility,
+                election_date,
+                minimum_voting_age AS "minimum_voting_age: i32",
+                round AS "round: i32"
+            FROM
+                elections
+            ORDER BY
+                year DESC,
+                created_at DESC
+            "#
+        )
+        .fetch_all(conn)
+        .await?;
+
+        Ok(records.into_iter().map(Election::from).collect())
+    }
+
+    /// Rejects voters younger than the minimum voting age on election day.
+    pub fn ensure_voting_age(&self, birth_date: NaiveDate) -> Result<(), anyhow::Error> {
+        let election_date = self
+            .election_date
+            .unwrap_or_else(|| chrono::Utc::now().date_naive());
+        let age = election_date.years_since(birth_date).unwrap_or(0);
+        if age < self.minimum_voting_age as u32 {
+            return Err(anyhow!(
+                "voter must be at least {} years old on election day",
+                self.minimum_voting_age
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn public_key_point(&self) -> Result<RistrettoPoint, anyhow::Error> {
+        let public_key = self
+            .public_key
+            .as_deref()
+            .ok_or_else(|| anyhow!("election has no public key"))?;
+        elgamal::decode_point(public_key)
+    }
+
+    async fn update(
+        &self,
+        conn: &SqlitePool,
+        actor: &str,
+        before: &Election,
+    ) -> Result<(), sqlx::Error> {
+        let status = self.status.to_string();
+        let mut tx = conn.begin().await?;
+        sqlx::query!(
+            r#"
+            UPDATE elections
+            SET status = ?, public_key = ?, updated_at = CURRENT_TIMESTAMP
+            WHERE id = ?
+            "#,
+            status,
+            self.public_key,
+            self.id,
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        AuditEvent::record(
+            &mut tx,
+            "election",
+            &self.id,
+            "update",
+            actor,
+            serde_json::to_value(before).ok(),
+            serde_json::to_value(self).ok(),
+        )
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// Opens the polls. For encrypted elections this fixes the public key:
+    /// with a threshold it is the one produced by the trustees' key ceremony,
+    /// otherwise the sum of the registered trustees' keys, so every trustee is
+    /// needed to decrypt.
+    pub async fn open(&self, conn: &SqlitePool, actor: &str) -> Result<Election, anyhow::Error> {
+        if self.status != ElectionStatus::Setup {
+            return Err(anyhow!("election is not in setup"));
+        }
+
+        let mut election = self.clone();
+        election.status = ElectionStatus::Open;
+
+        if self.tally_mode == TallyMode::Encrypted {
+            let trustees = Trustee::list(conn, &self.id).await?;
+            if trustees.is_empty() {
+                return Err(anyhow!("encrypted elections need at least one trustee"));
+            }
+            let public_key = match self.trustee_count {
+                Some(trustee_count) => {
+                    self.ceremony_public_key(conn, &trustees, trustee_count)
+                        .await?
+                }
+                None => {
+                    let mut public_key = RistrettoPoint::identity();
+                    for trustee in trustees.iter() {
+                        trustee.verify_key_proof()?;
+                        public_key += elgamal::decode_point(&trustee.public_key)?;
+                    }
+                    public_key
+                }
+            };
+            election.public_key = Some(elgamal::encode_point(&public_key));
+        }
+
+        election.update(conn, actor, self).await?;
+
+        Ok(election)
+    }
+
+    /// Checks that the key ceremony completed and that every trustee's
+    /// verification key matches the published commitments, then derives the
+    /// election key from them.
+    async fn ceremony_public_key(
+        &self,
+        conn: &SqlitePool,
+        trustees: &[Trustee],
+        trustee_count: i32,
+    ) -> Result<RistrettoPoint, anyhow::Error> {
+        if trustees.len() != trustee_count as usize {
+            return Err(anyhow!(
+                "{} of {} trustees registered",
+                trustees.len(),
+                trustee_count
+            ));
+        }
+        let dealers = Trustee::commitments(conn, &self.id).await?;
+        if dealers.len() != trustees.len() {
+            return Err(anyhow!(
+                "{} of {} trustees dealt",
+                dealers.len(),
+                trustees.len()
+            ));
+        }
+
+        for trustee in trustees.iter() {
+            let index = trustee
+                .share_index
+                .ok_or_else(|| anyhow!("trustee {} has no share index", trustee.name))?;
+            let verification_key = trustee
+                .verification_key
+                .as_deref()
+                .ok_or_else(|| anyhow!("trustee {} has not finalized", trustee.name))?;
+            if elgamal::decode_point(verification_key)?
+                != ceremony::verification_key(&dealers, index as u64)
+            {
+                return Err(anyhow!(
+                    "verification key of trustee {} does not match the commitments",
+                    trustee.name
+                ));
+            }
+        }
+
+        Ok(ceremony::election_public_key(&dealers))
+    }
+
+    /// Closes the polls. Plain elections issue the bulletin of each polling
+    /// section right away, so the signing key is checked before closing.
+    pub async fn close(&self, conn: &SqlitePool, actor: &str) -> Result<Election, anyhow::Error> {
+        if self.status != ElectionStatus::Open {
+            return Err(anyhow!("election is not open"));
+        }
+        if self.tally_mode == TallyMode::Plain {
+            bulletins::signing_key()?;
+        }
+
+        let mut election = self.clone();
+        election.status = ElectionStatus::Closed;
+        election.update(conn, actor, self).await?;
+
+        if election.tally_mode == TallyMode::Plain {
+            Bulletin::issue_all(conn, &election).await?;
+        }
+
+        Ok(election)
+    }
+
+    /// Signs off the results of a closed election. Encrypted elections must
+    /// have been tallied first, since their counts only exist afterwards.
+    pub async fn certify(&self, conn: &SqlitePool, actor: &str) -> Result<Election, anyhow::Error> {
+        if self.status != ElectionStatus::Closed {
+            return Err(anyhow!("election is not closed"));
+        }
+        if self.tally_mode == TallyMode::Encrypted {
+            let tallied = sqlx::query!(
+                r#"SELECT election_id AS "election_id!: String" FROM election_results WHERE election_id = ?"#,
+                self.id
+            )
+            .fetch_optional(conn)
+            .await?;
+            if tallied.is_none() {
+                return Err(anyhow!("election has not been tallied"));
+            }
+        }
+
+        let mut election = self.clone();
+        election.status = ElectionStatus::Certified;
+        election.update(conn, actor, self).await?;
+
+        Ok(election)
+    }
+
+    /// Why the public may not see this election's counts yet, if it may not.
+    pub fn results_embargo(&self) -> Option<&'static str> {
+        match (&self.results_visibility, &self.status) {
+            (ResultsVisibility::Live, _) => None,
+            (
+                ResultsVisibility::HiddenUntilClose,
+                ElectionStatus::Closed | ElectionStatus::Certified,
+            ) => None,
+            (ResultsVisibility::HiddenUntilCertified, ElectionStatus::Certified) => None,
+            (ResultsVisibility::HiddenUntilClose, _) => {
+                Some("results are hidden until the polls close")
+            }
+            (ResultsVisibility::HiddenUntilCertified, _) => {
+                Some("results are hidden until the election is certified")
+            }
+        }
+    }
+
+    /// Adds an encrypted ballot to the running per-candidature tallies. Runs
+    /// in the caller's transaction so the tally moves with the vote insert.
+    pub async fn accumulate(
+        conn: &mut sqlx::SqliteConnection,
+        election_id: &str,
+        ballot: &BTreeMap<String, EncodedCiphertext>,
+    ) -> Result<(), anyhow::Error> {
+        for (candidature_id, encoded) in ballot.iter() {
+            let current = sqlx::query_as!(
+                EncodedCiphertext,
+                r#"
+                SELECT
+                    c1,
+                    c2
+                FROM
+                    encrypted_tallies
+                WHERE
+                    election_id = ? AND
+                    candidature_id = ?
+                "#,
+                election_id,
+                candidature_id
+            )
+            .fetch_optional(&mut *conn)
+            .await?;
+
+            let current = match current {
+                Some(current) => Ciphertext::decode(&current)?,
+                None => Ciphertext::zero(),
+            };
+            let tally = (current + Ciphertext::decode(encoded)?).encode();
+
+            sqlx::query!(
+                r#"
+                INSERT INTO encrypted_tallies (election_id, candidature_id, c1, c2)
+                VALUES (?, ?, ?, ?)
+                ON CONFLICT (election_id, candidature_id)
+                DO UPDATE SET c1 = excluded.c1, c2 = excluded.c2
+                "#,
+                election_id,
+                candidature_id,
+                tally.c1,
+                tally.c2,
+            )
+            .execute(&mut *conn)
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    pub async fn encrypted_tallies(
+        conn: &SqlitePool,
+        election_id: &str,
+    ) -> Result<Vec<(String, Ciphertext)>, anyhow::Error> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT
+                candidature_id AS "candidature_id!: String",
+                c1,
+                c2
+            FROM
+                encrypted_tallies
+            WHERE
+                election_id = ?
+            "#,
+            election_id
+        )
+        .fetch_all(conn)
+        .await?;
+
+        rows.into_iter()
+            .map(|row| {
+                let ciphertext = Ciphertext::decode(&EncodedCiphertext {
+                    c1: row.c1,
+                    c2: row.c2,
+                })?;
+                Ok((row.candidature_id, ciphertext))
+            })
+            .collect()
+    }
+
+    /// Combines the trustees' decryption shares once the polls are closed and
+    /// publishes the plaintext totals to `election_results`. Threshold
+    /// elections need shares from any `threshold` trustees, interpolated with
+    /// Lagrange coefficients; otherwise every trustee must take part.
+    pub async fn tally(&self, conn: &SqlitePool, actor: &str) -> Result<Vec<Value>, anyhow::Error> {
+        if self.tally_mode != TallyMode::Encrypted {
+            return Err(anyhow!("only encrypted elections are tallied by trustees"));
+        }
+        if self.status != ElectionStatus::Closed {
+            return Err(anyhow!("election is not closed"));
+        }
+
+        let trustees = Trustee::list(conn, &self.id).await?;
+        let total_votes = sqlx::query_scalar!(
+            r#"SELECT COUNT(id) AS "count!: i64" FROM votes WHERE election_id = ?"#,
+            self.id
+        )
+        .fetch_one(conn)
+        .await?;
+
+        let mut results = Vec::new();
+        for (candidature_id, ciphertext) in Election::encrypted_tallies(conn, &self.id).await? {
+            let mut shares = Vec::new();
+            for trustee in trustees.iter() {
+                let Some(share) = trustee.decryption_share(conn, &candidature_id).await? else {
+                    continue;
+                };
+                if !share.verify(&trustee.decryption_key()?, &ciphertext) {
+                    return Err(anyhow!(
+                        "invalid decryption share from trustee {}",
+                        trustee.name
+                    ));
+                }
+                shares.push((trustee, share));
+            }
+
+            let combined = match self.threshold {
+                Some(threshold) => {
+                    if shares.len() < threshold as usize {
+                        return Err(anyhow!(
+                            "waiting for trustees: {} of {} shares",
+                            shares.len(),
+                            threshold
+                        ));
+                    }
+                    shares.truncate(threshold as usize);
+                    let indices = shares
+                        .iter()
+                        .map(|(trustee, _)| {
+                            trustee
+                                .share_index
+                                .map(|index| index as u64)
+                                .ok_or_else(|| {
+                                    anyhow!("trustee {} has no share index", trustee.name)
+                                })
+                        })
+                        .collect::<Result<Vec<u64>, anyhow::Error>>()?;
+                    shares
+                        .iter()
+                        .zip(indices.iter())
+                        .map(|((_, share), index)| {
+                            ceremony::lagrange_coefficient(*index, &indices) * share.share
+                        })
+                        .sum()
+                }
+                None => {
+                    if let Some(missing) = trustees
+                        .iter()
+                        .find(|trustee| !shares.iter().any(|(shared, _)| shared.id == trustee.id))
+                    {
+                        return Err(anyhow!("waiting for trustee {}", missing.name));
+                    }
+                    shares.iter().map(|(_, share)| share.share).sum()
+                }
+            };
+
+            let votes = ciphertext
+                .decrypt_with(&combined, total_votes as u64)
+                .ok_or_else(|| anyhow!("could not decrypt tally of {}", candidature_id))?;
+            results.push((candidature_id, votes));
+        }
+
+        let mut tx = conn.begin().await?;
+        for (candidature_id, votes) in results.iter() {
+            let votes = *votes as i64;
+            sqlx::query!(
+                r#"
+                INSERT INTO election_results (election_id, candidature_id, votes)
+                VALUES (?, ?, ?)
+                ON CONFLICT (election_id, candidature_id)
+                DO UPDATE SET votes = excluded.votes
+                "#,
+                self.id,
+                candidature_id,
+                votes,
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        let summary: Vec<Value> = results
+            .iter()
+            .map(|(candidature_id, votes)| {
+                json!({
+                    "candidature_id": candidature_id,
+                    "votes": votes,
+                })
+            })
+            .collect();
+
+        AuditEvent::record(
+            &mut tx,
+            "election",
+            &self.id,
+            "tally",
+            actor,
+            None,
+            Some(json!(summary)),
+        )
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(summary)
+    }
+}
+
+/// An `elections` row, with the modes as stored.
+struct ElectionRecord {
+    id: String,
+    name: String,
+    year: i32,
+    tally_mode: String,
+    status: String,
+    public_key: Option<String>,
+    threshold: Option<i32>,
+    trustee_count: Option<i32>,
+    results_visibility: String,
+    election_date: Option<NaiveDate>,
+    minimum_voting_age: i32,
+    round: i32,
+}
+
+impl From<ElectionRecord> for Election {
+    fn from(record: ElectionRecord) -> Election {
+        Election {
+            id: record.id,
+            name: record.name,
+            year: record.year,
+            tally_mode: TallyMode::from(record.tally_mode),
+            status: ElectionStatus::from(record.status),
+            public_key: record.public_key,
+            threshold: record.threshold,
+            trustee_count: record.trustee_count,
+            results_visibility: ResultsVisibility::from(record.results_visibility),
+            election_date: record.election_date,
+            minimum_voting_age: record.minimum_voting_age,
+            round: record.round,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Datelike;
+
+    use super::*;
+    use crate::{
+        testing::{self, test_pool},
+        CandidaturePosition, SqliteRepository, Vote, VoteCredential, VoteKind, Voter,
+    };
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn counts_the_voting_age_on_election_day() {
+        let mut election = Election::build("Eleições 2026".to_string(), 2026, TallyMode::Plain);
+        election.election_date = Some(date(2026, 10, 4));
+
+        assert!(election.ensure_voting_age(date(2010, 10, 4)).is_ok());
+        let reason = election.ensure_voting_age(date(2010, 10, 5)).unwrap_err();
+        assert_eq!(
+            reason.to_string(),
+            "voter must be at least 16 years old on election day"
+        );
+        // Born after the election.
+        assert!(election.ensure_voting_age(date(2027, 1, 1)).is_err());
+
+        election.minimum_voting_age = 18;
+        assert!(election.ensure_voting_age(date(2010, 10, 4)).is_err());
+        assert!(election.ensure_voting_age(date(2008, 10, 4)).is_ok());
+    }
+
+    #[tokio::test]
+    async fn rejects_ballots_of_voters_under_age() {
+        let conn = test_pool().await;
+        let today = chrono::Utc::now().date_naive();
+        testing::open_election(&conn, today.year(), 1).await;
+
+        let voter = Voter::build(
+            "Lucas".to_string(),
+            "Silva".to_string(),
+            "Ana".to_string(),
+            "José".to_string(),
+            today.with_year(today.year() - 15).unwrap_or(today),
+            None,
+            None,
+        );
+        voter.create(&conn, testing::ACTOR).await.unwrap();
+
+        let reason = Vote::build(
+            &conn,
+            &SqliteRepository::new(conn.clone()),
+            VoteCredential::Voter(voter.id.clone()),
+            VoteKind::Blank,
+            None,
+            CandidaturePosition::President,
+        )
+        .await
+        .unwrap_err();
+        assert!(reason.to_string().contains("at least 16 years old"));
+    }
+}
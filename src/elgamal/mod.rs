@@ -0,0 +1,310 @@
+use std::{collections::HashMap, ops::Add};
+
+use anyhow::anyhow;
+use curve25519_dalek::{
+    constants::RISTRETTO_BASEPOINT_POINT,
+    ristretto::{CompressedRistretto, RistrettoPoint},
+    scalar::Scalar,
+    traits::Identity,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha512};
+
+const DLEQ_DOMAIN: &[u8] = b"bbox/elgamal/dleq/v1";
+const KEY_PROOF_DOMAIN: &[u8] = b"bbox/elgamal/key-proof/v1";
+
+/// Exponential ElGamal ciphertext over Ristretto: `(rG, mG + rY)`. Adding two
+/// ciphertexts adds their plaintexts, which is what lets tallies accumulate
+/// without ever being decrypted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ciphertext {
+    pub c1: RistrettoPoint,
+    pub c2: RistrettoPoint,
+}
+
+/// Hex form of a ciphertext, as stored in the database and in ballots.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EncodedCiphertext {
+    pub c1: String,
+    pub c2: String,
+}
+
+/// A trustee's share `xC1` of a decryption together with a Chaum-Pedersen
+/// proof that it used the same `x` as its public key `xG`.
+#[derive(Debug, Clone, Copy)]
+pub struct DecryptionShare {
+    pub share: RistrettoPoint,
+    pub challenge: Scalar,
+    pub response: Scalar,
+}
+
+/// Schnorr proof that whoever published the public key `xG` knows `x`, bound
+/// to a context such as the election the key is registered for.
+#[derive(Debug, Clone, Copy)]
+pub struct KeyProof {
+    pub challenge: Scalar,
+    pub response: Scalar,
+}
+
+pub fn encode_point(point: &RistrettoPoint) -> String {
+    hex::encode(point.compress().as_bytes())
+}
+
+pub fn decode_point(value: &str) -> Result<RistrettoPoint, anyhow::Error> {
+    let bytes = hex::decode(value)?;
+    CompressedRistretto::from_slice(&bytes)?
+        .decompress()
+        .ok_or_else(|| anyhow!("invalid ristretto point"))
+}
+
+pub fn encode_scalar(scalar: &Scalar) -> String {
+    hex::encode(scalar.as_bytes())
+}
+
+pub fn decode_scalar(value: &str) -> Result<Scalar, anyhow::Error> {
+    let bytes: [u8; 32] = hex::decode(value)?
+        .try_into()
+        .map_err(|_| anyhow!("invalid scalar length"))?;
+    Option::from(Scalar::from_canonical_bytes(bytes)).ok_or_else(|| anyhow!("invalid scalar"))
+}
+
+pub fn generate_keypair() -> (Scalar, RistrettoPoint) {
+    let secret = Scalar::random(&mut rand::thread_rng());
+    (secret, secret * RISTRETTO_BASEPOINT_POINT)
+}
+
+impl Ciphertext {
+    pub fn zero() -> Ciphertext {
+        Ciphertext {
+            c1: RistrettoPoint::identity(),
+            c2: RistrettoPoint::identity(),
+        }
+    }
+
+    pub fn encrypt(public_key: &RistrettoPoint, message: u64) -> Ciphertext {
+        let r = Scalar::random(&mut rand::thread_rng());
+        Ciphertext {
+            c1: r * RISTRETTO_BASEPOINT_POINT,
+            c2: Scalar::from(message) * RISTRETTO_BASEPOINT_POINT + r * public_key,
+        }
+    }
+
+    pub fn encode(&self) -> EncodedCiphertext {
+        EncodedCiphertext {
+            c1: encode_point(&self.c1),
+            c2: encode_point(&self.c2),
+        }
+    }
+
+    pub fn decode(encoded: &EncodedCiphertext) -> Result<Ciphertext, anyhow::Error> {
+        Ok(Ciphertext {
+            c1: decode_point(&encoded.c1)?,
+            c2: decode_point(&encoded.c2)?,
+        })
+    }
+
+    /// Removes the combined decryption shares and recovers the plaintext by
+    /// searching `0..=max`, which is feasible because tallies are small.
+    pub fn decrypt_with(&self, combined_share: &RistrettoPoint, max: u64) -> Option<u64> {
+        discrete_log(&(self.c2 - combined_share), max)
+    }
+}
+
+impl Add for Ciphertext {
+    type Output = Ciphertext;
+
+    fn add(self, other: Ciphertext) -> Ciphertext {
+        Ciphertext {
+            c1: self.c1 + other.c1,
+            c2: self.c2 + other.c2,
+        }
+    }
+}
+
+fn dleq_challenge(points: &[&RistrettoPoint]) -> Scalar {
+    let mut hasher = Sha512::new();
+    hasher.update(DLEQ_DOMAIN);
+    for point in points {
+        hasher.update(point.compress().as_bytes());
+    }
+    Scalar::from_bytes_mod_order_wide(&hasher.finalize().into())
+}
+
+impl DecryptionShare {
+    pub fn compute(secret: &Scalar, ciphertext: &Ciphertext) -> DecryptionShare {
+        let public_key = secret * RISTRETTO_BASEPOINT_POINT;
+        let share = secret * ciphertext.c1;
+
+        let nonce = Scalar::random(&mut rand::thread_rng());
+        let a = nonce * RISTRETTO_BASEPOINT_POINT;
+        let b = nonce * ciphertext.c1;
+        let challenge = dleq_challenge(&[&public_key, &ciphertext.c1, &share, &a, &b]);
+
+        DecryptionShare {
+            share,
+            challenge,
+            response: nonce + challenge * secret,
+        }
+    }
+
+    pub fn verify(&self, public_key: &RistrettoPoint, ciphertext: &Ciphertext) -> bool {
+        let a = self.response * RISTRETTO_BASEPOINT_POINT - self.challenge * public_key;
+        let b = self.response * ciphertext.c1 - self.challenge * self.share;
+        dleq_challenge(&[public_key, &ciphertext.c1, &self.share, &a, &b]) == self.challenge
+    }
+}
+
+fn key_proof_challenge(
+    context: &[u8],
+    public_key: &RistrettoPoint,
+    commitment: &RistrettoPoint,
+) -> Scalar {
+    let mut hasher = Sha512::new();
+    hasher.update(KEY_PROOF_DOMAIN);
+    hasher.update((context.len() as u64).to_be_bytes());
+    hasher.update(context);
+    hasher.update(public_key.compress().as_bytes());
+    hasher.update(commitment.compress().as_bytes());
+    Scalar::from_bytes_mod_order_wide(&hasher.finalize().into())
+}
+
+impl KeyProof {
+    pub fn prove(secret: &Scalar, context: &[u8]) -> KeyProof {
+        let public_key = secret * RISTRETTO_BASEPOINT_POINT;
+        let nonce = Scalar::random(&mut rand::thread_rng());
+        let commitment = nonce * RISTRETTO_BASEPOINT_POINT;
+        let challenge = key_proof_challenge(context, &public_key, &commitment);
+
+        KeyProof {
+            challenge,
+            response: nonce + challenge * secret,
+        }
+    }
+
+    pub fn verify(&self, public_key: &RistrettoPoint, context: &[u8]) -> bool {
+        let commitment = self.response * RISTRETTO_BASEPOINT_POINT - self.challenge * public_key;
+        key_proof_challenge(context, public_key, &commitment) == self.challenge
+    }
+}
+
+/// Baby-step giant-step search for `m` with `mG == point` and `m <= max`.
+pub fn discrete_log(point: &RistrettoPoint, max: u64) -> Option<u64> {
+    let step = ((max + 1) as f64).sqrt().ceil() as u64;
+    let step = step.max(1);
+
+    let mut baby_steps = HashMap::with_capacity(step as usize);
+    let mut current = RistrettoPoint::identity();
+    for j in 0..step {
+        baby_steps.insert(current.compress(), j);
+        current += RISTRETTO_BASEPOINT_POINT;
+    }
+
+    let giant_step = Scalar::from(step) * RISTRETTO_BASEPOINT_POINT;
+    let mut current = *point;
+    for i in 0..=step {
+        if let Some(j) = baby_steps.get(&current.compress()) {
+            let m = i * step + j;
+            return (m <= max).then_some(m);
+        }
+        current -= giant_step;
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decrypt(secret: &Scalar, ciphertext: &Ciphertext, max: u64) -> Option<u64> {
+        ciphertext.decrypt_with(&(secret * ciphertext.c1), max)
+    }
+
+    #[test]
+    fn decrypts_what_it_encrypts() {
+        let (secret, public_key) = generate_keypair();
+        for message in [0, 1, 2, 41, 1000] {
+            let ciphertext = Ciphertext::encrypt(&public_key, message);
+            assert_eq!(decrypt(&secret, &ciphertext, 1000), Some(message));
+        }
+    }
+
+    #[test]
+    fn encryption_is_randomized() {
+        let (_, public_key) = generate_keypair();
+        assert_ne!(
+            Ciphertext::encrypt(&public_key, 1),
+            Ciphertext::encrypt(&public_key, 1)
+        );
+    }
+
+    #[test]
+    fn ciphertexts_add_their_plaintexts() {
+        let (secret, public_key) = generate_keypair();
+        let tally = [1, 0, 1, 1, 0]
+            .into_iter()
+            .map(|vote| Ciphertext::encrypt(&public_key, vote))
+            .fold(Ciphertext::zero(), |tally, vote| tally + vote);
+        assert_eq!(decrypt(&secret, &tally, 5), Some(3));
+    }
+
+    #[test]
+    fn discrete_log_respects_the_bound() {
+        let point = Scalar::from(17u64) * RISTRETTO_BASEPOINT_POINT;
+        assert_eq!(discrete_log(&point, 17), Some(17));
+        assert_eq!(discrete_log(&point, 100), Some(17));
+        assert_eq!(discrete_log(&point, 16), None);
+        assert_eq!(discrete_log(&RistrettoPoint::identity(), 0), Some(0));
+    }
+
+    #[test]
+    fn encodings_round_trip() {
+        let (secret, public_key) = generate_keypair();
+        assert_eq!(
+            decode_point(&encode_point(&public_key)).unwrap(),
+            public_key
+        );
+        assert_eq!(decode_scalar(&encode_scalar(&secret)).unwrap(), secret);
+
+        let ciphertext = Ciphertext::encrypt(&public_key, 3);
+        assert_eq!(
+            Ciphertext::decode(&ciphertext.encode()).unwrap(),
+            ciphertext
+        );
+
+        assert!(decode_point("00").is_err());
+        assert!(decode_scalar(&"ff".repeat(32)).is_err());
+    }
+
+    #[test]
+    fn decryption_shares_prove_the_trustee_key() {
+        let (secret, public_key) = generate_keypair();
+        let ciphertext = Ciphertext::encrypt(&public_key, 7);
+        let share = DecryptionShare::compute(&secret, &ciphertext);
+        assert!(share.verify(&public_key, &ciphertext));
+        assert_eq!(ciphertext.decrypt_with(&share.share, 10), Some(7));
+
+        let (_, other_key) = generate_keypair();
+        assert!(!share.verify(&other_key, &ciphertext));
+        let other_ciphertext = Ciphertext::encrypt(&public_key, 7);
+        assert!(!share.verify(&public_key, &other_ciphertext));
+        let forged = DecryptionShare {
+            share: share.share + RISTRETTO_BASEPOINT_POINT,
+            ..share
+        };
+        assert!(!forged.verify(&public_key, &ciphertext));
+    }
+
+    #[test]
+    fn key_proofs_are_bound_to_key_and_context() {
+        let (secret, public_key) = generate_keypair();
+        let proof = KeyProof::prove(&secret, b"election");
+        assert!(proof.verify(&public_key, b"election"));
+        assert!(!proof.verify(&public_key, b"other election"));
+
+        let (_, other_key) = generate_keypair();
+        assert!(!proof.verify(&other_key, b"election"));
+        // A rogue key that cancels another trustee's cannot be proven.
+        assert!(!proof.verify(&(public_key - other_key), b"election"));
+    }
+}
@@ -0,0 +1,127 @@
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+
+/// Length-prefixed encoding: every name and value is written as a big-endian
+/// `u32` length followed by its bytes, so no two field sequences collide.
+pub(crate) struct CanonicalEncoder {
+    buf: Vec<u8>,
+}
+
+impl CanonicalEncoder {
+    pub(crate) fn new(domain: &str) -> CanonicalEncoder {
+        let mut encoder = CanonicalEncoder { buf: Vec::new() };
+        encoder.write(domain.as_bytes());
+        encoder
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        self.buf
+            .extend_from_slice(&(bytes.len() as u32).to_be_bytes());
+        self.buf.extend_from_slice(bytes);
+    }
+
+    pub(crate) fn field(&mut self, name: &str, value: &str) -> &mut Self {
+        self.write(name.as_bytes());
+        self.write(value.as_bytes());
+        self
+    }
+
+    /// Absent values are skipped entirely rather than encoded as empty, so
+    /// an optional field introduced later leaves older encodings unchanged.
+    pub(crate) fn optional_field(&mut self, name: &str, value: Option<&str>) -> &mut Self {
+        if let Some(value) = value {
+            self.field(name, value);
+        }
+        self
+    }
+
+    pub(crate) fn finish(self) -> Vec<u8> {
+        self.buf
+    }
+}
+
+pub(crate) fn sign(secret_key: &str, input: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret_key.as_bytes()).unwrap();
+    mac.update(input);
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// Public fingerprint of a signing secret, so exported records can say which
+/// key signed them without revealing it.
+pub(crate) fn key_id(secret_key: &str) -> String {
+    let mut encoder = CanonicalEncoder::new("bbox/key-id/v1");
+    encoder.field("secret_key", secret_key);
+    hex::encode(&Sha256::digest(encoder.finish())[..8])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_lengths_before_bytes() {
+        let mut encoder = CanonicalEncoder::new("d");
+        encoder.field("a", "bc");
+        assert_eq!(encoder.finish(), b"\0\0\0\x01d\0\0\0\x01a\0\0\0\x02bc");
+    }
+
+    #[test]
+    fn field_boundaries_do_not_collide() {
+        let mut left = CanonicalEncoder::new("test");
+        left.field("ab", "c");
+        let mut right = CanonicalEncoder::new("test");
+        right.field("a", "bc");
+        assert_ne!(left.finish(), right.finish());
+
+        let mut left = CanonicalEncoder::new("test");
+        left.field("a", "");
+        let mut right = CanonicalEncoder::new("test");
+        right.field("", "a");
+        assert_ne!(left.finish(), right.finish());
+    }
+
+    #[test]
+    fn domains_separate_encodings() {
+        let mut left = CanonicalEncoder::new("bbox/a/v1");
+        left.field("name", "value");
+        let mut right = CanonicalEncoder::new("bbox/b/v1");
+        right.field("name", "value");
+        assert_ne!(left.finish(), right.finish());
+    }
+
+    #[test]
+    fn absent_optional_fields_leave_the_encoding_unchanged() {
+        let mut without = CanonicalEncoder::new("test");
+        without.field("name", "value");
+        let mut absent = CanonicalEncoder::new("test");
+        absent
+            .field("name", "value")
+            .optional_field("added_later", None);
+        assert_eq!(without.finish(), absent.finish());
+
+        let mut empty = CanonicalEncoder::new("test");
+        empty
+            .field("name", "value")
+            .optional_field("added_later", Some(""));
+        let mut without = CanonicalEncoder::new("test");
+        without.field("name", "value");
+        assert_ne!(without.finish(), empty.finish());
+    }
+
+    #[test]
+    fn signs_with_hmac_sha256() {
+        // RFC 4231, test case 2.
+        assert_eq!(
+            sign("Jefe", b"what do ya want for nothing?"),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn key_ids_are_short_and_stable() {
+        let key_id = key_id("secret");
+        assert_eq!(key_id.len(), 16);
+        assert_eq!(key_id, super::key_id("secret"));
+        assert_ne!(key_id, super::key_id("other secret"));
+    }
+}
@@ -0,0 +1,252 @@
+use std::fmt;
+
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use uuid::Uuid;
+
+use crate::{AuditEvent, Election, Party};
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub enum FederationKind {
+    /// Lasts the whole term and runs as a single party everywhere.
+    #[serde(rename = "federation")]
+    Federation,
+    /// Joins parties for one election in one district.
+    #[serde(rename = "coalition")]
+    Coalition,
+}
+
+impl fmt::Display for FederationKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self {
+            FederationKind::Federation => "federation",
+            FederationKind::Coalition => "coalition",
+        };
+        write!(f, "{}", kind)
+    }
+}
+
+impl From<String> for FederationKind {
+    fn from(kind: String) -> FederationKind {
+        match kind.as_str() {
+            "coalition" => FederationKind::Coalition,
+            _ => FederationKind::Federation,
+        }
+    }
+}
+
+/// Parties that share a list in an election: their votes are pooled when
+/// seats are allocated.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Federation {
+    pub id: String,
+    pub election_id: String,
+    pub name: String,
+    pub acronym: String,
+    pub kind: FederationKind,
+    /// District a coalition is limited to; federations have none.
+    pub district: Option<String>,
+    pub party_ids: Vec<String>,
+}
+
+impl Federation {
+    pub fn build(
+        election_id: String,
+        name: String,
+        acronym: String,
+        kind: FederationKind,
+        party_ids: Vec<String>,
+    ) -> Federation {
+        Federation {
+            id: Uuid::now_v7().to_string(),
+            election_id,
+            name,
+            acronym,
+            kind,
+            district: None,
+            party_ids,
+        }
+    }
+
+    pub async fn create(&self, conn: &SqlitePool, actor: &str) -> Result<(), anyhow::Error> {
+        if self.party_ids.len() < 2 {
+            return Err(anyhow!("a federation needs at least two parties"));
+        }
+        if Election::find(conn, &self.election_id).await?.is_none() {
+            return Err(anyhow!("election not found"));
+        }
+        for (index, party_id) in self.party_ids.iter().enumerate() {
+            if self.party_ids[..index].contains(party_id) {
+                return Err(anyhow!("party {} is listed twice", party_id));
+            }
+            let party = Party::find(conn, party_id)
+                .await?
+                .ok_or_else(|| anyhow!("party {} not found", party_id))?;
+            if let Some(federation) =
+                Federation::find_for_party(conn, &self.election_id, party_id).await?
+            {
+                return Err(anyhow!(
+                    "{} already belongs to {} in this election",
+                    party.acronym,
+                    federation.acronym
+                ));
+            }
+        }
+
+        let kind = self.kind.to_string();
+        let mut tx = conn.begin().await?;
+        sqlx::query!(
+            r#"
+            INSERT INTO federations (id, election_id, name, acronym, kind, district)
+            VALUES (?, ?, ?, ?, ?, ?)
+            "#,
+            self.id,
+            self.election_id,
+            self.name,
+            self.acronym,
+            kind,
+            self.district,
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        for party_id in self.party_ids.iter() {
+            sqlx::query!(
+                r#"
+                INSERT INTO federation_parties (federation_id, election_id, party_id)
+                VALUES (?, ?, ?)
+                "#,
+                self.id,
+                self.election_id,
+                party_id,
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        AuditEvent::record(
+            &mut tx,
+            "federation",
+            &self.id,
+            "create",
+            actor,
+            None,
+            serde_json::to_value(self).ok(),
+        )
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn from_record(
+        conn: &SqlitePool,
+        record: FederationRecord,
+    ) -> Result<Federation, sqlx::Error> {
+        let party_ids = sqlx::query_scalar!(
+            r#"
+            SELECT
+                party_id AS "party_id!: String"
+            FROM
+                federation_parties
+            WHERE
+                federation_id = ?
+            ORDER BY
+                party_id
+            "#,
+            record.id
+        )
+        .fetch_all(conn)
+        .await?;
+
+        Ok(Federation {
+            id: record.id,
+            election_id: record.election_id,
+            name: record.name,
+            acronym: record.acronym,
+            kind: FederationKind::from(record.kind),
+            district: record.district,
+            party_ids,
+        })
+    }
+
+    pub async fn list(
+        conn: &SqlitePool,
+        election_id: &str,
+    ) -> Result<Vec<Federation>, sqlx::Error> {
+        let records = sqlx::query_as!(
+            FederationRecord,
+            r#"
+            SELECT
+                id AS "id!: String",
+                election_id AS "election_id!: String",
+                name,
+                acronym,
+                kind,
+                district
+            FROM
+                federations
+            WHERE
+                election_id = ?
+            ORDER BY
+                acronym
+            "#,
+            election_id
+        )
+        .fetch_all(conn)
+        .await?;
+
+        let mut federations = Vec::new();
+        for record in records {
+            federations.push(Federation::from_record(conn, record).await?);
+        }
+        Ok(federations)
+    }
+
+    pub async fn find_for_party(
+        conn: &SqlitePool,
+        election_id: &str,
+        party_id: &str,
+    ) -> Result<Option<Federation>, sqlx::Error> {
+        let record = sqlx::query_as!(
+            FederationRecord,
+            r#"
+            SELECT
+                f.id AS "id!: String",
+                f.election_id AS "election_id!: String",
+                f.name,
+                f.acronym,
+                f.kind,
+                f.district
+            FROM
+                federations f
+            JOIN
+                federation_parties fp ON fp.federation_id = f.id
+            WHERE
+                fp.election_id = ? AND
+                fp.party_id = ?
+            "#,
+            election_id,
+            party_id
+        )
+        .fetch_optional(conn)
+        .await?;
+
+        match record {
+            Some(record) => Ok(Some(Federation::from_record(conn, record).await?)),
+            None => Ok(None),
+        }
+    }
+}
+
+/// A `federations` row, before its parties are loaded.
+struct FederationRecord {
+    id: String,
+    election_id: String,
+    name: String,
+    acronym: String,
+    kind: String,
+    district: Option<String>,
+}
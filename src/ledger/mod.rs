@@ -0,0 +1,472 @@
+use std::collections::{BTreeMap, HashSet};
+
+use anyhow::anyhow;
+use chrono::SecondsFormat;
+use ed25519_dalek::{Signature, Signer, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::SqlitePool;
+
+use crate::{bulletins, encoding::CanonicalEncoder, ResultRow, VoteKind};
+
+const GENESIS_HASH: &str = "GENESIS";
+const DOMAIN: &str = "bbox/ledger/v1";
+const HEAD_DOMAIN: &str = "bbox/ledger-head/v1";
+
+/// A vote as published for public audit. Everything that could identify the
+/// voter (voter id, ballot token, polling section) is left out, and `hash`
+/// can only be recomputed by whoever holds the key named by `key_id`.
+///
+/// What anyone can recompute is `digest`, a SHA-256 over the exported fields
+/// and the previous entry's digest. Changing, dropping or reordering an entry
+/// changes every digest after it, up to the one `LedgerHead` signs.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LedgerEntry {
+    pub hash: String,
+    pub previous_hash: String,
+    pub election_id: Option<String>,
+    pub position: String,
+    pub candidature_code: Option<String>,
+    pub kind: VoteKind,
+    pub year: i32,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub key_id: Option<String>,
+    pub encrypted_ballot: Option<String>,
+    pub digest: String,
+}
+
+/// The last line of an export: the digest of the newest entry, signed with
+/// the bulletin signing key. Signatures are stored as issued, so exporting
+/// the same chain again returns the same head.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LedgerHead {
+    pub entries: i64,
+    pub digest: String,
+    pub public_key: String,
+    pub signature: String,
+}
+
+/// Votes counted from a ledger for one candidature, or the blank or null
+/// votes of a position when `candidature_code` is empty.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct LedgerTally {
+    pub election_id: Option<String>,
+    pub position: String,
+    pub kind: VoteKind,
+    pub candidature_code: Option<String>,
+    pub votes: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LedgerAudit {
+    pub entries: usize,
+    pub key_ids: Vec<String>,
+    pub tallies: Vec<LedgerTally>,
+    /// Encrypted ballots per election; only the trustees can count them.
+    pub encrypted_ballots: BTreeMap<String, i64>,
+}
+
+impl LedgerEntry {
+    /// Every vote in creation order, digested for export.
+    pub async fn list(conn: &SqlitePool) -> Result<Vec<LedgerEntry>, anyhow::Error> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT
+                v.hash,
+                v.previous_hash,
+                v.election_id AS "election_id: String",
+                v.candidature_position,
+                ca.code AS "code?",
+                v.kind,
+                v.year AS "year: i32",
+                v.created_at AS "created_at: chrono::DateTime<chrono::Utc>",
+                v.key_id,
+                v.encrypted_ballot
+            FROM
+                votes v
+            LEFT JOIN
+                candidatures ca ON ca.id = v.candidature_id
+            ORDER BY
+                v.created_at ASC
+            "#
+        )
+        .fetch_all(conn)
+        .await?;
+
+        let mut entries: Vec<LedgerEntry> = rows
+            .into_iter()
+            .map(|row| LedgerEntry {
+                hash: row.hash,
+                previous_hash: row.previous_hash,
+                election_id: row.election_id,
+                position: row.candidature_position,
+                candidature_code: row.code,
+                kind: VoteKind::from(row.kind),
+                year: row.year,
+                created_at: row.created_at,
+                key_id: row.key_id,
+                encrypted_ballot: row.encrypted_ballot,
+                digest: String::new(),
+            })
+            .collect();
+
+        let mut previous_digest = String::new();
+        for entry in entries.iter_mut() {
+            entry.digest = entry.compute_digest(&previous_digest);
+            previous_digest = entry.digest.clone();
+        }
+
+        Ok(entries)
+    }
+
+    /// Elections with votes in the ledger.
+    pub async fn election_ids(conn: &SqlitePool) -> Result<Vec<String>, sqlx::Error> {
+        sqlx::query_scalar!(
+            r#"
+            SELECT DISTINCT
+                election_id AS "election_id!: String"
+            FROM
+                votes
+            WHERE
+                election_id IS NOT NULL
+            "#
+        )
+        .fetch_all(conn)
+        .await
+    }
+
+    /// Everything exported but the digest, chained to the
+    /// previous entry's digest (empty for the genesis vote).
+    fn compute_digest(&self, previous_digest: &str) -> String {
+        let year = self.year.to_string();
+        let created_at = self.created_at.to_rfc3339_opts(SecondsFormat::Nanos, true);
+        let kind = self.kind.to_string();
+
+        let mut encoder = CanonicalEncoder::new(DOMAIN);
+        encoder
+            .field("previous_digest", previous_digest)
+            .field("hash", &self.hash)
+            .field("previous_hash", &self.previous_hash)
+            .optional_field("election_id", self.election_id.as_deref())
+            .field("position", &self.position)
+            .optional_field("candidature_code", self.candidature_code.as_deref())
+            .field("kind", &kind)
+            .field("year", &year)
+            .field("created_at", &created_at)
+            .optional_field("key_id", self.key_id.as_deref())
+            .optional_field("encrypted_ballot", self.encrypted_ballot.as_deref());
+        hex::encode(Sha256::digest(encoder.finish()))
+    }
+
+    /// The entries, one per line, followed by their head.
+    pub fn to_jsonl(
+        entries: &[LedgerEntry],
+        head: &LedgerHead,
+    ) -> Result<Vec<u8>, serde_json::Error> {
+        let mut buf = Vec::new();
+        for entry in entries {
+            serde_json::to_writer(&mut buf, entry)?;
+            buf.push(b'\n');
+        }
+        serde_json::to_writer(&mut buf, head)?;
+        buf.push(b'\n');
+        Ok(buf)
+    }
+
+    pub fn from_jsonl(input: &str) -> Result<(Vec<LedgerEntry>, LedgerHead), anyhow::Error> {
+        let mut lines: Vec<(usize, &str)> = input
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .collect();
+        let (number, head) = lines.pop().ok_or_else(|| anyhow!("ledger is empty"))?;
+        let head = serde_json::from_str(head)
+            .map_err(|reason| anyhow!("line {}: {}", number + 1, reason))?;
+        let entries = lines
+            .into_iter()
+            .map(|(number, line)| {
+                serde_json::from_str(line)
+                    .map_err(|reason| anyhow!("line {}: {}", number + 1, reason))
+            })
+            .collect::<Result<_, anyhow::Error>>()?;
+        Ok((entries, head))
+    }
+
+    /// Checks that the entries form a single chain from the genesis vote,
+    /// recomputes every digest and checks that `head` signs the last one
+    /// with `public_key`, the hex Ed25519 key bulletins are signed with.
+    /// Then counts the plain votes in it.
+    pub fn audit(
+        entries: &[LedgerEntry],
+        head: &LedgerHead,
+        public_key: &str,
+    ) -> Result<LedgerAudit, anyhow::Error> {
+        if !public_key.eq_ignore_ascii_case(&head.public_key) {
+            return Err(anyhow!("ledger was signed with a different key"));
+        }
+        let public_key: [u8; 32] = hex::decode(public_key)?
+            .try_into()
+            .map_err(|_| anyhow!("invalid public key length"))?;
+        let public_key = VerifyingKey::from_bytes(&public_key)?;
+
+        let genesis = entries.first().ok_or_else(|| anyhow!("ledger is empty"))?;
+        if genesis.hash != GENESIS_HASH {
+            return Err(anyhow!("ledger does not start at the genesis vote"));
+        }
+        let mut previous_digest = "";
+        for (number, entry) in entries.iter().enumerate() {
+            if entry.compute_digest(previous_digest) != entry.digest {
+                return Err(anyhow!("line {}: digest does not match", number + 1));
+            }
+            previous_digest = &entry.digest;
+        }
+        if head.entries != entries.len() as i64 || head.digest != previous_digest {
+            return Err(anyhow!("head does not match the last entry"));
+        }
+        if !head.verify_signature(&public_key) {
+            return Err(anyhow!("invalid head signature"));
+        }
+
+        let mut seen = HashSet::new();
+        let mut key_ids = Vec::new();
+        let mut tallies: BTreeMap<(Option<String>, String, String, Option<String>), i64> =
+            BTreeMap::new();
+        let mut encrypted_ballots = BTreeMap::new();
+
+        for (number, pair) in entries.windows(2).enumerate() {
+            let (previous, entry) = (&pair[0], &pair[1]);
+            let line = number + 2;
+            if entry.previous_hash != previous.hash {
+                return Err(anyhow!("line {}: does not link to the previous vote", line));
+            }
+            if entry.created_at < previous.created_at {
+                return Err(anyhow!("line {}: is older than the previous vote", line));
+            }
+            if entry.hash.len() != 64 || hex::decode(&entry.hash).is_err() {
+                return Err(anyhow!("line {}: malformed hash", line));
+            }
+            if !seen.insert(entry.hash.as_str()) {
+                return Err(anyhow!("line {}: duplicate hash", line));
+            }
+            if let Some(key_id) = &entry.key_id {
+                if !key_ids.contains(key_id) {
+                    key_ids.push(key_id.clone());
+                }
+            }
+
+            if entry.encrypted_ballot.is_some() {
+                let election_id = entry.election_id.clone().unwrap_or_default();
+                *encrypted_ballots.entry(election_id).or_insert(0) += 1;
+                continue;
+            }
+            if entry.kind == VoteKind::Candidature && entry.candidature_code.is_none() {
+                return Err(anyhow!("line {}: vote has no candidature", line));
+            }
+            *tallies
+                .entry((
+                    entry.election_id.clone(),
+                    entry.position.clone(),
+                    entry.kind.to_string(),
+                    entry.candidature_code.clone(),
+                ))
+                .or_insert(0) += 1;
+        }
+
+        Ok(LedgerAudit {
+            entries: entries.len(),
+            key_ids,
+            tallies: tallies
+                .into_iter()
+                .map(
+                    |((election_id, position, kind, candidature_code), votes)| LedgerTally {
+                        election_id,
+                        position,
+                        kind: VoteKind::from(kind),
+                        candidature_code,
+                        votes,
+                    },
+                )
+                .collect(),
+            encrypted_ballots,
+        })
+    }
+}
+
+impl LedgerHead {
+    /// The signed head of `entries`, as stored when first exported.
+    pub async fn issue(
+        conn: &SqlitePool,
+        entries: &[LedgerEntry],
+    ) -> Result<LedgerHead, anyhow::Error> {
+        let signing_key = bulletins::signing_key()?;
+        let mut head = LedgerHead {
+            entries: entries.len() as i64,
+            digest: entries
+                .last()
+                .map(|entry| entry.digest.clone())
+                .unwrap_or_default(),
+            public_key: hex::encode(signing_key.verifying_key().as_bytes()),
+            signature: String::new(),
+        };
+
+        let stored = sqlx::query_scalar!(
+            r#"SELECT signature FROM ledger_heads WHERE digest = ? AND public_key = ?"#,
+            head.digest,
+            head.public_key
+        )
+        .fetch_optional(conn)
+        .await?;
+        if let Some(signature) = stored {
+            head.signature = signature;
+            return Ok(head);
+        }
+
+        head.signature = hex::encode(signing_key.sign(&head.encode()).to_bytes());
+        sqlx::query!(
+            r#"
+            INSERT OR IGNORE INTO ledger_heads (digest, public_key, entries, signature)
+            VALUES (?, ?, ?, ?)
+            "#,
+            head.digest,
+            head.public_key,
+            head.entries,
+            head.signature
+        )
+        .execute(conn)
+        .await?;
+
+        Ok(head)
+    }
+
+    /// Everything the signature covers.
+    fn encode(&self) -> Vec<u8> {
+        let entries = self.entries.to_string();
+        let mut encoder = CanonicalEncoder::new(HEAD_DOMAIN);
+        encoder
+            .field("entries", &entries)
+            .field("digest", &self.digest)
+            .field("public_key", &self.public_key);
+        encoder.finish()
+    }
+
+    fn verify_signature(&self, public_key: &VerifyingKey) -> bool {
+        let Ok(signature) = hex::decode(&self.signature) else {
+            return false;
+        };
+        let Ok(signature) = <[u8; 64]>::try_from(signature) else {
+            return false;
+        };
+        public_key
+            .verify_strict(&self.encode(), &Signature::from_bytes(&signature))
+            .is_ok()
+    }
+}
+
+impl LedgerAudit {
+    /// Compares published result rows with the tallies recounted from the
+    /// ledger and describes every difference. Rows of encrypted elections
+    /// cannot be recounted and are skipped.
+    pub fn compare(&self, results: &[ResultRow]) -> Vec<String> {
+        let mut published: BTreeMap<(String, String, String), i64> = BTreeMap::new();
+        for row in results {
+            if self.encrypted_ballots.contains_key(&row.election_id) {
+                continue;
+            }
+            *published
+                .entry((
+                    row.election_id.clone(),
+                    row.position.clone(),
+                    row.candidature_code.clone(),
+                ))
+                .or_insert(0) += row.votes;
+        }
+
+        let mut counted: BTreeMap<(String, String, String), i64> = BTreeMap::new();
+        for tally in self.tallies.iter() {
+            let (Some(election_id), Some(code)) = (&tally.election_id, &tally.candidature_code)
+            else {
+                continue;
+            };
+            counted.insert(
+                (election_id.clone(), tally.position.clone(), code.clone()),
+                tally.votes,
+            );
+        }
+
+        let mut differences = Vec::new();
+        for (key, votes) in published.iter() {
+            let recounted = counted.get(key).copied().unwrap_or(0);
+            if recounted != *votes {
+                differences.push(format!(
+                    "{} {} {}: published {}, recounted {}",
+                    key.0, key.1, key.2, votes, recounted
+                ));
+            }
+        }
+        for (key, votes) in counted.iter() {
+            if !published.contains_key(key) && results.iter().any(|row| row.election_id == key.0) {
+                differences.push(format!(
+                    "{} {} {}: not published, recounted {}",
+                    key.0, key.1, key.2, votes
+                ));
+            }
+        }
+        differences
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Datelike, NaiveDate};
+
+    use super::*;
+    use crate::{
+        testing::{self, test_pool},
+        CandidaturePosition, SqliteRepository, Vote, VoteCredential,
+    };
+
+    #[tokio::test]
+    async fn signs_the_head_once_and_audits_the_chain() {
+        let conn = test_pool().await;
+        let year = chrono::Utc::now().year();
+        let party = testing::party(&conn, 55).await;
+        let candidate =
+            testing::candidate(&conn, &party, NaiveDate::from_ymd_opt(1960, 1, 1).unwrap()).await;
+        testing::open_election(&conn, year, 1).await;
+        testing::candidature(&candidate, "55", CandidaturePosition::President, year)
+            .create(&conn, testing::ACTOR)
+            .await
+            .unwrap();
+        for _ in 0..2 {
+            let voter = testing::voter(&conn, None).await;
+            let mut vote = Vote::build(
+                &conn,
+                &SqliteRepository::new(conn.clone()),
+                VoteCredential::Voter(voter.id),
+                VoteKind::Candidature,
+                Some("55".to_string()),
+                CandidaturePosition::President,
+            )
+            .await
+            .unwrap();
+            vote.create(&conn).await.unwrap();
+        }
+
+        let entries = LedgerEntry::list(&conn).await.unwrap();
+        let head = LedgerHead::issue(&conn, &entries).await.unwrap();
+        let again = LedgerHead::issue(&conn, &entries).await.unwrap();
+        assert_eq!(head.signature, again.signature);
+
+        let export = LedgerEntry::to_jsonl(&entries, &head).unwrap();
+        let (entries, head) = LedgerEntry::from_jsonl(&String::from_utf8(export).unwrap()).unwrap();
+        let audit = LedgerEntry::audit(&entries, &head, &head.public_key).unwrap();
+        assert_eq!(audit.entries, 3);
+        assert_eq!(audit.tallies[0].votes, 2);
+
+        let mut tampered = entries.clone();
+        tampered[1].candidature_code = Some("13".to_string());
+        assert!(LedgerEntry::audit(&tampered, &head, &head.public_key).is_err());
+        let truncated = &entries[..2];
+        assert!(LedgerEntry::audit(truncated, &head, &head.public_key).is_err());
+    }
+}
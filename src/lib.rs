@@ -1,11 +1,44 @@
+mod audit;
+mod ballot_tokens;
+mod bulletins;
 mod candidates;
 mod candidatures;
+mod database;
+mod elections;
+pub mod elgamal;
+mod encoding;
+mod federations;
+mod ledger;
+pub mod media;
+mod migrate;
+mod pagination;
 mod party;
+mod repository;
+mod results;
+mod search;
+mod seats;
+#[cfg(test)]
+mod testing;
+mod trustees;
 mod voters;
 mod votes;
 
+pub use audit::*;
+pub use ballot_tokens::*;
+pub use bulletins::*;
 pub use candidates::*;
 pub use candidatures::*;
+pub use database::*;
+pub use elections::*;
+pub use federations::*;
+pub use ledger::*;
+pub use migrate::*;
+pub use pagination::*;
 pub use party::*;
+pub use repository::*;
+pub use results::*;
+pub use search::*;
+pub use seats::*;
+pub use trustees::*;
 pub use voters::*;
 pub use votes::*;
@@ -1,23 +1,111 @@
+mod cli;
+
+use std::sync::Arc;
+
 use actix_cors::Cors;
 use actix_web::{
     get, post, rt,
     web::{self, Data, Query},
     App, HttpRequest, HttpResponse, HttpServer,
 };
-use bbox::{Candidate, Candidature, CandidaturePosition, Party, Vote, Voter};
-use chrono::Datelike;
+use bbox::{
+    media::{self, MEDIA_PATH},
+    AlreadyVoted, AuditEvent, BallotKey, BallotToken, Bulletin, Candidate, Candidature,
+    CandidatureFilter, CandidatureNotFound, CandidaturePosition, DatabaseConfig, Election,
+    ExportFormat, Federation, FederationKind, LedgerEntry, LedgerHead, PageRequest, Party,
+    Repository, ResultRow, ResultsVisibility, SearchResult, SeatAllocation, SqliteRepository,
+    TallyMode, Vote, VoteCredential, VoteFilter, VoteKind, Voter,
+};
+use chrono::{Datelike, NaiveDate};
+use clap::Parser;
+use cli::{Cli, Command};
 use dotenv::dotenv;
-use rand::distributions::Alphanumeric;
-use rand::{thread_rng, Rng};
 use serde::Deserialize;
 use serde_json::json;
 use sqlx::SqlitePool;
+use subtle::ConstantTimeEq;
 use uuid::Uuid;
 use validator::{Validate, ValidationError};
 
-async fn establish_connection() -> Result<SqlitePool, sqlx::Error> {
+async fn establish_connection() -> Result<SqlitePool, anyhow::Error> {
     let database_url = std::env::var("DATABASE_URL").expect("DATABASE_URL must be set");
-    SqlitePool::connect(&database_url).await
+    Ok(DatabaseConfig::load()?.connect(&database_url).await?)
+}
+
+/// The placeholder token of `.env.example`, which the server refuses to
+/// start with.
+const DEFAULT_ADMIN_TOKEN: &str = "change-me";
+
+/// Checks `ADMIN_TOKENS` before serving, so a deployment never runs with the
+/// token everyone has read in `.env.example`.
+fn check_admin_tokens() -> Result<(), anyhow::Error> {
+    let admins = std::env::var("ADMIN_TOKENS").unwrap_or_default();
+    for pair in admins.split(',') {
+        if let Some((name, DEFAULT_ADMIN_TOKEN)) = pair.trim().split_once(':') {
+            return Err(anyhow::anyhow!(
+                "ADMIN_TOKENS still has the default token for {}, set a secret one",
+                name
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Shortest `SECRET_KEY` accepted, in bytes. HMAC-SHA256 keys shorter than
+/// the digest weaken it.
+const MIN_SECRET_KEY_BYTES: usize = 32;
+
+/// Checks `SECRET_KEY` before anything is hashed with it, so a missing key
+/// fails here rather than panicking halfway through seeding, and an empty
+/// one is never accepted as the HMAC key of votes and the audit log.
+fn check_secret_key() -> Result<(), anyhow::Error> {
+    let secret_key = std::env::var("SECRET_KEY").unwrap_or_default();
+    if secret_key.trim().is_empty() {
+        return Err(anyhow::anyhow!("SECRET_KEY must be set"));
+    }
+    if secret_key.len() < MIN_SECRET_KEY_BYTES {
+        return Err(anyhow::anyhow!(
+            "SECRET_KEY must be at least {} bytes long",
+            MIN_SECRET_KEY_BYTES
+        ));
+    }
+
+    Ok(())
+}
+
+/// Resolves the admin behind `Authorization: Bearer <token>`. Tokens are
+/// configured in `ADMIN_TOKENS` as comma separated `name:token` pairs, and
+/// the name is what gets recorded as the actor in the audit log. Tokens are
+/// compared in constant time so response times don't leak their bytes.
+fn authenticate_admin(req: &HttpRequest) -> Option<String> {
+    let header = req.headers().get("Authorization")?.to_str().ok()?;
+    let token = header.strip_prefix("Bearer ")?;
+    let admins = std::env::var("ADMIN_TOKENS").ok()?;
+
+    admins.split(',').find_map(|pair| {
+        let (name, admin_token) = pair.trim().split_once(':')?;
+        let matches: bool = admin_token.as_bytes().ct_eq(token.as_bytes()).into();
+        (!admin_token.is_empty() && matches).then(|| name.to_string())
+    })
+}
+
+fn unauthorized() -> HttpResponse {
+    HttpResponse::Unauthorized().json(json!({
+        "message": "admin credentials required",
+    }))
+}
+
+/// Checks the results-visibility policy of the election `Vote::list` counts
+/// for the filter. Callers let admins through regardless.
+async fn results_embargo(
+    conn: &SqlitePool,
+    filter: &VoteFilter,
+) -> Result<Option<&'static str>, sqlx::Error> {
+    Ok(filter
+        .election(conn)
+        .await?
+        .and_then(|election| election.results_embargo()))
 }
 
 fn validate_uuid(uuid: &str) -> Result<(), ValidationError> {
@@ -27,50 +115,116 @@ fn validate_uuid(uuid: &str) -> Result<(), ValidationError> {
     }
 }
 
+/// How a request identifies the voter: by id, CPF or título de eleitor.
+/// CPF and título numbers are printed on documents and widely shared, so
+/// they prove nothing about who is asking; only admins (poll workers at the
+/// section) may identify a voter by them.
 #[derive(Debug, Validate, Deserialize)]
-struct VoteRequest {
+struct VoterIdentity {
     #[validate(custom(function = "validate_uuid"))]
-    pub voter_id: String,
+    pub voter_id: Option<String>,
+    pub national_id: Option<String>,
+    pub voter_card: Option<String>,
+}
+
+impl VoterIdentity {
+    /// Whether resolving the identity takes admin credentials.
+    fn needs_admin(&self) -> bool {
+        self.voter_id.is_none() && (self.national_id.is_some() || self.voter_card.is_some())
+    }
+
+    async fn resolve(&self, repository: &dyn Repository) -> Result<String, String> {
+        if let Some(voter_id) = &self.voter_id {
+            return Ok(voter_id.clone());
+        }
+        let voter = if let Some(national_id) = &self.national_id {
+            repository.find_voter_by_national_id(national_id).await
+        } else if let Some(voter_card) = &self.voter_card {
+            repository.find_voter_by_voter_card(voter_card).await
+        } else {
+            return Err("voter_id, national_id or voter_card is required".to_string());
+        };
+        match voter {
+            Ok(Some(voter)) => Ok(voter.id),
+            Ok(None) => Err("voter not found".to_string()),
+            Err(reason) => Err(reason.to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Validate, Deserialize)]
+struct VoteRequest {
+    #[serde(flatten)]
+    #[validate(nested)]
+    pub voter: VoterIdentity,
+    /// Absent for blank and null votes.
     #[validate(length(min = 1))]
-    pub candidature_code: String,
+    pub candidature_code: Option<String>,
     #[validate(length(min = 1))]
     pub candidature_position: String,
-}
-
-#[derive(Debug, Deserialize, Clone)]
-struct GetCandidatureQuery {
-    pub position: CandidaturePosition,
+    #[serde(default)]
+    pub kind: VoteKind,
 }
 
 #[get("/candidatures")]
 async fn get_candidatures(
     state: Data<State>,
-    query: Query<GetCandidatureQuery>,
+    filter: Query<CandidatureFilter>,
+    page: Query<PageRequest>,
 ) -> Result<HttpResponse, actix_web::Error> {
-    let candidatures = Candidature::list(&state.conn, query.position.clone())
-        .await
-        .unwrap();
-    Ok(HttpResponse::Ok().json(candidatures))
-}
-
-#[derive(Debug, Deserialize)]
-struct VoteQuery {
-    pub candidature_position: CandidaturePosition,
+    match Candidature::list(&state.conn, &filter, &page).await {
+        Ok(candidatures) => Ok(HttpResponse::Ok().json(candidatures)),
+        Err(reason) => Ok(HttpResponse::BadRequest().json(json!({
+            "message": reason.to_string(),
+        }))),
+    }
 }
 
 #[get("/votes")]
 async fn get_votes(
+    req: HttpRequest,
     state: Data<State>,
-    query: Query<VoteQuery>,
+    filter: Query<VoteFilter>,
+    page: Query<PageRequest>,
 ) -> Result<HttpResponse, actix_web::Error> {
-    let votes = Vote::list(&state.conn, query.candidature_position.clone())
-        .await
-        .unwrap();
-    Ok(HttpResponse::Ok().json(votes))
+    if authenticate_admin(&req).is_none() {
+        match results_embargo(&state.conn, &filter).await {
+            Ok(None) => {}
+            Ok(Some(reason)) => {
+                return Ok(HttpResponse::Forbidden().json(json!({
+                    "message": reason,
+                })))
+            }
+            Err(reason) => {
+                return Ok(HttpResponse::InternalServerError().json(json!({
+                    "message": reason.to_string(),
+                })))
+            }
+        }
+    }
+    match Vote::list(&state.conn, &filter, &page).await {
+        Ok(votes) => Ok(HttpResponse::Ok().json(votes)),
+        Err(reason) => Ok(HttpResponse::BadRequest().json(json!({
+            "message": reason.to_string(),
+        }))),
+    }
+}
+
+#[get("/votes/verify")]
+async fn verify_votes(state: Data<State>) -> Result<HttpResponse, actix_web::Error> {
+    match Vote::verify_chain(&state.conn).await {
+        Ok(verified) => Ok(HttpResponse::Ok().json(json!({
+            "verified": verified,
+        }))),
+        Err(reason) => Ok(HttpResponse::Conflict().json(json!({
+            "message": reason.to_string(),
+        }))),
+    }
 }
 
 #[post("/votes")]
 async fn create_vote(
+    req: HttpRequest,
     state: Data<State>,
     vote_request: web::Json<VoteRequest>,
 ) -> Result<HttpResponse, actix_web::Error> {
@@ -82,33 +236,945 @@ async fn create_vote(
             })));
         }
     }
+    if vote_request.voter.needs_admin() && authenticate_admin(&req).is_none() {
+        return Ok(unauthorized());
+    }
+    let voter_id = match vote_request.voter.resolve(state.repository.as_ref()).await {
+        Ok(voter_id) => voter_id,
+        Err(reason) => {
+            return Ok(HttpResponse::BadRequest().json(json!({
+                "message": reason,
+            })));
+        }
+    };
     let vote = Vote::build(
         &state.conn,
-        vote_request.voter_id.clone(),
+        state.repository.as_ref(),
+        VoteCredential::Voter(voter_id),
+        vote_request.kind.clone(),
         vote_request.candidature_code.clone(),
         CandidaturePosition::from(vote_request.candidature_position.clone()),
     )
     .await;
     match vote {
-        Ok(vote) => match vote.create(&state.conn).await {
+        Ok(mut vote) => match state.repository.create_vote(&mut vote).await {
             Ok(_) => Ok(HttpResponse::Created().json(vote)),
-            Err(reason) => Ok(HttpResponse::BadRequest().json(json!({
+            Err(reason) => Ok(vote_rejected(reason)),
+        },
+        Err(reason) => Ok(vote_rejected(reason)),
+    }
+}
+
+/// A second vote of the same voter is a conflict rather than a bad request,
+/// so clients can tell it apart from a malformed ballot, and an unknown code
+/// is not found.
+fn vote_rejected(reason: anyhow::Error) -> HttpResponse {
+    if reason.is::<AlreadyVoted>() {
+        return HttpResponse::Conflict().json(json!({
+            "error": "already_voted",
+            "message": reason.to_string(),
+        }));
+    }
+    if reason.is::<CandidatureNotFound>() {
+        return HttpResponse::NotFound().json(json!({
+            "message": reason.to_string(),
+        }));
+    }
+
+    HttpResponse::BadRequest().json(json!({
+        "message": reason.to_string(),
+    }))
+}
+
+#[derive(Debug, Deserialize)]
+struct BallotKeyQuery {
+    pub candidature_position: CandidaturePosition,
+    /// The current election of the year when absent.
+    pub election: Option<String>,
+}
+
+impl BallotKeyQuery {
+    async fn election(&self, conn: &SqlitePool) -> Result<Option<Election>, sqlx::Error> {
+        match &self.election {
+            Some(election_id) => Election::find(conn, election_id).await,
+            None => Election::current(conn, chrono::Utc::now().year()).await,
+        }
+    }
+}
+
+#[get("/ballot-tokens/key")]
+async fn get_ballot_key(
+    state: Data<State>,
+    query: Query<BallotKeyQuery>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let election = match query.election(&state.conn).await {
+        Ok(Some(election)) => election,
+        Ok(None) => {
+            return Ok(HttpResponse::NotFound().json(json!({
+                "message": "election not found",
+            })))
+        }
+        Err(reason) => {
+            return Ok(HttpResponse::InternalServerError().json(json!({
                 "message": reason.to_string(),
-            }))),
+            })))
+        }
+    };
+    let key =
+        BallotKey::find_by_position(&state.conn, &query.candidature_position, &election.id).await;
+    match key {
+        Ok(Some(key)) => Ok(HttpResponse::Ok().json(key.public_key())),
+        Ok(None) => Ok(HttpResponse::NotFound().json(json!({
+            "message": "ballot key not found",
+        }))),
+        Err(reason) => Ok(HttpResponse::InternalServerError().json(json!({
+            "message": reason.to_string(),
+        }))),
+    }
+}
+
+/// Generates an election's ballot key for a position, before voters can ask
+/// for tokens. Creating it again returns the existing key.
+#[post("/ballot-keys")]
+async fn create_ballot_key(
+    req: HttpRequest,
+    state: Data<State>,
+    key_request: web::Json<BallotKeyQuery>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let Some(actor) = authenticate_admin(&req) else {
+        return Ok(unauthorized());
+    };
+    let election = match key_request.election(&state.conn).await {
+        Ok(Some(election)) => election,
+        Ok(None) => {
+            return Ok(HttpResponse::NotFound().json(json!({
+                "message": "election not found",
+            })))
+        }
+        Err(reason) => {
+            return Ok(HttpResponse::InternalServerError().json(json!({
+                "message": reason.to_string(),
+            })))
+        }
+    };
+    let key = BallotKey::create(
+        &state.conn,
+        key_request.candidature_position.clone(),
+        &election,
+        &actor,
+    )
+    .await;
+    match key {
+        Ok(key) => Ok(HttpResponse::Created().json(key.public_key())),
+        Err(reason) => Ok(HttpResponse::InternalServerError().json(json!({
+            "message": reason.to_string(),
+        }))),
+    }
+}
+
+#[derive(Debug, Validate, Deserialize)]
+struct BallotTokenRequest {
+    #[serde(flatten)]
+    #[validate(nested)]
+    pub voter: VoterIdentity,
+    pub candidature_position: CandidaturePosition,
+    #[validate(length(min = 1))]
+    pub blinded_message: String,
+}
+
+#[post("/ballot-tokens")]
+async fn create_ballot_token(
+    req: HttpRequest,
+    state: Data<State>,
+    token_request: web::Json<BallotTokenRequest>,
+) -> Result<HttpResponse, actix_web::Error> {
+    if let Err(reason) = token_request.validate() {
+        return Ok(HttpResponse::BadRequest().json(json!({
+            "message": reason.to_string(),
+        })));
+    }
+    if token_request.voter.needs_admin() && authenticate_admin(&req).is_none() {
+        return Ok(unauthorized());
+    }
+    let voter_id = match token_request.voter.resolve(state.repository.as_ref()).await {
+        Ok(voter_id) => voter_id,
+        Err(reason) => {
+            return Ok(HttpResponse::BadRequest().json(json!({
+                "message": reason,
+            })));
+        }
+    };
+    let signed = BallotToken::issue(
+        &state.conn,
+        &voter_id,
+        token_request.candidature_position.clone(),
+        &token_request.blinded_message,
+    )
+    .await;
+    match signed {
+        Ok(signed) => Ok(HttpResponse::Created().json(signed)),
+        Err(reason) => Ok(vote_rejected(reason)),
+    }
+}
+
+#[derive(Debug, Validate, Deserialize)]
+struct BallotRequest {
+    /// Absent for blank and null votes.
+    #[validate(length(min = 1))]
+    pub candidature_code: Option<String>,
+    pub candidature_position: CandidaturePosition,
+    pub token: BallotToken,
+    #[serde(default)]
+    pub kind: VoteKind,
+}
+
+/// Casts an anonymous ballot. The request carries no voter id, only a token
+/// whose signature proves a voter was entitled to it.
+#[post("/ballots")]
+async fn create_ballot(
+    state: Data<State>,
+    ballot_request: web::Json<BallotRequest>,
+) -> Result<HttpResponse, actix_web::Error> {
+    if let Err(reason) = ballot_request.validate() {
+        return Ok(HttpResponse::BadRequest().json(json!({
+            "message": reason.to_string(),
+        })));
+    }
+    let vote = Vote::build(
+        &state.conn,
+        state.repository.as_ref(),
+        VoteCredential::BallotToken(ballot_request.token.clone()),
+        ballot_request.kind.clone(),
+        ballot_request.candidature_code.clone(),
+        ballot_request.candidature_position.clone(),
+    )
+    .await;
+    match vote {
+        Ok(mut vote) => match state.repository.create_vote(&mut vote).await {
+            Ok(_) => Ok(HttpResponse::Created().json(vote)),
+            Err(reason) => Ok(vote_rejected(reason)),
         },
+        Err(reason) => Ok(vote_rejected(reason)),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ElectionQuery {
+    pub year: Option<i32>,
+}
+
+#[get("/elections")]
+async fn get_elections(
+    state: Data<State>,
+    query: Query<ElectionQuery>,
+) -> Result<HttpResponse, actix_web::Error> {
+    match Election::list(&state.conn).await {
+        Ok(mut elections) => {
+            if let Some(year) = query.year {
+                elections.retain(|election| election.year == year);
+            }
+            Ok(HttpResponse::Ok().json(elections))
+        }
+        Err(reason) => Ok(HttpResponse::InternalServerError().json(json!({
+            "message": reason.to_string(),
+        }))),
+    }
+}
+
+#[get("/elections/{id}/federations")]
+async fn get_federations(
+    state: Data<State>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, actix_web::Error> {
+    match Federation::list(&state.conn, &path.into_inner()).await {
+        Ok(federations) => Ok(HttpResponse::Ok().json(federations)),
+        Err(reason) => Ok(HttpResponse::InternalServerError().json(json!({
+            "message": reason.to_string(),
+        }))),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct SeatQuery {
+    pub position: CandidaturePosition,
+    pub seats: i64,
+    pub district: Option<String>,
+}
+
+/// Proportional seat allocation of a position, pooling federated parties.
+#[get("/elections/{id}/seats")]
+async fn get_seats(
+    req: HttpRequest,
+    state: Data<State>,
+    path: web::Path<String>,
+    query: Query<SeatQuery>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let election = match Election::find(&state.conn, &path.into_inner()).await {
+        Ok(Some(election)) => election,
+        Ok(None) => {
+            return Ok(HttpResponse::NotFound().json(json!({
+                "message": "election not found",
+            })))
+        }
+        Err(reason) => {
+            return Ok(HttpResponse::InternalServerError().json(json!({
+                "message": reason.to_string(),
+            })))
+        }
+    };
+    if authenticate_admin(&req).is_none() {
+        if let Some(reason) = election.results_embargo() {
+            return Ok(HttpResponse::Forbidden().json(json!({
+                "message": reason,
+            })));
+        }
+    }
+    let allocation = SeatAllocation::compute(
+        &state.conn,
+        &election,
+        query.position.clone(),
+        query.district.as_deref(),
+        query.seats,
+    )
+    .await;
+    match allocation {
+        Ok(allocation) => Ok(HttpResponse::Ok().json(allocation)),
         Err(reason) => Ok(HttpResponse::BadRequest().json(json!({
             "message": reason.to_string(),
         }))),
     }
 }
 
+#[derive(Debug, Deserialize)]
+struct ExportQuery {
+    pub format: Option<String>,
+    pub election: Option<String>,
+    pub year: Option<i32>,
+}
+
+/// Flat result rows for analysts. Elections under a results embargo are
+/// left out unless the caller is an admin.
+#[get("/results/export")]
+async fn export_results(
+    req: HttpRequest,
+    state: Data<State>,
+    query: Query<ExportQuery>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let format = match query
+        .format
+        .as_deref()
+        .unwrap_or("csv")
+        .parse::<ExportFormat>()
+    {
+        Ok(format) => format,
+        Err(reason) => {
+            return Ok(HttpResponse::BadRequest().json(json!({
+                "message": reason.to_string(),
+            })))
+        }
+    };
+    let admin = authenticate_admin(&req).is_some();
+    let rows =
+        match ResultRow::list(&state.conn, query.election.as_deref(), query.year, admin).await {
+            Ok(rows) => rows,
+            Err(reason) => {
+                return Ok(HttpResponse::InternalServerError().json(json!({
+                    "message": reason.to_string(),
+                })))
+            }
+        };
+    match ResultRow::export(&rows, &format) {
+        Ok(export) => Ok(HttpResponse::Ok()
+            .content_type(format.content_type())
+            .insert_header((
+                "Content-Disposition",
+                format!("attachment; filename=\"results.{}\"", format),
+            ))
+            .body(export)),
+        Err(reason) => Ok(HttpResponse::InternalServerError().json(json!({
+            "message": reason.to_string(),
+        }))),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct CompareQuery {
+    pub position: CandidaturePosition,
+    /// Comma separated; every year with results when absent.
+    pub years: Option<String>,
+    pub district: Option<String>,
+    #[serde(default)]
+    pub by_district: bool,
+}
+
+/// Year-over-year votes and vote share of each party for a position.
+/// Embargoed elections are left out unless the caller is an admin.
+#[get("/results/compare")]
+async fn compare_results(
+    req: HttpRequest,
+    state: Data<State>,
+    query: Query<CompareQuery>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let years = match &query.years {
+        Some(years) => match years
+            .split(',')
+            .map(|year| year.trim().parse::<i32>())
+            .collect::<Result<Vec<i32>, _>>()
+        {
+            Ok(years) => Some(years),
+            Err(_) => {
+                return Ok(HttpResponse::BadRequest().json(json!({
+                    "message": "years must be comma separated numbers",
+                })))
+            }
+        },
+        None => None,
+    };
+    let admin = authenticate_admin(&req).is_some();
+    let mut rows = match ResultRow::list(&state.conn, None, None, admin).await {
+        Ok(rows) => rows,
+        Err(reason) => {
+            return Ok(HttpResponse::InternalServerError().json(json!({
+                "message": reason.to_string(),
+            })))
+        }
+    };
+    if let Some(years) = years {
+        rows.retain(|row| years.contains(&row.year));
+    }
+    Ok(HttpResponse::Ok().json(ResultRow::compare(
+        &rows,
+        &query.position.to_string(),
+        query.district.as_deref(),
+        query.by_district,
+    )))
+}
+
+/// The whole vote chain without voter identities, in JSON Lines and closed
+/// by its signed head, for `bbox-verify`. Withheld from the public while the results of any election
+/// in it are embargoed, as the chain cannot be exported in part.
+#[get("/ledger/export")]
+async fn export_ledger(
+    req: HttpRequest,
+    state: Data<State>,
+) -> Result<HttpResponse, actix_web::Error> {
+    if authenticate_admin(&req).is_none() {
+        let election_ids = match LedgerEntry::election_ids(&state.conn).await {
+            Ok(election_ids) => election_ids,
+            Err(reason) => {
+                return Ok(HttpResponse::InternalServerError().json(json!({
+                    "message": reason.to_string(),
+                })))
+            }
+        };
+        for election_id in election_ids {
+            let filter = VoteFilter {
+                election: Some(election_id),
+                ..VoteFilter::default()
+            };
+            match results_embargo(&state.conn, &filter).await {
+                Ok(None) => {}
+                Ok(Some(reason)) => {
+                    return Ok(HttpResponse::Forbidden().json(json!({
+                        "message": reason,
+                    })))
+                }
+                Err(reason) => {
+                    return Ok(HttpResponse::InternalServerError().json(json!({
+                        "message": reason.to_string(),
+                    })))
+                }
+            }
+        }
+    }
+    let export = match LedgerEntry::list(&state.conn).await {
+        Ok(entries) => LedgerHead::issue(&state.conn, &entries)
+            .await
+            .and_then(|head| Ok(LedgerEntry::to_jsonl(&entries, &head)?)),
+        Err(reason) => Err(reason),
+    };
+    match export {
+        Ok(export) => Ok(HttpResponse::Ok()
+            .content_type("application/x-ndjson")
+            .insert_header((
+                "Content-Disposition",
+                "attachment; filename=\"ledger.jsonl\"",
+            ))
+            .body(export)),
+        Err(reason) => Ok(HttpResponse::InternalServerError().json(json!({
+            "message": reason.to_string(),
+        }))),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct BulletinQuery {
+    pub format: Option<String>,
+}
+
+/// Signed result bulletin of a polling section as issued when the election
+/// closed: JSON, or with `?format=text` or `?format=pdf` the printable form.
+/// Follows the election's results visibility policy like the other result
+/// endpoints.
+#[get("/elections/{id}/bulletins/{district}/{section}")]
+async fn get_bulletin(
+    req: HttpRequest,
+    state: Data<State>,
+    path: web::Path<(String, String, String)>,
+    query: Query<BulletinQuery>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let (id, district, section) = path.into_inner();
+    let election = match Election::find(&state.conn, &id).await {
+        Ok(Some(election)) => election,
+        Ok(None) => {
+            return Ok(HttpResponse::NotFound().json(json!({
+                "message": "election not found",
+            })))
+        }
+        Err(reason) => {
+            return Ok(HttpResponse::InternalServerError().json(json!({
+                "message": reason.to_string(),
+            })))
+        }
+    };
+    if authenticate_admin(&req).is_none() {
+        if let Some(reason) = election.results_embargo() {
+            return Ok(HttpResponse::Forbidden().json(json!({
+                "message": reason,
+            })));
+        }
+    }
+    match Bulletin::find(&state.conn, &election.id, &district, &section).await {
+        Ok(Some(bulletin)) => match query.format.as_deref() {
+            Some("text") => Ok(HttpResponse::Ok()
+                .content_type("text/plain; charset=utf-8")
+                .body(bulletin.to_text())),
+            Some("pdf") => Ok(HttpResponse::Ok()
+                .content_type("application/pdf")
+                .body(bulletin.to_pdf())),
+            _ => Ok(HttpResponse::Ok().json(bulletin)),
+        },
+        Ok(None) => Ok(HttpResponse::NotFound().json(json!({
+            "message": "bulletin not found",
+        }))),
+        Err(reason) => Ok(HttpResponse::InternalServerError().json(json!({
+            "message": reason.to_string(),
+        }))),
+    }
+}
+
+#[derive(Debug, Validate, Deserialize)]
+struct ElectionRequest {
+    #[validate(length(min = 1))]
+    pub name: String,
+    pub year: i32,
+    pub tally_mode: TallyMode,
+    pub threshold: Option<i32>,
+    pub trustee_count: Option<i32>,
+    #[serde(default)]
+    pub results_visibility: ResultsVisibility,
+    pub election_date: Option<NaiveDate>,
+    pub minimum_voting_age: Option<i32>,
+    pub round: Option<i32>,
+}
+
+#[post("/elections")]
+async fn create_election(
+    req: HttpRequest,
+    state: Data<State>,
+    election_request: web::Json<ElectionRequest>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let Some(actor) = authenticate_admin(&req) else {
+        return Ok(unauthorized());
+    };
+    if let Err(reason) = election_request.validate() {
+        return Ok(HttpResponse::BadRequest().json(json!({
+            "message": reason.to_string(),
+        })));
+    }
+    let mut election = Election::build(
+        election_request.name.clone(),
+        election_request.year,
+        election_request.tally_mode.clone(),
+    );
+    election.threshold = election_request.threshold;
+    election.trustee_count = election_request.trustee_count;
+    election.results_visibility = election_request.results_visibility.clone();
+    election.election_date = election_request.election_date;
+    if let Some(minimum_voting_age) = election_request.minimum_voting_age {
+        election.minimum_voting_age = minimum_voting_age;
+    }
+    if let Some(round) = election_request.round {
+        election.round = round;
+    }
+    match election.create(&state.conn, &actor).await {
+        Ok(_) => Ok(HttpResponse::Created().json(election)),
+        Err(reason) => Ok(HttpResponse::BadRequest().json(json!({
+            "message": reason.to_string(),
+        }))),
+    }
+}
+
+#[derive(Debug, Validate, Deserialize)]
+struct FederationRequest {
+    #[validate(length(min = 1))]
+    pub name: String,
+    #[validate(length(min = 1, max = 20))]
+    pub acronym: String,
+    pub kind: FederationKind,
+    pub district: Option<String>,
+    pub party_ids: Vec<String>,
+}
+
+#[post("/elections/{id}/federations")]
+async fn create_federation(
+    req: HttpRequest,
+    state: Data<State>,
+    path: web::Path<String>,
+    federation_request: web::Json<FederationRequest>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let Some(actor) = authenticate_admin(&req) else {
+        return Ok(unauthorized());
+    };
+    if let Err(reason) = federation_request.validate() {
+        return Ok(HttpResponse::BadRequest().json(json!({
+            "message": reason.to_string(),
+        })));
+    }
+    let mut federation = Federation::build(
+        path.into_inner(),
+        federation_request.name.clone(),
+        federation_request.acronym.clone(),
+        federation_request.kind.clone(),
+        federation_request.party_ids.clone(),
+    );
+    federation.district = federation_request.district.clone();
+    match federation.create(&state.conn, &actor).await {
+        Ok(_) => Ok(HttpResponse::Created().json(federation)),
+        Err(reason) => Ok(HttpResponse::BadRequest().json(json!({
+            "message": reason.to_string(),
+        }))),
+    }
+}
+
+/// Drives an election through its lifecycle: `open`, `close`, `tally`
+/// (combine the trustees' decryption shares of an encrypted election) or
+/// `certify`.
+#[post("/elections/{id}/{transition}")]
+async fn transition_election(
+    req: HttpRequest,
+    state: Data<State>,
+    path: web::Path<(String, String)>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let Some(actor) = authenticate_admin(&req) else {
+        return Ok(unauthorized());
+    };
+    let (id, transition) = path.into_inner();
+    let election = match Election::find(&state.conn, &id).await {
+        Ok(Some(election)) => election,
+        Ok(None) => {
+            return Ok(HttpResponse::NotFound().json(json!({
+                "message": "election not found",
+            })))
+        }
+        Err(reason) => {
+            return Ok(HttpResponse::InternalServerError().json(json!({
+                "message": reason.to_string(),
+            })))
+        }
+    };
+    let result = match transition.as_str() {
+        "open" => election
+            .open(&state.conn, &actor)
+            .await
+            .map(|election| json!(election)),
+        "close" => election
+            .close(&state.conn, &actor)
+            .await
+            .map(|election| json!(election)),
+        "tally" => election
+            .tally(&state.conn, &actor)
+            .await
+            .map(|results| json!(results)),
+        "certify" => election
+            .certify(&state.conn, &actor)
+            .await
+            .map(|election| json!(election)),
+        _ => {
+            return Ok(HttpResponse::NotFound().json(json!({
+                "message": "unknown transition",
+            })))
+        }
+    };
+    match result {
+        Ok(value) => Ok(HttpResponse::Ok().json(value)),
+        Err(reason) => Ok(HttpResponse::BadRequest().json(json!({
+            "message": reason.to_string(),
+        }))),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct SearchQuery {
+    pub q: String,
+    /// Only `candidate`, `party` or `candidature` results.
+    pub entity: Option<String>,
+    pub limit: Option<i64>,
+}
+
+#[get("/search")]
+async fn search(
+    state: Data<State>,
+    query: Query<SearchQuery>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let results = SearchResult::search(
+        &state.conn,
+        &query.q,
+        query.entity.as_deref(),
+        query.limit.unwrap_or(20).clamp(1, 100),
+    )
+    .await;
+    match results {
+        Ok(results) => Ok(HttpResponse::Ok().json(results)),
+        Err(reason) => Ok(HttpResponse::InternalServerError().json(json!({
+            "message": reason.to_string(),
+        }))),
+    }
+}
+
+#[get("/candidates/{id}")]
+async fn get_candidate(
+    state: Data<State>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, actix_web::Error> {
+    match state.repository.find_candidate(&path.into_inner()).await {
+        Ok(Some(candidate)) => Ok(HttpResponse::Ok().json(candidate)),
+        Ok(None) => Ok(HttpResponse::NotFound().json(json!({
+            "message": "candidate not found",
+        }))),
+        Err(reason) => Ok(HttpResponse::InternalServerError().json(json!({
+            "message": reason.to_string(),
+        }))),
+    }
+}
+
+#[derive(Debug, Validate, Deserialize)]
+struct CandidateRequest {
+    #[validate(length(min = 1, max = 100))]
+    pub first_name: String,
+    #[validate(length(min = 1, max = 100))]
+    pub last_name: String,
+    pub birth_date: NaiveDate,
+    pub party_id: Option<String>,
+    #[validate(length(min = 1, max = 30))]
+    pub ballot_name: Option<String>,
+    #[validate(length(max = 10000))]
+    pub biography: Option<String>,
+}
+
+#[post("/candidates")]
+async fn create_candidate(
+    req: HttpRequest,
+    state: Data<State>,
+    candidate_request: web::Json<CandidateRequest>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let Some(actor) = authenticate_admin(&req) else {
+        return Ok(unauthorized());
+    };
+    if let Err(reason) = candidate_request.validate() {
+        return Ok(HttpResponse::BadRequest().json(json!({
+            "message": reason.to_string(),
+        })));
+    }
+    let candidate_request = candidate_request.into_inner();
+    let mut candidate = Candidate::build(candidate_request.first_name, candidate_request.last_name);
+    candidate.birth_date = Some(candidate_request.birth_date);
+    candidate.party_id = candidate_request.party_id;
+    candidate.ballot_name = candidate_request.ballot_name;
+    candidate.biography = candidate_request.biography;
+    match state.repository.create_candidate(&candidate, &actor).await {
+        Ok(_) => Ok(HttpResponse::Created().json(candidate)),
+        Err(reason) => Ok(HttpResponse::BadRequest().json(json!({
+            "message": reason.to_string(),
+        }))),
+    }
+}
+
+/// Uploads a candidate's photo (JPEG, PNG or WebP) or proposals (PDF) as the
+/// raw request body. Photos are cropped and resized before being stored.
+#[post("/candidates/{id}/{upload}")]
+async fn upload_candidate_media(
+    req: HttpRequest,
+    state: Data<State>,
+    path: web::Path<(String, String)>,
+    body: web::Bytes,
+) -> Result<HttpResponse, actix_web::Error> {
+    let Some(actor) = authenticate_admin(&req) else {
+        return Ok(unauthorized());
+    };
+    let (id, upload) = path.into_inner();
+    let mut candidate = match Candidate::find(&state.conn, &id).await {
+        Ok(Some(candidate)) => candidate,
+        Ok(None) => {
+            return Ok(HttpResponse::NotFound().json(json!({
+                "message": "candidate not found",
+            })))
+        }
+        Err(reason) => {
+            return Ok(HttpResponse::InternalServerError().json(json!({
+                "message": reason.to_string(),
+            })))
+        }
+    };
+    let stored = match upload.as_str() {
+        "photo" => media::store_photo("candidates", body.to_vec())
+            .await
+            .map(|url| candidate.photo_url = Some(url)),
+        "proposals" => media::store_document("candidates", &body)
+            .map(|url| candidate.proposals_url = Some(url)),
+        _ => {
+            return Ok(HttpResponse::NotFound().json(json!({
+                "message": "unknown upload",
+            })))
+        }
+    };
+    if let Err(reason) = stored {
+        return Ok(HttpResponse::BadRequest().json(json!({
+            "message": reason.to_string(),
+        })));
+    }
+    match candidate.update(&state.conn, &actor).await {
+        Ok(_) => Ok(HttpResponse::Ok().json(candidate)),
+        Err(reason) => Ok(HttpResponse::BadRequest().json(json!({
+            "message": reason.to_string(),
+        }))),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct VoterImportQuery {
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+/// Imports a CSV voter roll sent as the request body. Responds 422 with the
+/// row-level report, and writes nothing, if any row is invalid.
+#[post("/voters/import")]
+async fn import_voters(
+    req: HttpRequest,
+    state: Data<State>,
+    query: Query<VoterImportQuery>,
+    body: web::Bytes,
+) -> Result<HttpResponse, actix_web::Error> {
+    let Some(actor) = authenticate_admin(&req) else {
+        return Ok(unauthorized());
+    };
+    match Voter::import_csv(&state.conn, &body, &actor, query.dry_run).await {
+        Ok(report) if report.errors.is_empty() => Ok(HttpResponse::Ok().json(report)),
+        Ok(report) => Ok(HttpResponse::UnprocessableEntity().json(report)),
+        Err(reason) => Ok(HttpResponse::BadRequest().json(json!({
+            "message": reason.to_string(),
+        }))),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct VoterLookupQuery {
+    pub national_id: Option<String>,
+    pub voter_card: Option<String>,
+}
+
+/// Finds a registered voter by CPF or título de eleitor.
+#[get("/voters/lookup")]
+async fn lookup_voter(
+    req: HttpRequest,
+    state: Data<State>,
+    query: Query<VoterLookupQuery>,
+) -> Result<HttpResponse, actix_web::Error> {
+    if authenticate_admin(&req).is_none() {
+        return Ok(unauthorized());
+    }
+    let voter = if let Some(national_id) = &query.national_id {
+        state
+            .repository
+            .find_voter_by_national_id(national_id)
+            .await
+    } else if let Some(voter_card) = &query.voter_card {
+        state.repository.find_voter_by_voter_card(voter_card).await
+    } else {
+        return Ok(HttpResponse::BadRequest().json(json!({
+            "message": "national_id or voter_card is required",
+        })));
+    };
+    match voter {
+        Ok(Some(voter)) => Ok(HttpResponse::Ok().json(voter)),
+        Ok(None) => Ok(HttpResponse::NotFound().json(json!({
+            "message": "voter not found",
+        }))),
+        Err(reason) => Ok(HttpResponse::InternalServerError().json(json!({
+            "message": reason.to_string(),
+        }))),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct AuditEventQuery {
+    pub entity: Option<String>,
+    pub entity_id: Option<String>,
+    pub limit: Option<i64>,
+}
+
+#[get("/audit-events")]
+async fn get_audit_events(
+    req: HttpRequest,
+    state: Data<State>,
+    query: Query<AuditEventQuery>,
+) -> Result<HttpResponse, actix_web::Error> {
+    if authenticate_admin(&req).is_none() {
+        return Ok(unauthorized());
+    }
+    let events = AuditEvent::list(
+        &state.conn,
+        query.entity.clone(),
+        query.entity_id.clone(),
+        query.limit.unwrap_or(100).clamp(1, 1000),
+    )
+    .await;
+    match events {
+        Ok(events) => Ok(HttpResponse::Ok().json(events)),
+        Err(reason) => Ok(HttpResponse::InternalServerError().json(json!({
+            "message": reason.to_string(),
+        }))),
+    }
+}
+
+#[get("/audit-events/verify")]
+async fn verify_audit_events(
+    req: HttpRequest,
+    state: Data<State>,
+) -> Result<HttpResponse, actix_web::Error> {
+    if authenticate_admin(&req).is_none() {
+        return Ok(unauthorized());
+    }
+    match AuditEvent::verify_chain(&state.conn).await {
+        Ok(verified) => Ok(HttpResponse::Ok().json(json!({
+            "verified": verified,
+        }))),
+        Err(reason) => Ok(HttpResponse::Conflict().json(json!({
+            "message": reason.to_string(),
+        }))),
+    }
+}
+
 #[get("/ws")]
 async fn ws(
     req: HttpRequest,
     stream: web::Payload,
     state: Data<State>,
-    query: Query<VoteQuery>,
+    filter: Query<VoteFilter>,
+    page: Query<PageRequest>,
 ) -> Result<HttpResponse, actix_web::Error> {
+    let admin = authenticate_admin(&req).is_some();
     let (res, mut session, _stream) = actix_ws::handle(&req, stream)?;
 
     // let candidatures = Candidature::list(&state.conn).await.unwrap();
@@ -125,11 +1191,19 @@ async fn ws(
         // receive messages from websocket
 
         loop {
-            let votes = Vote::list(&state.conn, query.candidature_position.clone())
-                .await
-                .unwrap();
-
-            let value = serde_json::to_string(&votes).unwrap();
+            let embargo = if admin {
+                Ok(None)
+            } else {
+                results_embargo(&state.conn, &filter).await
+            };
+            let value = match embargo {
+                Err(reason) => json!({ "message": reason.to_string() }).to_string(),
+                Ok(Some(reason)) => json!({ "message": reason }).to_string(),
+                Ok(None) => match Vote::list(&state.conn, &filter, &page).await {
+                    Ok(votes) => serde_json::to_string(&votes).unwrap(),
+                    Err(reason) => json!({ "message": reason.to_string() }).to_string(),
+                },
+            };
             session.text(value).await.unwrap();
             tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
         }
@@ -139,32 +1213,119 @@ async fn ws(
     Ok(res)
 }
 
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 struct State {
     pub conn: SqlitePool,
-}
-
-fn generate_random_string(length: usize) -> String {
-    let mut rng = thread_rng();
-    (0..length)
-        .map(|_| rng.sample(Alphanumeric)) // Generate random alphanumeric characters
-        .map(char::from) // Convert bytes to char
-        .collect() // Collect into a String
+    /// Registry and vote chain. The server keeps it on the SQLite pool, as
+    /// elections, ballot keys and bulletins only live there.
+    pub repository: Arc<dyn Repository>,
 }
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     dotenv().ok();
+    let cli = Cli::parse();
+    let command = cli.command.unwrap_or(Command::Serve);
+
+    // Every command may append to the vote or audit chains.
+    if let Err(reason) = check_secret_key() {
+        eprintln!("error: {}", reason);
+        std::process::exit(1);
+    }
+
+    // Runs before the SQLite pool is opened, as DATABASE_URL may point to
+    // PostgreSQL.
+    if let Command::Repository(command) = command {
+        let database_url = std::env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+        let result = match bbox::connect_repository(&database_url).await {
+            Ok(repository) => cli::run_repository(repository.as_ref(), command).await,
+            Err(reason) => Err(reason),
+        };
+        if let Err(reason) = result {
+            eprintln!("error: {}", reason);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
 
     let conn = establish_connection()
         .await
         .expect("Failed to connect to database");
 
+    match command {
+        Command::Serve => serve(conn).await,
+        Command::Migrate => {
+            match bbox::migrate(&conn).await {
+                Ok(applied) if applied.is_empty() => println!("database is up to date"),
+                Ok(applied) => {
+                    for version in applied {
+                        println!("applied {}", version);
+                    }
+                }
+                Err(reason) => {
+                    eprintln!("error: {}", reason);
+                    std::process::exit(1);
+                }
+            }
+            Ok(())
+        }
+        Command::Trustee(command) => {
+            if let Err(reason) = cli::run_trustee(&conn, command).await {
+                eprintln!("error: {}", reason);
+                std::process::exit(1);
+            }
+            Ok(())
+        }
+        Command::Report(command) => {
+            if let Err(reason) = cli::run_report(&conn, command).await {
+                eprintln!("error: {}", reason);
+                std::process::exit(1);
+            }
+            Ok(())
+        }
+        Command::Results(command) => {
+            if let Err(reason) = cli::run_results(&conn, command).await {
+                eprintln!("error: {}", reason);
+                std::process::exit(1);
+            }
+            Ok(())
+        }
+        Command::Ledger(command) => {
+            if let Err(reason) = cli::run_ledger(&conn, command).await {
+                eprintln!("error: {}", reason);
+                std::process::exit(1);
+            }
+            Ok(())
+        }
+        Command::Voters(command) => {
+            if let Err(reason) = cli::run_voters(&conn, command).await {
+                eprintln!("error: {}", reason);
+                std::process::exit(1);
+            }
+            Ok(())
+        }
+        Command::Repository(_) => unreachable!("repository commands run before connecting"),
+    }
+}
+
+async fn serve(conn: SqlitePool) -> std::io::Result<()> {
+    if let Err(reason) = check_admin_tokens() {
+        eprintln!("error: {}", reason);
+        std::process::exit(1);
+    }
+
+    if std::env::var("AUTO_MIGRATE").is_ok_and(|value| value == "true") {
+        bbox::migrate(&conn)
+            .await
+            .expect("Failed to migrate database");
+    }
+
     let party1 = Party {
         id: Uuid::now_v7().to_string(),
         name: "Partido Social Democrático".to_string(),
         description: "Partido Social Democrático".to_string(),
         acronym: "PSD".to_string(),
+        number: Some(55),
     };
 
     let party2 = Party {
@@ -172,13 +1333,14 @@ async fn main() -> std::io::Result<()> {
         name: "Partido Comunista Brasileiro".to_string(),
         description: "Partido Comunista Brasileiro".to_string(),
         acronym: "PCB".to_string(),
+        number: Some(21),
     };
 
-    if let Err(reason) = party1.create(&conn).await {
+    if let Err(reason) = party1.create(&conn, "system").await {
         println!("error on create party: {}", reason);
     }
 
-    if let Err(reason) = party2.create(&conn).await {
+    if let Err(reason) = party2.create(&conn, "system").await {
         println!("error on create party: {}", reason);
     }
 
@@ -186,31 +1348,44 @@ async fn main() -> std::io::Result<()> {
         id: Uuid::now_v7().to_string(),
         first_name: "João".to_string(),
         last_name: "Silva".to_string(),
+        birth_date: NaiveDate::from_ymd_opt(1955, 3, 21),
+        party_id: Some(party1.id.clone()),
+        ballot_name: Some("João da Silva".to_string()),
+        biography: None,
+        proposals_url: None,
+        photo_url: None,
     };
 
     let candidate2 = Candidate {
         id: Uuid::now_v7().to_string(),
         first_name: "Maria".to_string(),
         last_name: "Silva".to_string(),
+        birth_date: NaiveDate::from_ymd_opt(1945, 10, 27),
+        party_id: Some(party2.id.clone()),
+        ballot_name: Some("Maria Silva".to_string()),
+        biography: None,
+        proposals_url: None,
+        photo_url: None,
     };
 
-    if let Err(reason) = candidate1.create(&conn).await {
+    if let Err(reason) = candidate1.create(&conn, "system").await {
         println!("error on create candidate: {}", reason);
     }
 
-    if let Err(reason) = candidate2.create(&conn).await {
+    if let Err(reason) = candidate2.create(&conn, "system").await {
         println!("error on create candidate: {}", reason);
     }
 
-    // use rng
     let candidature1 = Candidature {
         id: Uuid::now_v7().to_string(),
         party_id: party1.id,
         candidate_id: candidate1.id,
         image_url: "https://media.gazetadopovo.com.br/2024/07/23194251/Jair-Bolsonaro-Arquivo-Carolina-Antunes-PR-960x540.jpg".to_string(),
-        code: generate_random_string(8),
+        code: "55".to_string(),
         position: CandidaturePosition::President,
         year: chrono::Utc::now().year(),
+        election_id: None,
+        district: None,
     };
 
     let candidature2 = Candidature {
@@ -219,16 +1394,18 @@ async fn main() -> std::io::Result<()> {
         candidate_id: candidate2.id,
         image_url: "https://static.poder360.com.br/2024/10/lula-entrevista-fortaleza-848x477.png"
             .to_string(),
-        code: generate_random_string(8),
+        code: "21".to_string(),
         position: CandidaturePosition::President,
         year: chrono::Utc::now().year(),
+        election_id: None,
+        district: None,
     };
 
-    if let Err(reason) = candidature1.create(&conn).await {
+    if let Err(reason) = candidature1.create(&conn, "system").await {
         println!("error on create candidature: {}", reason);
     }
 
-    if let Err(reason) = candidature2.create(&conn).await {
+    if let Err(reason) = candidature2.create(&conn, "system").await {
         println!("error on create candidature: {}", reason);
     }
 
@@ -238,12 +1415,31 @@ async fn main() -> std::io::Result<()> {
         last_name: "Silva".to_string(),
         mother_name: "Ana".to_string(),
         father_name: "José".to_string(),
-        birth_date: "01/01/2000".to_string(),
+        birth_date: NaiveDate::from_ymd_opt(2000, 1, 1).unwrap(),
+        district: Some("001".to_string()),
+        section: Some("0001".to_string()),
+        national_id: None,
+        voter_card: None,
     };
-    if let Err(reason) = voter.create(&conn).await {
+    if let Err(reason) = voter.create(&conn, "system").await {
         println!("error on create voter: {}", reason);
     }
 
+    let current_year = chrono::Utc::now().year();
+    if let Ok(None) = Election::current(&conn, current_year).await {
+        let election = Election::build(
+            format!("Eleições Gerais {}", current_year),
+            current_year,
+            TallyMode::Plain,
+        );
+        if let Err(reason) = election.create(&conn, "system").await {
+            println!("error on create election: {}", reason);
+        }
+        if let Err(reason) = election.open(&conn, "system").await {
+            println!("error on open election: {}", reason);
+        }
+    }
+
     // let vote = Vote::build(&conn, voter.id, candidature.code).await?;
 
     // vote.create(&conn).await?;
@@ -256,13 +1452,47 @@ async fn main() -> std::io::Result<()> {
                     .allow_any_method()
                     .allow_any_origin(),
             )
-            .app_data(Data::new(State { conn: conn.clone() }))
+            .app_data(Data::new(State {
+                conn: conn.clone(),
+                repository: Arc::new(SqliteRepository::new(conn.clone())),
+            }))
             .service(ws)
+            .service(actix_files::Files::new(MEDIA_PATH, media::media_dir()))
             .service(
                 web::scope("/api/v1")
                     .service(get_candidatures)
+                    .service(verify_votes)
                     .service(get_votes)
-                    .service(create_vote),
+                    .service(create_vote)
+                    .service(get_ballot_key)
+                    .service(create_ballot_token)
+                    .service(create_ballot)
+                    .service(get_elections)
+                    .service(get_bulletin)
+                    .service(get_candidate)
+                    .service(search)
+                    .service(get_federations)
+                    .service(get_seats)
+                    .service(export_results)
+                    .service(compare_results)
+                    .service(export_ledger)
+                    .service(
+                        web::scope("/admin")
+                            // Voter rolls are far larger than the default
+                            // 256 KiB body limit.
+                            .app_data(web::PayloadConfig::new(64 * 1024 * 1024))
+                            .service(verify_audit_events)
+                            .service(create_ballot_key)
+                            .service(get_audit_events)
+                            .service(create_election)
+                            // Before the catch-all transition route.
+                            .service(create_federation)
+                            .service(transition_election)
+                            .service(import_voters)
+                            .service(create_candidate)
+                            .service(upload_candidate_media)
+                            .service(lookup_voter),
+                    ),
             )
     })
     .bind(("0.0.0.0", 4000))?
@@ -0,0 +1,57 @@
+use std::{env, io::Cursor, path::PathBuf};
+
+use anyhow::anyhow;
+use image::{imageops::FilterType, ImageFormat};
+use uuid::Uuid;
+
+/// Public path the media directory is served under.
+pub const MEDIA_PATH: &str = "/media";
+
+const MAX_UPLOAD_BYTES: usize = 10 * 1024 * 1024;
+/// Candidate photos are shown on the voting machine in 3x4.
+const PHOTO_WIDTH: u32 = 300;
+const PHOTO_HEIGHT: u32 = 400;
+
+/// Where uploads are stored, from `MEDIA_DIR`.
+pub fn media_dir() -> PathBuf {
+    PathBuf::from(env::var("MEDIA_DIR").unwrap_or_else(|_| "media".to_string()))
+}
+
+fn write(folder: &str, extension: &str, bytes: &[u8]) -> Result<String, anyhow::Error> {
+    let dir = media_dir().join(folder);
+    std::fs::create_dir_all(&dir)?;
+    let name = format!("{}.{}", Uuid::now_v7(), extension);
+    std::fs::write(dir.join(&name), bytes)?;
+    Ok(format!("{}/{}/{}", MEDIA_PATH, folder, name))
+}
+
+/// Decodes a JPEG, PNG or WebP upload, crops it to 3x4 and stores it as a
+/// JPEG. Returns the URL it is served at.
+pub async fn store_photo(folder: &'static str, bytes: Vec<u8>) -> Result<String, anyhow::Error> {
+    if bytes.len() > MAX_UPLOAD_BYTES {
+        return Err(anyhow!("photo is larger than 10 MiB"));
+    }
+    // Decoding and resizing take long enough to stall the executor.
+    let jpeg = tokio::task::spawn_blocking(move || -> Result<Vec<u8>, anyhow::Error> {
+        let photo = image::load_from_memory(&bytes)
+            .map_err(|_| anyhow!("photo must be a JPEG, PNG or WebP image"))?
+            .resize_to_fill(PHOTO_WIDTH, PHOTO_HEIGHT, FilterType::Lanczos3)
+            .into_rgb8();
+        let mut jpeg = Vec::new();
+        photo.write_to(&mut Cursor::new(&mut jpeg), ImageFormat::Jpeg)?;
+        Ok(jpeg)
+    })
+    .await??;
+    write(folder, "jpg", &jpeg)
+}
+
+/// Stores a PDF upload as is. Returns the URL it is served at.
+pub fn store_document(folder: &str, bytes: &[u8]) -> Result<String, anyhow::Error> {
+    if bytes.len() > MAX_UPLOAD_BYTES {
+        return Err(anyhow!("document is larger than 10 MiB"));
+    }
+    if !bytes.starts_with(b"%PDF-") {
+        return Err(anyhow!("document must be a PDF"));
+    }
+    write(folder, "pdf", bytes)
+}
@@ -0,0 +1,257 @@
+use anyhow::anyhow;
+use sqlx::{migrate::Migrator, Row, SqliteConnection, SqlitePool};
+
+use crate::{BallotKey, Vote};
+
+/// Schema migrations in `migrations/`, embedded in the binary at build time.
+/// The PostgreSQL schema in `migrations/postgres` is applied by
+/// `PostgresRepository` instead.
+pub static MIGRATOR: Migrator = sqlx::migrate!();
+
+async fn applied_versions(conn: &SqlitePool) -> Result<Vec<i64>, sqlx::Error> {
+    let table = sqlx::query(
+        r#"
+        SELECT
+            name
+        FROM
+            sqlite_master
+        WHERE
+            type = 'table' AND
+            name = '_sqlx_migrations'
+        "#,
+    )
+    .fetch_optional(conn)
+    .await?;
+    if table.is_none() {
+        return Ok(Vec::new());
+    }
+
+    let rows = sqlx::query("SELECT version FROM _sqlx_migrations")
+        .fetch_all(conn)
+        .await?;
+
+    Ok(rows.iter().map(|row| row.get(0)).collect())
+}
+
+/// Runs the schema migrations with foreign keys off, which rebuilding a
+/// table that others reference needs, then checks every reference.
+async fn run_migrations(conn: &mut SqliteConnection) -> Result<(), anyhow::Error> {
+    MIGRATOR.run(&mut *conn).await?;
+
+    let violations = sqlx::query("PRAGMA foreign_key_check")
+        .fetch_all(&mut *conn)
+        .await?;
+    if let Some(violation) = violations.first() {
+        return Err(anyhow!(
+            "{} rows break foreign keys, the first in table {}",
+            violations.len(),
+            violation.get::<String, usize>(0)
+        ));
+    }
+
+    Ok(())
+}
+
+/// Applies the pending schema migrations, then the data migrations, which
+/// are idempotent. Returns the versions of the schema migrations applied.
+pub async fn migrate(conn: &SqlitePool) -> Result<Vec<i64>, anyhow::Error> {
+    let applied = applied_versions(conn).await?;
+
+    // The connection goes back to the pool afterwards, so it gets back the
+    // foreign key setting it was opened with (`SQLITE_FOREIGN_KEYS`).
+    let mut migration_conn = conn.acquire().await?;
+    let foreign_keys: bool = sqlx::query_scalar("PRAGMA foreign_keys")
+        .fetch_one(&mut *migration_conn)
+        .await?;
+    sqlx::query("PRAGMA foreign_keys = OFF")
+        .execute(&mut *migration_conn)
+        .await?;
+    let migrated = run_migrations(&mut migration_conn).await;
+    sqlx::query(if foreign_keys {
+        "PRAGMA foreign_keys = ON"
+    } else {
+        "PRAGMA foreign_keys = OFF"
+    })
+    .execute(&mut *migration_conn)
+    .await?;
+    drop(migration_conn);
+    migrated?;
+
+    Vote::ensure_genesis(conn).await?;
+    BallotKey::seal_stored(conn).await?;
+
+    Ok(MIGRATOR
+        .iter()
+        .map(|migration| migration.version)
+        .filter(|version| !applied.contains(version))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Datelike, NaiveDate};
+
+    use super::*;
+    use crate::{
+        testing::{self, test_pool},
+        CandidaturePosition, SqliteRepository, VoteCredential, VoteKind,
+    };
+
+    async fn insert_vote(
+        conn: &SqlitePool,
+        voter_id: Option<&str>,
+        ballot_token: Option<&str>,
+        candidature_id: Option<&str>,
+        kind: &str,
+    ) -> Result<(), sqlx::Error> {
+        let previous_hash = Vote::last_hash(conn).await?;
+        sqlx::query(
+            r#"
+            INSERT INTO votes (id, voter_id, ballot_token, candidature_id, candidature_position, year, hash, previous_hash, kind)
+            VALUES (?, ?, ?, ?, 'Presidente', 2026, ?, ?, ?)
+            "#,
+        )
+        .bind(uuid::Uuid::now_v7().to_string())
+        .bind(voter_id)
+        .bind(ballot_token)
+        .bind(candidature_id)
+        .bind(uuid::Uuid::now_v7().to_string())
+        .bind(previous_hash)
+        .bind(kind)
+        .execute(conn)
+        .await?;
+
+        Ok(())
+    }
+
+    fn is_check_violation(reason: sqlx::Error) -> bool {
+        reason.to_string().contains("CHECK constraint failed")
+    }
+
+    #[tokio::test]
+    async fn checks_positions_kinds_and_credentials() {
+        let conn = test_pool().await;
+        let party = testing::party(&conn, 55).await;
+        let candidate =
+            testing::candidate(&conn, &party, NaiveDate::from_ymd_opt(1960, 1, 1).unwrap()).await;
+        let candidature =
+            testing::candidature(&candidate, "55", CandidaturePosition::President, 2026);
+        candidature.create(&conn, testing::ACTOR).await.unwrap();
+        let voter = testing::voter(&conn, None).await;
+
+        let reason = sqlx::query(
+            r#"
+            INSERT INTO candidatures (id, party_id, candidate_id, image_url, code, year, position)
+            VALUES (?, ?, ?, '', '56', 2026, 'Imperador')
+            "#,
+        )
+        .bind(uuid::Uuid::now_v7().to_string())
+        .bind(&party.id)
+        .bind(&candidate.id)
+        .execute(&conn)
+        .await
+        .unwrap_err();
+        assert!(is_check_violation(reason));
+
+        // Both credentials, then neither.
+        let reason = insert_vote(
+            &conn,
+            Some(&voter.id),
+            Some("token"),
+            Some(&candidature.id),
+            "candidature",
+        )
+        .await
+        .unwrap_err();
+        assert!(is_check_violation(reason));
+        let reason = insert_vote(&conn, None, None, Some(&candidature.id), "candidature")
+            .await
+            .unwrap_err();
+        assert!(is_check_violation(reason));
+
+        // A blank vote names no candidature, and a candidature vote needs one.
+        let reason = insert_vote(&conn, Some(&voter.id), None, Some(&candidature.id), "blank")
+            .await
+            .unwrap_err();
+        assert!(is_check_violation(reason));
+        let reason = insert_vote(&conn, Some(&voter.id), None, None, "candidature")
+            .await
+            .unwrap_err();
+        assert!(is_check_violation(reason));
+        let reason = insert_vote(&conn, Some(&voter.id), None, None, "spoiled")
+            .await
+            .unwrap_err();
+        assert!(is_check_violation(reason));
+
+        insert_vote(&conn, Some(&voter.id), None, None, "blank")
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn migrate_restores_foreign_keys_and_reports_broken_references() {
+        let conn = test_pool().await;
+        let foreign_keys: bool = sqlx::query_scalar("PRAGMA foreign_keys")
+            .fetch_one(&conn)
+            .await
+            .unwrap();
+        assert!(foreign_keys);
+
+        // A reference broken while the checks were off, as a migration
+        // rebuilding a table could leave behind.
+        sqlx::query("PRAGMA foreign_keys = OFF")
+            .execute(&conn)
+            .await
+            .unwrap();
+        let mut candidate = crate::Candidate::build("Ana".to_string(), "Souza".to_string());
+        candidate.party_id = Some(uuid::Uuid::now_v7().to_string());
+        candidate.create(&conn, testing::ACTOR).await.unwrap();
+        sqlx::query("PRAGMA foreign_keys = ON")
+            .execute(&conn)
+            .await
+            .unwrap();
+
+        let reason = migrate(&conn).await.unwrap_err();
+        assert!(reason.to_string().contains("table candidates"));
+        let foreign_keys: bool = sqlx::query_scalar("PRAGMA foreign_keys")
+            .fetch_one(&conn)
+            .await
+            .unwrap();
+        assert!(foreign_keys);
+    }
+
+    #[tokio::test]
+    async fn reseeds_the_genesis_vote_for_the_year_of_setup() {
+        let conn = test_pool().await;
+        let genesis = || async {
+            sqlx::query(
+                "SELECT year, hash FROM votes WHERE id = '00000000-0000-0000-0000-000000000000'",
+            )
+            .fetch_one(&conn)
+            .await
+            .map(|row| (row.get::<i32, _>("year"), row.get::<String, _>("hash")))
+            .unwrap()
+        };
+        let year = chrono::Utc::now().year();
+        assert_eq!(genesis().await, (year, "GENESIS".to_string()));
+
+        let voter = testing::voter(&conn, None).await;
+        let mut vote = Vote::build(
+            &conn,
+            &SqliteRepository::new(conn.clone()),
+            VoteCredential::Voter(voter.id.clone()),
+            VoteKind::Blank,
+            None,
+            CandidaturePosition::President,
+        )
+        .await
+        .unwrap();
+        vote.create(&conn).await.unwrap();
+        assert_eq!(vote.previous_hash, "GENESIS");
+
+        // Migrating again leaves the chain and its genesis vote alone.
+        assert!(migrate(&conn).await.unwrap().is_empty());
+        assert_eq!(genesis().await, (year, "GENESIS".to_string()));
+        assert_eq!(Vote::verify_chain(&conn).await.unwrap(), 1);
+    }
+}
@@ -0,0 +1,198 @@
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::{query::Query, sqlite::SqliteArguments, Sqlite};
+
+const DEFAULT_LIMIT: i64 = 50;
+const MAX_LIMIT: i64 = 200;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum SortOrder {
+    #[serde(rename = "asc")]
+    Asc,
+    #[serde(rename = "desc")]
+    Desc,
+}
+
+impl SortOrder {
+    fn sql(&self) -> (&'static str, &'static str) {
+        match self {
+            SortOrder::Asc => ("ASC", ">"),
+            SortOrder::Desc => ("DESC", "<"),
+        }
+    }
+}
+
+/// Position after the last item of a page: the sort key of that item and
+/// its id, which breaks ties. Sent to clients as an opaque hex string.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Cursor {
+    pub key: Value,
+    pub id: String,
+}
+
+impl Cursor {
+    pub fn encode(&self) -> String {
+        hex::encode(serde_json::to_vec(self).expect("cursor serializes"))
+    }
+
+    pub fn decode(cursor: &str) -> Result<Cursor, anyhow::Error> {
+        let bytes = hex::decode(cursor).map_err(|_| anyhow!("invalid cursor"))?;
+        serde_json::from_slice(&bytes).map_err(|_| anyhow!("invalid cursor"))
+    }
+}
+
+/// Paging parameters shared by list endpoints.
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct PageRequest {
+    pub limit: Option<i64>,
+    pub cursor: Option<String>,
+    pub order: Option<SortOrder>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    /// Pass as `cursor` to get the next page; absent on the last one.
+    pub next_cursor: Option<String>,
+}
+
+impl PageRequest {
+    pub fn limit(&self) -> i64 {
+        self.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT)
+    }
+
+    pub fn cursor(&self) -> Result<Option<Cursor>, anyhow::Error> {
+        self.cursor.as_deref().map(Cursor::decode).transpose()
+    }
+
+    /// Keyset clause and ORDER BY/LIMIT tail for a query over rows with an
+    /// `id` column, sorted by `column`. The clause takes the cursor key and
+    /// id as parameters `?{first}` and `?{first + 1}`, and the limit is
+    /// `?{first + 2}`; bind them with `bind_page`.
+    pub fn keyset(&self, column: &str, default: SortOrder, first: usize) -> (String, String) {
+        let (direction, operator) = self.order.unwrap_or(default).sql();
+        let (key, id, limit) = (first, first + 1, first + 2);
+        (
+            format!(
+                "(?{key} IS NULL OR {column} {operator} ?{key} OR ({column} = ?{key} AND id {operator} ?{id}))",
+            ),
+            format!("ORDER BY {column} {direction}, id {direction} LIMIT ?{limit}"),
+        )
+    }
+
+    /// Binds the cursor and one more row than the limit, so `page` can tell
+    /// whether another page follows.
+    pub fn bind_page<'q>(
+        &self,
+        query: Query<'q, Sqlite, SqliteArguments<'q>>,
+        cursor: Option<Cursor>,
+    ) -> Query<'q, Sqlite, SqliteArguments<'q>> {
+        let (key, id) = match cursor {
+            Some(cursor) => (Some(cursor.key), Some(cursor.id)),
+            None => (None, None),
+        };
+        let query = match key {
+            Some(Value::Number(number)) if number.is_i64() => query.bind(number.as_i64()),
+            Some(Value::Number(number)) => query.bind(number.as_f64()),
+            Some(Value::String(key)) => query.bind(key),
+            _ => query.bind(None::<String>),
+        };
+        query.bind(id).bind(self.limit() + 1)
+    }
+
+    /// Turns the rows fetched with `bind_page` into a page.
+    pub fn page<T>(&self, mut items: Vec<(T, Cursor)>) -> Page<T> {
+        let next_cursor = if items.len() as i64 > self.limit() {
+            items.truncate(self.limit() as usize);
+            items.last().map(|(_, cursor)| cursor.encode())
+        } else {
+            None
+        };
+        Page {
+            items: items.into_iter().map(|(item, _)| item).collect(),
+            next_cursor,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use sqlx::{Row, SqlitePool};
+
+    use super::*;
+    use crate::testing::test_pool;
+
+    #[test]
+    fn cursors_round_trip() {
+        for key in [json!("Souza"), json!(42), json!(0.5), Value::Null] {
+            let cursor = Cursor {
+                key: key.clone(),
+                id: "01a151bf-1531-7353-80db-6c18bd00fc75".to_string(),
+            };
+            let decoded = Cursor::decode(&cursor.encode()).unwrap();
+            assert_eq!(decoded.key, key);
+            assert_eq!(decoded.id, cursor.id);
+        }
+
+        assert!(Cursor::decode("not hex").is_err());
+        assert!(Cursor::decode(&hex::encode("{\"key\": 1}")).is_err());
+    }
+
+    /// Every id of `scores`, walked two at a time in `order`.
+    async fn walk(conn: &SqlitePool, order: SortOrder) -> Vec<String> {
+        let mut ids = Vec::new();
+        let mut request = PageRequest {
+            limit: Some(2),
+            cursor: None,
+            order: Some(order),
+        };
+        loop {
+            let (clause, tail) = request.keyset("score", SortOrder::Asc, 1);
+            let sql = format!("SELECT id, score FROM scores WHERE {} {}", clause, tail);
+            let rows = request
+                .bind_page(sqlx::query(&sql), request.cursor().unwrap())
+                .fetch_all(conn)
+                .await
+                .unwrap();
+            let page = request.page(
+                rows.iter()
+                    .map(|row| {
+                        let id: String = row.get("id");
+                        let key = json!(row.get::<i64, _>("score"));
+                        (id.clone(), Cursor { key, id })
+                    })
+                    .collect(),
+            );
+            ids.extend(page.items);
+            match page.next_cursor {
+                Some(cursor) => request.cursor = Some(cursor),
+                None => return ids,
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn pages_through_ties_without_gaps() {
+        let conn = test_pool().await;
+        sqlx::query("CREATE TABLE scores (id TEXT PRIMARY KEY, score INTEGER NOT NULL)")
+            .execute(&conn)
+            .await
+            .unwrap();
+        for (id, score) in [("a", 1), ("b", 2), ("c", 2), ("d", 2), ("e", 3)] {
+            sqlx::query("INSERT INTO scores (id, score) VALUES (?, ?)")
+                .bind(id)
+                .bind(score)
+                .execute(&conn)
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(walk(&conn, SortOrder::Asc).await, ["a", "b", "c", "d", "e"]);
+        assert_eq!(
+            walk(&conn, SortOrder::Desc).await,
+            ["e", "d", "c", "b", "a"]
+        );
+    }
+}
@@ -1,13 +1,19 @@
+use anyhow::anyhow;
 use serde::{Deserialize, Serialize};
 use sqlx::SqlitePool;
 use uuid::Uuid;
 
+use crate::AuditEvent;
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Party {
     pub id: String,
     pub name: String,
     pub description: String,
     pub acronym: String,
+    /// Two-digit number assigned by the electoral court; every candidature
+    /// code of the party starts with it.
+    pub number: Option<i32>,
 }
 
 impl Party {
@@ -17,22 +23,70 @@ impl Party {
             name,
             description,
             acronym,
+            number: None,
+        }
+    }
+    pub(crate) fn ensure_number(&self) -> Result<(), anyhow::Error> {
+        if let Some(number) = self.number {
+            if !(10..=99).contains(&number) {
+                return Err(anyhow!("party number must have two digits"));
+            }
         }
+        Ok(())
     }
-    pub async fn create<'a>(&self, conn: &'a SqlitePool) -> Result<(), sqlx::Error> {
-        sqlx::query(
+
+    pub async fn create(&self, conn: &SqlitePool, actor: &str) -> Result<(), anyhow::Error> {
+        self.ensure_number()?;
+
+        let mut tx = conn.begin().await?;
+        sqlx::query!(
             r#"
-            INSERT INTO parties (id, name, description, acronym)
-            VALUES (?, ?, ?, ?)
+            INSERT INTO parties (id, name, description, acronym, number)
+            VALUES (?, ?, ?, ?, ?)
             "#,
+            self.id,
+            self.name,
+            self.description,
+            self.acronym,
+            self.number,
         )
-        .bind(&self.id)
-        .bind(&self.name)
-        .bind(&self.description)
-        .bind(&self.acronym)
-        .execute(conn)
+        .execute(&mut *tx)
         .await?;
 
+        AuditEvent::record(
+            &mut tx,
+            "party",
+            &self.id,
+            "create",
+            actor,
+            None,
+            serde_json::to_value(self).ok(),
+        )
+        .await?;
+
+        tx.commit().await?;
+
         Ok(())
     }
+
+    pub async fn find(conn: &SqlitePool, id: &str) -> Result<Option<Party>, sqlx::Error> {
+        sqlx::query_as!(
+            Party,
+            r#"
+            SELECT
+                id AS "id!: String",
+                name,
+                description,
+                acronym,
+                number AS "number: i32"
+            FROM
+                parties
+            WHERE
+                id = ?
+            "#,
+            id
+        )
+        .fetch_optional(conn)
+        .await
+    }
 }
@@ -0,0 +1,113 @@
+mod postgres;
+mod sqlite;
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+
+use crate::{
+    elgamal::Ciphertext, Candidate, Candidature, CandidaturePosition, Election, Party, Vote, Voter,
+};
+
+pub use postgres::PostgresRepository;
+pub use sqlite::SqliteRepository;
+
+/// Storage of the electoral registry and the vote chain, independent of the
+/// database behind it. Writes are audited like the model methods they stand
+/// for, with `actor` recorded in the audit log.
+///
+/// Elections, ballot tokens, trustees and the other election machinery are
+/// still SQLite only and go through the models directly; backends take the
+/// election a write belongs to from the caller.
+#[async_trait]
+pub trait Repository: Send + Sync {
+    /// Name of the backend, as reported by `bbox repository check`.
+    fn backend(&self) -> &'static str;
+
+    async fn create_party(&self, party: &Party, actor: &str) -> Result<(), anyhow::Error>;
+    async fn find_party(&self, id: &str) -> Result<Option<Party>, anyhow::Error>;
+
+    async fn create_candidate(
+        &self,
+        candidate: &Candidate,
+        actor: &str,
+    ) -> Result<(), anyhow::Error>;
+    async fn find_candidate(&self, id: &str) -> Result<Option<Candidate>, anyhow::Error>;
+
+    /// Registers the candidature after the eligibility rules of
+    /// `Candidature::create`, for `election` or, without one, as of today.
+    async fn create_candidature(
+        &self,
+        candidature: &Candidature,
+        election: Option<&Election>,
+        actor: &str,
+    ) -> Result<(), anyhow::Error>;
+    /// See `Candidature::find_by_code`.
+    async fn find_candidature(
+        &self,
+        code: &str,
+        position: &CandidaturePosition,
+        district: Option<&str>,
+        year: i32,
+        election_id: Option<&str>,
+    ) -> Result<Option<Candidature>, anyhow::Error>;
+    async fn list_candidatures(
+        &self,
+        position: &CandidaturePosition,
+        year: i32,
+    ) -> Result<Vec<Candidature>, anyhow::Error>;
+
+    async fn create_voter(&self, voter: &Voter, actor: &str) -> Result<(), anyhow::Error>;
+    async fn find_voter(&self, id: &str) -> Result<Option<Voter>, anyhow::Error>;
+    /// Looks a voter up by CPF, with or without punctuation.
+    async fn find_voter_by_national_id(
+        &self,
+        national_id: &str,
+    ) -> Result<Option<Voter>, anyhow::Error>;
+    /// Looks a voter up by título de eleitor, with or without spaces.
+    async fn find_voter_by_voter_card(
+        &self,
+        voter_card: &str,
+    ) -> Result<Option<Voter>, anyhow::Error>;
+
+    /// Appends a vote to the chain, linking and signing it with `Vote::link`
+    /// while holding the backend's write lock on the chain. Encrypted
+    /// ballots are added to their election's tallies in the same
+    /// transaction.
+    async fn create_vote(&self, vote: &mut Vote) -> Result<(), anyhow::Error>;
+    async fn last_vote_hash(&self) -> Result<String, anyhow::Error>;
+    async fn has_voted(
+        &self,
+        voter_id: &str,
+        position: &CandidaturePosition,
+        year: i32,
+    ) -> Result<bool, anyhow::Error>;
+    /// Whether the voter already took part for the position in `election`,
+    /// or outside an election in `year`, as `Vote::build` checks before
+    /// signing a ballot.
+    async fn has_participated(
+        &self,
+        voter_id: &str,
+        position: &CandidaturePosition,
+        election: Option<&Election>,
+        year: i32,
+    ) -> Result<bool, anyhow::Error>;
+    /// Every vote in creation order, genesis vote first, for `Vote::verify`.
+    async fn list_votes(&self) -> Result<Vec<Vote>, anyhow::Error>;
+    /// Sum of the encrypted ballots cast in the election, per candidature.
+    async fn encrypted_tallies(
+        &self,
+        election_id: &str,
+    ) -> Result<Vec<(String, Ciphertext)>, anyhow::Error>;
+}
+
+/// Opens the repository `database_url` points to: PostgreSQL for
+/// `postgres://` and `postgresql://` URLs, SQLite otherwise. The PostgreSQL
+/// schema in `migrations/postgres` is applied on connect.
+pub async fn connect_repository(database_url: &str) -> Result<Arc<dyn Repository>, anyhow::Error> {
+    if database_url.starts_with("postgres://") || database_url.starts_with("postgresql://") {
+        Ok(Arc::new(PostgresRepository::connect(database_url).await?))
+    } else {
+        Ok(Arc::new(SqliteRepository::connect(database_url).await?))
+    }
+}
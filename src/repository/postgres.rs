@@ -0,0 +1,778 @@
+use std::collections::BTreeMap;
+
+use async_trait::async_trait;
+use serde_json::Value;
+use sqlx::{
+    postgres::{PgPool, PgRow},
+    PgConnection, Row,
+};
+use validator::Validate;
+
+use super::Repository;
+use crate::{
+    elgamal::{Ciphertext, EncodedCiphertext},
+    normalize_document, AlreadyVoted, AuditEvent, Candidate, Candidature, CandidatureError,
+    CandidaturePosition, Election, Party, Vote, VoteKind, Voter,
+};
+
+/// PostgreSQL storage for deployments that outgrow a single SQLite file.
+/// Elections only live in SQLite; callers pass them in where the rules
+/// depend on them.
+#[derive(Debug, Clone)]
+pub struct PostgresRepository {
+    conn: PgPool,
+}
+
+impl PostgresRepository {
+    pub fn new(conn: PgPool) -> PostgresRepository {
+        PostgresRepository { conn }
+    }
+
+    /// Connects and brings the schema up to date.
+    pub async fn connect(database_url: &str) -> Result<PostgresRepository, anyhow::Error> {
+        let conn = PgPool::connect(database_url).await?;
+        sqlx::migrate!("./migrations/postgres").run(&conn).await?;
+        Ok(PostgresRepository::new(conn))
+    }
+
+    /// Same chain as `AuditEvent::record`, in the PostgreSQL audit log.
+    async fn audit(
+        conn: &mut PgConnection,
+        entity: &str,
+        entity_id: &str,
+        action: &str,
+        actor: &str,
+        after: Option<Value>,
+    ) -> Result<(), sqlx::Error> {
+        // Serializes writers so two events never link to the same hash.
+        sqlx::query("LOCK TABLE audit_events IN EXCLUSIVE MODE")
+            .execute(&mut *conn)
+            .await?;
+        let previous_hash = sqlx::query("SELECT hash FROM audit_events ORDER BY seq DESC LIMIT 1")
+            .fetch_optional(&mut *conn)
+            .await?
+            .map(|row| row.get::<String, _>("hash"));
+
+        let event = AuditEvent::build(entity, entity_id, action, actor, None, after, previous_hash);
+
+        sqlx::query(
+            r#"
+            INSERT INTO audit_events (id, entity, entity_id, action, actor, before, after, hash, previous_hash, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+            "#,
+        )
+        .bind(&event.id)
+        .bind(&event.entity)
+        .bind(&event.entity_id)
+        .bind(&event.action)
+        .bind(&event.actor)
+        .bind(event.before.as_ref().map(Value::to_string))
+        .bind(event.after.as_ref().map(Value::to_string))
+        .bind(&event.hash)
+        .bind(&event.previous_hash)
+        .bind(event.created_at)
+        .execute(&mut *conn)
+        .await?;
+
+        Ok(())
+    }
+
+    fn candidate_from_row(row: &PgRow) -> Candidate {
+        Candidate {
+            id: row.get("id"),
+            first_name: row.get("first_name"),
+            last_name: row.get("last_name"),
+            birth_date: row.get("birth_date"),
+            party_id: row.get("party_id"),
+            ballot_name: row.get("ballot_name"),
+            biography: row.get("biography"),
+            proposals_url: row.get("proposals_url"),
+            photo_url: row.get("photo_url"),
+        }
+    }
+
+    fn candidature_from_row(row: &PgRow) -> Candidature {
+        Candidature {
+            id: row.get("id"),
+            party_id: row.get("party_id"),
+            candidate_id: row.get("candidate_id"),
+            code: row.get("code"),
+            position: CandidaturePosition::from(row.get::<String, _>("position")),
+            year: row.get("year"),
+            image_url: row.get("image_url"),
+            election_id: row.get("election_id"),
+            district: row.get("district"),
+        }
+    }
+
+    fn voter_from_row(row: &PgRow) -> Voter {
+        Voter {
+            id: row.get("id"),
+            first_name: row.get("first_name"),
+            last_name: row.get("last_name"),
+            mother_name: row.get("mother_name"),
+            father_name: row.get("father_name"),
+            birth_date: row.get("birth_date"),
+            district: row.get("district"),
+            section: row.get("section"),
+            national_id: row.get("national_id"),
+            voter_card: row.get("voter_card"),
+        }
+    }
+
+    fn vote_from_row(row: &PgRow) -> Vote {
+        Vote {
+            id: row.get("id"),
+            voter_id: row.get("voter_id"),
+            candidature_id: row.get("candidature_id"),
+            candidature_position: CandidaturePosition::from(
+                row.get::<String, _>("candidature_position"),
+            ),
+            hash: row.get("hash"),
+            previous_hash: row.get("previous_hash"),
+            hash_version: row.get("hash_version"),
+            year: row.get("year"),
+            created_at: row.get("created_at"),
+            ballot_token: row.get("ballot_token"),
+            election_id: row.get("election_id"),
+            encrypted_ballot: row.get("encrypted_ballot"),
+            kind: VoteKind::from(row.get::<String, _>("kind")),
+            district: row.get("district"),
+            section: row.get("section"),
+            key_id: row.get("key_id"),
+        }
+    }
+
+    async fn find_voter_by(&self, column: &str, value: &str) -> Result<Option<Voter>, sqlx::Error> {
+        let row = sqlx::query(&format!(
+            r#"
+            SELECT
+                id,
+                first_name,
+                last_name,
+                mother_name,
+                father_name,
+                birth_date,
+                district,
+                section,
+                national_id,
+                voter_card
+            FROM
+                voters
+            WHERE
+                {} = $1
+            "#,
+            column
+        ))
+        .bind(value)
+        .fetch_optional(&self.conn)
+        .await?;
+
+        Ok(row.as_ref().map(PostgresRepository::voter_from_row))
+    }
+
+    /// Same as `Election::accumulate`, in the caller's transaction so the
+    /// tally moves with the vote insert.
+    async fn accumulate(
+        conn: &mut PgConnection,
+        election_id: &str,
+        ballot: &BTreeMap<String, EncodedCiphertext>,
+    ) -> Result<(), anyhow::Error> {
+        for (candidature_id, encoded) in ballot.iter() {
+            let current = sqlx::query(
+                r#"
+                SELECT
+                    c1,
+                    c2
+                FROM
+                    encrypted_tallies
+                WHERE
+                    election_id = $1 AND
+                    candidature_id = $2
+                "#,
+            )
+            .bind(election_id)
+            .bind(candidature_id)
+            .fetch_optional(&mut *conn)
+            .await?;
+
+            let current = match current {
+                Some(row) => Ciphertext::decode(&EncodedCiphertext {
+                    c1: row.get("c1"),
+                    c2: row.get("c2"),
+                })?,
+                None => Ciphertext::zero(),
+            };
+            let tally = (current + Ciphertext::decode(encoded)?).encode();
+
+            sqlx::query(
+                r#"
+                INSERT INTO encrypted_tallies (election_id, candidature_id, c1, c2)
+                VALUES ($1, $2, $3, $4)
+                ON CONFLICT (election_id, candidature_id)
+                DO UPDATE SET c1 = excluded.c1, c2 = excluded.c2
+                "#,
+            )
+            .bind(election_id)
+            .bind(candidature_id)
+            .bind(&tally.c1)
+            .bind(&tally.c2)
+            .execute(&mut *conn)
+            .await?;
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Repository for PostgresRepository {
+    fn backend(&self) -> &'static str {
+        "postgres"
+    }
+
+    async fn create_party(&self, party: &Party, actor: &str) -> Result<(), anyhow::Error> {
+        party.ensure_number()?;
+
+        let mut tx = self.conn.begin().await?;
+        sqlx::query(
+            r#"
+            INSERT INTO parties (id, name, description, acronym, number)
+            VALUES ($1, $2, $3, $4, $5)
+            "#,
+        )
+        .bind(&party.id)
+        .bind(&party.name)
+        .bind(&party.description)
+        .bind(&party.acronym)
+        .bind(party.number)
+        .execute(&mut *tx)
+        .await?;
+
+        PostgresRepository::audit(
+            &mut tx,
+            "party",
+            &party.id,
+            "create",
+            actor,
+            serde_json::to_value(party).ok(),
+        )
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn find_party(&self, id: &str) -> Result<Option<Party>, anyhow::Error> {
+        let row = sqlx::query(
+            r#"
+            SELECT
+                id,
+                name,
+                description,
+                acronym,
+                number
+            FROM
+                parties
+            WHERE
+                id = $1
+            "#,
+        )
+        .bind(id)
+        .fetch_optional(&self.conn)
+        .await?;
+
+        Ok(row.map(|row| Party {
+            id: row.get("id"),
+            name: row.get("name"),
+            description: row.get("description"),
+            acronym: row.get("acronym"),
+            number: row.get("number"),
+        }))
+    }
+
+    async fn create_candidate(
+        &self,
+        candidate: &Candidate,
+        actor: &str,
+    ) -> Result<(), anyhow::Error> {
+        let mut tx = self.conn.begin().await?;
+        sqlx::query(
+            r#"
+            INSERT INTO candidates (id, first_name, last_name, birth_date, party_id, ballot_name, biography, proposals_url, photo_url)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+            "#,
+        )
+        .bind(&candidate.id)
+        .bind(&candidate.first_name)
+        .bind(&candidate.last_name)
+        .bind(candidate.birth_date)
+        .bind(&candidate.party_id)
+        .bind(&candidate.ballot_name)
+        .bind(&candidate.biography)
+        .bind(&candidate.proposals_url)
+        .bind(&candidate.photo_url)
+        .execute(&mut *tx)
+        .await?;
+
+        PostgresRepository::audit(
+            &mut tx,
+            "candidate",
+            &candidate.id,
+            "create",
+            actor,
+            serde_json::to_value(candidate).ok(),
+        )
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn find_candidate(&self, id: &str) -> Result<Option<Candidate>, anyhow::Error> {
+        let row = sqlx::query(
+            r#"
+            SELECT
+                id,
+                first_name,
+                last_name,
+                birth_date,
+                party_id,
+                ballot_name,
+                biography,
+                proposals_url,
+                photo_url
+            FROM
+                candidates
+            WHERE
+                id = $1
+            "#,
+        )
+        .bind(id)
+        .fetch_optional(&self.conn)
+        .await?;
+
+        Ok(row.as_ref().map(PostgresRepository::candidate_from_row))
+    }
+
+    async fn create_candidature(
+        &self,
+        candidature: &Candidature,
+        election: Option<&Election>,
+        actor: &str,
+    ) -> Result<(), anyhow::Error> {
+        let candidate = self
+            .find_candidate(&candidature.candidate_id)
+            .await?
+            .ok_or(CandidatureError::CandidateNotFound)?;
+        let party = self.find_party(&candidature.party_id).await?;
+        candidature.ensure_rules(
+            &candidate,
+            party.as_ref(),
+            Candidature::election_date(election),
+        )?;
+
+        let mut tx = self.conn.begin().await?;
+        sqlx::query(
+            r#"
+            INSERT INTO candidatures (id, party_id, candidate_id, code, position, year, image_url, election_id, district)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+            "#,
+        )
+        .bind(&candidature.id)
+        .bind(&candidature.party_id)
+        .bind(&candidature.candidate_id)
+        .bind(&candidature.code)
+        .bind(candidature.position.to_string())
+        .bind(candidature.year)
+        .bind(&candidature.image_url)
+        .bind(&candidature.election_id)
+        .bind(&candidature.district)
+        .execute(&mut *tx)
+        .await
+        .map_err(CandidatureError::from_insert)?;
+
+        PostgresRepository::audit(
+            &mut tx,
+            "candidature",
+            &candidature.id,
+            "create",
+            actor,
+            serde_json::to_value(candidature).ok(),
+        )
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn find_candidature(
+        &self,
+        code: &str,
+        position: &CandidaturePosition,
+        district: Option<&str>,
+        year: i32,
+        election_id: Option<&str>,
+    ) -> Result<Option<Candidature>, anyhow::Error> {
+        let row = sqlx::query(
+            r#"
+            SELECT
+                id,
+                party_id,
+                candidate_id,
+                code,
+                position,
+                year,
+                image_url,
+                election_id,
+                district
+            FROM
+                candidatures
+            WHERE
+                code = $1 AND
+                position = $2 AND
+                (district IS NULL OR district = $3) AND
+                year = $4 AND
+                ($5::TEXT IS NULL OR election_id IS NULL OR election_id = $5)
+            ORDER BY
+                district IS NULL
+            LIMIT 1
+            "#,
+        )
+        .bind(code)
+        .bind(position.to_string())
+        .bind(district)
+        .bind(year)
+        .bind(election_id)
+        .fetch_optional(&self.conn)
+        .await?;
+
+        Ok(row.as_ref().map(PostgresRepository::candidature_from_row))
+    }
+
+    async fn list_candidatures(
+        &self,
+        position: &CandidaturePosition,
+        year: i32,
+    ) -> Result<Vec<Candidature>, anyhow::Error> {
+        let rows = sqlx::query(
+            r#"
+            SELECT
+                id,
+                party_id,
+                candidate_id,
+                code,
+                position,
+                year,
+                image_url,
+                election_id,
+                district
+            FROM
+                candidatures
+            WHERE
+                position = $1 AND
+                year = $2
+            ORDER BY
+                code
+            "#,
+        )
+        .bind(position.to_string())
+        .bind(year)
+        .fetch_all(&self.conn)
+        .await?;
+
+        Ok(rows
+            .iter()
+            .map(PostgresRepository::candidature_from_row)
+            .collect())
+    }
+
+    async fn create_voter(&self, voter: &Voter, actor: &str) -> Result<(), anyhow::Error> {
+        voter.validate()?;
+
+        let mut tx = self.conn.begin().await?;
+        sqlx::query(
+            r#"
+            INSERT INTO voters (id, first_name, last_name, mother_name, father_name, birth_date, district, section, national_id, voter_card)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+            "#,
+        )
+        .bind(&voter.id)
+        .bind(&voter.first_name)
+        .bind(&voter.last_name)
+        .bind(&voter.mother_name)
+        .bind(&voter.father_name)
+        .bind(voter.birth_date)
+        .bind(&voter.district)
+        .bind(&voter.section)
+        .bind(&voter.national_id)
+        .bind(&voter.voter_card)
+        .execute(&mut *tx)
+        .await?;
+
+        PostgresRepository::audit(
+            &mut tx,
+            "voter",
+            &voter.id,
+            "create",
+            actor,
+            serde_json::to_value(voter).ok(),
+        )
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn find_voter(&self, id: &str) -> Result<Option<Voter>, anyhow::Error> {
+        Ok(self.find_voter_by("id", id).await?)
+    }
+
+    async fn find_voter_by_national_id(
+        &self,
+        national_id: &str,
+    ) -> Result<Option<Voter>, anyhow::Error> {
+        Ok(self
+            .find_voter_by("national_id", &normalize_document(national_id))
+            .await?)
+    }
+
+    async fn find_voter_by_voter_card(
+        &self,
+        voter_card: &str,
+    ) -> Result<Option<Voter>, anyhow::Error> {
+        Ok(self
+            .find_voter_by("voter_card", &normalize_document(voter_card))
+            .await?)
+    }
+
+    async fn create_vote(&self, vote: &mut Vote) -> Result<(), anyhow::Error> {
+        let mut tx = self.conn.begin().await?;
+        // Serializes writers so two votes never link to the same hash.
+        sqlx::query("LOCK TABLE votes IN EXCLUSIVE MODE")
+            .execute(&mut *tx)
+            .await?;
+        let head = sqlx::query(
+            "SELECT hash, created_at FROM votes ORDER BY created_at DESC, id DESC LIMIT 1",
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+        vote.link(head.get("hash"), head.get("created_at"));
+        if let Some(voter_id) = &vote.voter_id {
+            // A participation outside an election counts for every election
+            // of its year, which the unique index cannot express; the table
+            // lock makes this check safe.
+            let participated = sqlx::query(
+                r#"
+                SELECT
+                    1
+                FROM
+                    participations
+                WHERE
+                    voter_id = $1 AND
+                    position = $2 AND
+                    year = $3 AND
+                    (election_id IS NULL OR $4::TEXT IS NULL)
+                "#,
+            )
+            .bind(voter_id)
+            .bind(vote.candidature_position.to_string())
+            .bind(vote.year)
+            .bind(&vote.election_id)
+            .fetch_optional(&mut *tx)
+            .await?;
+            if participated.is_some() {
+                return Err(AlreadyVoted.into());
+            }
+
+            let recorded = sqlx::query(
+                r#"
+                INSERT INTO participations (voter_id, position, election_id, year)
+                VALUES ($1, $2, $3, $4)
+                "#,
+            )
+            .bind(voter_id)
+            .bind(vote.candidature_position.to_string())
+            .bind(&vote.election_id)
+            .bind(vote.year)
+            .execute(&mut *tx)
+            .await;
+            match recorded {
+                Ok(_) => {}
+                Err(sqlx::Error::Database(reason)) if reason.is_unique_violation() => {
+                    return Err(AlreadyVoted.into());
+                }
+                Err(reason) => return Err(reason.into()),
+            }
+        }
+
+        sqlx::query(
+            r#"
+            INSERT INTO votes (id, voter_id, candidature_id, candidature_position, hash, previous_hash, hash_version, year, created_at, ballot_token, election_id, encrypted_ballot, kind, district, section, key_id)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16)
+            "#,
+        )
+        .bind(&vote.id)
+        .bind(&vote.voter_id)
+        .bind(&vote.candidature_id)
+        .bind(vote.candidature_position.to_string())
+        .bind(&vote.hash)
+        .bind(&vote.previous_hash)
+        .bind(vote.hash_version)
+        .bind(vote.year)
+        .bind(vote.created_at)
+        .bind(&vote.ballot_token)
+        .bind(&vote.election_id)
+        .bind(&vote.encrypted_ballot)
+        .bind(vote.kind.to_string())
+        .bind(&vote.district)
+        .bind(&vote.section)
+        .bind(&vote.key_id)
+        .execute(&mut *tx)
+        .await?;
+
+        if let (Some(election_id), Some(ballot)) = (&vote.election_id, &vote.encrypted_ballot) {
+            let ballot: BTreeMap<String, EncodedCiphertext> = serde_json::from_str(ballot)?;
+            PostgresRepository::accumulate(&mut tx, election_id, &ballot).await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn last_vote_hash(&self) -> Result<String, anyhow::Error> {
+        let row = sqlx::query("SELECT hash FROM votes ORDER BY created_at DESC, id DESC LIMIT 1")
+            .fetch_one(&self.conn)
+            .await?;
+
+        Ok(row.get("hash"))
+    }
+
+    async fn has_voted(
+        &self,
+        voter_id: &str,
+        position: &CandidaturePosition,
+        year: i32,
+    ) -> Result<bool, anyhow::Error> {
+        let row = sqlx::query(
+            r#"
+            SELECT
+                voter_id
+            FROM
+                participations
+            WHERE
+                voter_id = $1 AND
+                position = $2 AND
+                year = $3
+            "#,
+        )
+        .bind(voter_id)
+        .bind(position.to_string())
+        .bind(year)
+        .fetch_optional(&self.conn)
+        .await?;
+
+        Ok(row.is_some())
+    }
+
+    async fn has_participated(
+        &self,
+        voter_id: &str,
+        position: &CandidaturePosition,
+        election: Option<&Election>,
+        year: i32,
+    ) -> Result<bool, anyhow::Error> {
+        // Participations here are all recorded as first round, so rows
+        // outside an election only count against a first round.
+        let row = sqlx::query(
+            r#"
+            SELECT
+                voter_id
+            FROM
+                participations
+            WHERE
+                voter_id = $1 AND
+                position = $2 AND
+                (election_id = $3 OR (election_id IS NULL AND year = $4 AND $5 = 1))
+            "#,
+        )
+        .bind(voter_id)
+        .bind(position.to_string())
+        .bind(election.map(|election| &election.id))
+        .bind(year)
+        .bind(election.map_or(1, |election| election.round))
+        .fetch_optional(&self.conn)
+        .await?;
+
+        Ok(row.is_some())
+    }
+
+    async fn list_votes(&self) -> Result<Vec<Vote>, anyhow::Error> {
+        let rows = sqlx::query(
+            r#"
+            SELECT
+                id,
+                voter_id,
+                candidature_id,
+                candidature_position,
+                hash,
+                previous_hash,
+                hash_version,
+                year,
+                created_at,
+                ballot_token,
+                election_id,
+                encrypted_ballot,
+                kind,
+                district,
+                section,
+                key_id
+            FROM
+                votes
+            ORDER BY
+                created_at ASC,
+                id ASC
+            "#,
+        )
+        .fetch_all(&self.conn)
+        .await?;
+
+        Ok(rows.iter().map(PostgresRepository::vote_from_row).collect())
+    }
+
+    async fn encrypted_tallies(
+        &self,
+        election_id: &str,
+    ) -> Result<Vec<(String, Ciphertext)>, anyhow::Error> {
+        let rows = sqlx::query(
+            r#"
+            SELECT
+                candidature_id,
+                c1,
+                c2
+            FROM
+                encrypted_tallies
+            WHERE
+                election_id = $1
+            "#,
+        )
+        .bind(election_id)
+        .fetch_all(&self.conn)
+        .await?;
+
+        rows.iter()
+            .map(|row| {
+                let ciphertext = Ciphertext::decode(&EncodedCiphertext {
+                    c1: row.get("c1"),
+                    c2: row.get("c2"),
+                })?;
+                Ok((row.get("candidature_id"), ciphertext))
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,144 @@
+use async_trait::async_trait;
+use sqlx::SqlitePool;
+
+use super::Repository;
+use crate::{
+    elgamal::Ciphertext, Candidate, Candidature, CandidaturePosition, DatabaseConfig, Election,
+    Party, Vote, Voter,
+};
+
+/// The models themselves, over the pool the rest of the application uses.
+#[derive(Debug, Clone)]
+pub struct SqliteRepository {
+    conn: SqlitePool,
+}
+
+impl SqliteRepository {
+    pub fn new(conn: SqlitePool) -> SqliteRepository {
+        SqliteRepository { conn }
+    }
+
+    /// Opens the database with the pool settings of `DatabaseConfig`.
+    pub async fn connect(database_url: &str) -> Result<SqliteRepository, anyhow::Error> {
+        let conn = DatabaseConfig::load()?.connect(database_url).await?;
+        Ok(SqliteRepository::new(conn))
+    }
+}
+
+#[async_trait]
+impl Repository for SqliteRepository {
+    fn backend(&self) -> &'static str {
+        "sqlite"
+    }
+
+    async fn create_party(&self, party: &Party, actor: &str) -> Result<(), anyhow::Error> {
+        party.create(&self.conn, actor).await
+    }
+
+    async fn find_party(&self, id: &str) -> Result<Option<Party>, anyhow::Error> {
+        Ok(Party::find(&self.conn, id).await?)
+    }
+
+    async fn create_candidate(
+        &self,
+        candidate: &Candidate,
+        actor: &str,
+    ) -> Result<(), anyhow::Error> {
+        Ok(candidate.create(&self.conn, actor).await?)
+    }
+
+    async fn find_candidate(&self, id: &str) -> Result<Option<Candidate>, anyhow::Error> {
+        Ok(Candidate::find(&self.conn, id).await?)
+    }
+
+    async fn create_candidature(
+        &self,
+        candidature: &Candidature,
+        election: Option<&Election>,
+        actor: &str,
+    ) -> Result<(), anyhow::Error> {
+        Ok(candidature.create_for(&self.conn, election, actor).await?)
+    }
+
+    async fn find_candidature(
+        &self,
+        code: &str,
+        position: &CandidaturePosition,
+        district: Option<&str>,
+        year: i32,
+        election_id: Option<&str>,
+    ) -> Result<Option<Candidature>, anyhow::Error> {
+        Ok(
+            Candidature::find_by_code(&self.conn, code, position, district, year, election_id)
+                .await?,
+        )
+    }
+
+    async fn list_candidatures(
+        &self,
+        position: &CandidaturePosition,
+        year: i32,
+    ) -> Result<Vec<Candidature>, anyhow::Error> {
+        Ok(Candidature::list_running(&self.conn, position, year).await?)
+    }
+
+    async fn create_voter(&self, voter: &Voter, actor: &str) -> Result<(), anyhow::Error> {
+        voter.create(&self.conn, actor).await
+    }
+
+    async fn find_voter(&self, id: &str) -> Result<Option<Voter>, anyhow::Error> {
+        Ok(Voter::find(&self.conn, id).await?)
+    }
+
+    async fn find_voter_by_national_id(
+        &self,
+        national_id: &str,
+    ) -> Result<Option<Voter>, anyhow::Error> {
+        Ok(Voter::find_by_national_id(&self.conn, national_id).await?)
+    }
+
+    async fn find_voter_by_voter_card(
+        &self,
+        voter_card: &str,
+    ) -> Result<Option<Voter>, anyhow::Error> {
+        Ok(Voter::find_by_voter_card(&self.conn, voter_card).await?)
+    }
+
+    async fn create_vote(&self, vote: &mut Vote) -> Result<(), anyhow::Error> {
+        vote.create(&self.conn).await
+    }
+
+    async fn last_vote_hash(&self) -> Result<String, anyhow::Error> {
+        Ok(Vote::last_hash(&self.conn).await?)
+    }
+
+    async fn has_voted(
+        &self,
+        voter_id: &str,
+        position: &CandidaturePosition,
+        year: i32,
+    ) -> Result<bool, anyhow::Error> {
+        Ok(Vote::has_voted(&self.conn, voter_id, position, year).await?)
+    }
+
+    async fn has_participated(
+        &self,
+        voter_id: &str,
+        position: &CandidaturePosition,
+        election: Option<&Election>,
+        year: i32,
+    ) -> Result<bool, anyhow::Error> {
+        Ok(Vote::has_participated(&self.conn, voter_id, position, election, year).await?)
+    }
+
+    async fn list_votes(&self) -> Result<Vec<Vote>, anyhow::Error> {
+        Ok(Vote::chain(&self.conn).await?)
+    }
+
+    async fn encrypted_tallies(
+        &self,
+        election_id: &str,
+    ) -> Result<Vec<(String, Ciphertext)>, anyhow::Error> {
+        Election::encrypted_tallies(&self.conn, election_id).await
+    }
+}
@@ -0,0 +1,375 @@
+use std::{collections::BTreeMap, fmt, str::FromStr, sync::Arc};
+
+use anyhow::anyhow;
+use arrow_array::{ArrayRef, Int32Array, Int64Array, RecordBatch, StringArray};
+use arrow_schema::{DataType, Field, Schema};
+use parquet::arrow::ArrowWriter;
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+
+use crate::Election;
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub enum ExportFormat {
+    #[serde(rename = "csv")]
+    Csv,
+    #[serde(rename = "jsonl")]
+    Jsonl,
+    #[serde(rename = "parquet")]
+    Parquet,
+}
+
+impl fmt::Display for ExportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let format = match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Jsonl => "jsonl",
+            ExportFormat::Parquet => "parquet",
+        };
+        write!(f, "{}", format)
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(format: &str) -> Result<ExportFormat, anyhow::Error> {
+        match format {
+            "csv" => Ok(ExportFormat::Csv),
+            "jsonl" => Ok(ExportFormat::Jsonl),
+            "parquet" => Ok(ExportFormat::Parquet),
+            _ => Err(anyhow!("unknown export format: {}", format)),
+        }
+    }
+}
+
+impl ExportFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "text/csv; charset=utf-8",
+            ExportFormat::Jsonl => "application/x-ndjson",
+            ExportFormat::Parquet => "application/vnd.apache.parquet",
+        }
+    }
+}
+
+/// First-round votes of a party for one position across years, in one
+/// district or, without one, everywhere.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PartyComparison {
+    pub party_acronym: String,
+    pub district: Option<String>,
+    pub votes: BTreeMap<i32, i64>,
+    /// Share of the valid votes of the position in the same district, 0-1.
+    pub share: BTreeMap<i32, f64>,
+    /// Votes and share in the last year minus the first.
+    pub votes_change: i64,
+    pub share_change: f64,
+}
+
+/// One flat line of results: the votes of a candidature in a district.
+/// Encrypted elections are only decrypted as a whole, so their rows have no
+/// district.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ResultRow {
+    pub election_id: String,
+    pub election_name: String,
+    pub year: i32,
+    /// 2 for a runoff. Exports from before rounds were recorded are all
+    /// first rounds.
+    #[serde(default = "first_round")]
+    pub round: i32,
+    pub district: Option<String>,
+    pub position: String,
+    pub candidature_code: String,
+    pub party_acronym: String,
+    pub candidate_name: String,
+    pub votes: i64,
+}
+
+fn first_round() -> i32 {
+    1
+}
+
+impl ResultRow {
+    /// Results of every election, or only `election_id` or those of `year`.
+    /// Elections under a results embargo are skipped unless
+    /// `include_embargoed` is set.
+    pub async fn list(
+        conn: &SqlitePool,
+        election_id: Option<&str>,
+        year: Option<i32>,
+        include_embargoed: bool,
+    ) -> Result<Vec<ResultRow>, sqlx::Error> {
+        let visible: Vec<String> = Election::list(conn)
+            .await?
+            .into_iter()
+            .filter(|election| include_embargoed || election.results_embargo().is_none())
+            .map(|election| election.id)
+            .collect();
+
+        let rows = sqlx::query_as!(
+            ResultRow,
+            r#"
+            SELECT
+                e.id AS "election_id!: String",
+                e.name AS election_name,
+                e.year AS "year!: i32",
+                e.round AS "round!: i32",
+                v.district,
+                v.candidature_position AS "position!: String",
+                ca.code AS candidature_code,
+                p.acronym AS party_acronym,
+                c.first_name || ' ' || c.last_name AS "candidate_name!: String",
+                COUNT(v.id) AS "votes!: i64"
+            FROM
+                votes v
+            JOIN
+                elections e ON e.id = v.election_id
+            JOIN
+                candidatures ca ON ca.id = v.candidature_id
+            JOIN
+                candidates c ON c.id = ca.candidate_id
+            JOIN
+                parties p ON p.id = ca.party_id
+            WHERE
+                e.tally_mode = 'plain' AND
+                (?1 IS NULL OR e.id = ?1) AND
+                (?2 IS NULL OR e.year = ?2)
+            GROUP BY
+                e.id,
+                v.district,
+                v.candidature_id
+            UNION ALL
+            SELECT
+                e.id,
+                e.name,
+                e.year,
+                e.round,
+                NULL,
+                ca.position,
+                ca.code,
+                p.acronym,
+                c.first_name || ' ' || c.last_name,
+                r.votes
+            FROM
+                election_results r
+            JOIN
+                elections e ON e.id = r.election_id
+            JOIN
+                candidatures ca ON ca.id = r.candidature_id
+            JOIN
+                candidates c ON c.id = ca.candidate_id
+            JOIN
+                parties p ON p.id = ca.party_id
+            WHERE
+                e.tally_mode = 'encrypted' AND
+                (?1 IS NULL OR e.id = ?1) AND
+                (?2 IS NULL OR e.year = ?2)
+            ORDER BY
+                3 DESC,
+                1 ASC,
+                5 ASC,
+                6 ASC,
+                10 DESC
+            "#,
+            election_id,
+            year
+        )
+        .fetch_all(conn)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .filter(|row| visible.contains(&row.election_id))
+            .collect())
+    }
+
+    pub fn export(rows: &[ResultRow], format: &ExportFormat) -> Result<Vec<u8>, anyhow::Error> {
+        match format {
+            ExportFormat::Csv => {
+                let mut writer = csv::Writer::from_writer(Vec::new());
+                for row in rows {
+                    writer.serialize(row)?;
+                }
+                Ok(writer.into_inner()?)
+            }
+            ExportFormat::Jsonl => {
+                let mut buf = Vec::new();
+                for row in rows {
+                    serde_json::to_writer(&mut buf, row)?;
+                    buf.push(b'\n');
+                }
+                Ok(buf)
+            }
+            ExportFormat::Parquet => ResultRow::to_parquet(rows),
+        }
+    }
+
+    fn to_parquet(rows: &[ResultRow]) -> Result<Vec<u8>, anyhow::Error> {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("election_id", DataType::Utf8, false),
+            Field::new("election_name", DataType::Utf8, false),
+            Field::new("year", DataType::Int32, false),
+            Field::new("round", DataType::Int32, false),
+            Field::new("district", DataType::Utf8, true),
+            Field::new("position", DataType::Utf8, false),
+            Field::new("candidature_code", DataType::Utf8, false),
+            Field::new("party_acronym", DataType::Utf8, false),
+            Field::new("candidate_name", DataType::Utf8, false),
+            Field::new("votes", DataType::Int64, false),
+        ]));
+
+        let strings = |value: fn(&ResultRow) -> &str| -> ArrayRef {
+            Arc::new(StringArray::from_iter_values(rows.iter().map(value)))
+        };
+        let columns: Vec<ArrayRef> = vec![
+            strings(|row| &row.election_id),
+            strings(|row| &row.election_name),
+            Arc::new(Int32Array::from_iter_values(
+                rows.iter().map(|row| row.year),
+            )),
+            Arc::new(Int32Array::from_iter_values(
+                rows.iter().map(|row| row.round),
+            )),
+            Arc::new(StringArray::from_iter(
+                rows.iter().map(|row| row.district.as_deref()),
+            )),
+            strings(|row| &row.position),
+            strings(|row| &row.candidature_code),
+            strings(|row| &row.party_acronym),
+            strings(|row| &row.candidate_name),
+            Arc::new(Int64Array::from_iter_values(
+                rows.iter().map(|row| row.votes),
+            )),
+        ];
+        let batch = RecordBatch::try_new(schema.clone(), columns)?;
+
+        let mut buf = Vec::new();
+        let mut writer = ArrowWriter::try_new(&mut buf, schema, None)?;
+        writer.write(&batch)?;
+        writer.close()?;
+        Ok(buf)
+    }
+
+    /// Compares parties year over year for a position. Rows are grouped by
+    /// district when `by_district` is set; encrypted elections have no
+    /// districts and count as a group of their own. Only first rounds are
+    /// compared: a runoff is a second count of the same voters between two
+    /// of the first round's candidates, so adding it would skew both votes
+    /// and shares.
+    pub fn compare(
+        rows: &[ResultRow],
+        position: &str,
+        district: Option<&str>,
+        by_district: bool,
+    ) -> Vec<PartyComparison> {
+        let rows: Vec<&ResultRow> = rows
+            .iter()
+            .filter(|row| row.round == 1)
+            .filter(|row| row.position == position)
+            .filter(|row| district.is_none() || row.district.as_deref() == district)
+            .collect();
+        let group = |row: &ResultRow| match by_district {
+            true => row.district.clone(),
+            false => district.map(str::to_string),
+        };
+
+        let mut totals: BTreeMap<(Option<String>, i32), i64> = BTreeMap::new();
+        let mut votes: BTreeMap<(String, Option<String>), BTreeMap<i32, i64>> = BTreeMap::new();
+        for row in rows {
+            *totals.entry((group(row), row.year)).or_insert(0) += row.votes;
+            *votes
+                .entry((row.party_acronym.clone(), group(row)))
+                .or_default()
+                .entry(row.year)
+                .or_insert(0) += row.votes;
+        }
+        let years: Vec<i32> = totals.keys().map(|(_, year)| *year).collect();
+
+        votes
+            .into_iter()
+            .map(|((party_acronym, district), mut votes)| {
+                // A party missing from a year had no votes in it.
+                for year in years.iter() {
+                    votes.entry(*year).or_insert(0);
+                }
+                let share: BTreeMap<i32, f64> = votes
+                    .iter()
+                    .filter_map(|(year, votes)| {
+                        let total = *totals.get(&(district.clone(), *year))?;
+                        Some((*year, *votes as f64 / total as f64))
+                    })
+                    .collect();
+                let change = |values: Vec<f64>| match (values.first(), values.last()) {
+                    (Some(first), Some(last)) => last - first,
+                    _ => 0.0,
+                };
+                let votes_change = change(votes.values().map(|votes| *votes as f64).collect());
+                let share_change = change(share.values().copied().collect());
+                PartyComparison {
+                    party_acronym,
+                    district,
+                    votes,
+                    share,
+                    votes_change: votes_change as i64,
+                    share_change,
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(year: i32, round: i32, party_acronym: &str, votes: i64) -> ResultRow {
+        ResultRow {
+            election_id: format!("{}-{}", year, round),
+            election_name: format!("Eleições {}", year),
+            year,
+            round,
+            district: None,
+            position: "Presidente".to_string(),
+            candidature_code: "55".to_string(),
+            party_acronym: party_acronym.to_string(),
+            candidate_name: "Ana Souza".to_string(),
+            votes,
+        }
+    }
+
+    #[test]
+    fn compares_first_rounds_only() {
+        let rows = vec![
+            row(2022, 1, "PA", 40),
+            row(2022, 1, "PB", 60),
+            row(2022, 2, "PA", 45),
+            row(2022, 2, "PB", 55),
+            row(2026, 1, "PA", 75),
+            row(2026, 1, "PB", 25),
+        ];
+
+        let comparison = ResultRow::compare(&rows, "Presidente", None, false);
+        let pa = comparison
+            .iter()
+            .find(|comparison| comparison.party_acronym == "PA")
+            .unwrap();
+        assert_eq!(pa.votes, BTreeMap::from([(2022, 40), (2026, 75)]));
+        assert_eq!(pa.share, BTreeMap::from([(2022, 0.4), (2026, 0.75)]));
+        assert_eq!(pa.votes_change, 35);
+        assert!((pa.share_change - 0.35).abs() < 1e-9);
+    }
+
+    #[test]
+    fn reads_exports_without_a_round() {
+        let csv = "election_id,election_name,year,district,position,candidature_code,party_acronym,candidate_name,votes\n\
+                   e,Eleições 2022,2022,,Presidente,55,PA,Ana Souza,40\n";
+        let row: ResultRow = csv::Reader::from_reader(csv.as_bytes())
+            .deserialize()
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!(row.round, 1);
+    }
+}
@@ -0,0 +1,61 @@
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SearchResult {
+    /// `candidate`, `party` or `candidature`.
+    pub entity: String,
+    pub entity_id: String,
+    pub title: String,
+    pub body: String,
+}
+
+/// Builds an FTS5 query that matches every word of the input as a prefix.
+/// Words are quoted, so operators and punctuation typed by users are never
+/// interpreted.
+fn match_expression(input: &str) -> Option<String> {
+    let terms: Vec<String> = input
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|term| !term.is_empty())
+        .map(|term| format!("\"{}\"*", term))
+        .collect();
+    (!terms.is_empty()).then(|| terms.join(" "))
+}
+
+impl SearchResult {
+    /// Searches candidates, parties and candidature codes by partial,
+    /// accent-insensitive words, best matches first.
+    pub async fn search(
+        conn: &SqlitePool,
+        input: &str,
+        entity: Option<&str>,
+        limit: i64,
+    ) -> Result<Vec<SearchResult>, sqlx::Error> {
+        let Some(expression) = match_expression(input) else {
+            return Ok(Vec::new());
+        };
+        sqlx::query_as!(
+            SearchResult,
+            r#"
+            SELECT
+                entity AS "entity!: String",
+                entity_id AS "entity_id!: String",
+                title AS "title!: String",
+                body AS "body!: String"
+            FROM
+                search_index
+            WHERE
+                search_index MATCH ?1 AND
+                (?2 IS NULL OR entity = ?2)
+            ORDER BY
+                bm25(search_index, 0.0, 0.0, 10.0, 1.0)
+            LIMIT ?3
+            "#,
+            expression,
+            entity,
+            limit
+        )
+        .fetch_all(conn)
+        .await
+    }
+}
@@ -0,0 +1,302 @@
+use std::collections::BTreeMap;
+
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+
+use crate::{CandidaturePosition, Election, Federation, Party, TallyMode};
+
+/// A party, or the parties of a federation, competing for seats as one.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SeatList {
+    pub acronym: String,
+    pub federation_id: Option<String>,
+    pub party_acronyms: Vec<String>,
+    pub votes: i64,
+    /// Seats won outright: votes divided by the electoral quotient.
+    pub quotient_seats: i64,
+    /// Seats won in the distribution of the remainders.
+    pub remainder_seats: i64,
+    pub seats: i64,
+}
+
+/// Proportional seats of a position, as the electoral court computes them:
+/// the electoral quotient is the valid votes over the seats, each list wins
+/// one seat per quotient, and the seats left over go by highest average
+/// (D'Hondt) to the lists that reached 80% of the quotient.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SeatAllocation {
+    pub election_id: String,
+    pub position: CandidaturePosition,
+    pub district: Option<String>,
+    pub seats: i64,
+    pub valid_votes: i64,
+    pub electoral_quotient: i64,
+    pub lists: Vec<SeatList>,
+}
+
+/// Votes of the candidatures of one party.
+struct PartyVotes {
+    party_id: String,
+    votes: i64,
+}
+
+impl SeatAllocation {
+    pub async fn compute(
+        conn: &SqlitePool,
+        election: &Election,
+        position: CandidaturePosition,
+        district: Option<&str>,
+        seats: i64,
+    ) -> Result<SeatAllocation, anyhow::Error> {
+        if seats < 1 {
+            return Err(anyhow!("seats must be at least 1"));
+        }
+        let position_name = position.to_string();
+        let rows = match election.tally_mode {
+            TallyMode::Plain => {
+                sqlx::query_as!(
+                    PartyVotes,
+                    r#"
+                    SELECT
+                        ca.party_id AS "party_id!: String",
+                        COUNT(v.id) AS "votes!: i64"
+                    FROM
+                        votes v
+                    JOIN
+                        candidatures ca ON ca.id = v.candidature_id
+                    WHERE
+                        v.election_id = ?1 AND
+                        v.candidature_position = ?2 AND
+                        (?3 IS NULL OR v.district = ?3)
+                    GROUP BY
+                        ca.party_id
+                    "#,
+                    election.id,
+                    position_name,
+                    district
+                )
+                .fetch_all(conn)
+                .await?
+            }
+            TallyMode::Encrypted => {
+                if district.is_some() {
+                    return Err(anyhow!("encrypted elections are only counted as a whole"));
+                }
+                sqlx::query_as!(
+                    PartyVotes,
+                    r#"
+                    SELECT
+                        ca.party_id AS "party_id!: String",
+                        SUM(r.votes) AS "votes!: i64"
+                    FROM
+                        election_results r
+                    JOIN
+                        candidatures ca ON ca.id = r.candidature_id
+                    WHERE
+                        r.election_id = ? AND
+                        ca.position = ?
+                    GROUP BY
+                        ca.party_id
+                    "#,
+                    election.id,
+                    position_name
+                )
+                .fetch_all(conn)
+                .await?
+            }
+        };
+
+        // Pool the votes of federated parties into a single list. Coalitions
+        // only count in their own district.
+        let mut lists: BTreeMap<String, SeatList> = BTreeMap::new();
+        for PartyVotes { party_id, votes } in rows {
+            let party = Party::find(conn, &party_id)
+                .await?
+                .ok_or_else(|| anyhow!("party {} not found", party_id))?;
+            let federation = Federation::find_for_party(conn, &election.id, &party_id)
+                .await?
+                .filter(|federation| {
+                    federation.district.is_none() || federation.district.as_deref() == district
+                });
+            let (key, acronym, federation_id) = match federation {
+                Some(federation) => (
+                    federation.id.clone(),
+                    federation.acronym,
+                    Some(federation.id),
+                ),
+                None => (party.id, party.acronym.clone(), None),
+            };
+            let list = lists.entry(key).or_insert_with(|| SeatList {
+                acronym,
+                federation_id,
+                party_acronyms: Vec::new(),
+                votes: 0,
+                quotient_seats: 0,
+                remainder_seats: 0,
+                seats: 0,
+            });
+            list.party_acronyms.push(party.acronym);
+            list.votes += votes;
+        }
+        let mut lists: Vec<SeatList> = lists.into_values().collect();
+
+        let valid_votes: i64 = lists.iter().map(|list| list.votes).sum();
+        let electoral_quotient = electoral_quotient(valid_votes, seats);
+        if electoral_quotient > 0 {
+            allocate(&mut lists, electoral_quotient, seats);
+        }
+        lists.sort_by(|a, b| b.seats.cmp(&a.seats).then(b.votes.cmp(&a.votes)));
+
+        Ok(SeatAllocation {
+            election_id: election.id.clone(),
+            position,
+            district: district.map(str::to_string),
+            seats,
+            valid_votes,
+            electoral_quotient,
+            lists,
+        })
+    }
+}
+
+/// Valid votes over seats. Fractions up to one half are dropped, above it
+/// round up.
+fn electoral_quotient(valid_votes: i64, seats: i64) -> i64 {
+    (2 * valid_votes + seats - 1) / (2 * seats)
+}
+
+fn allocate(lists: &mut [SeatList], electoral_quotient: i64, seats: i64) {
+    for list in lists.iter_mut() {
+        list.quotient_seats = list.votes / electoral_quotient;
+    }
+    let mut left = seats - lists.iter().map(|list| list.quotient_seats).sum::<i64>();
+    // With very few votes the rounded-down quotient can hand out more seats
+    // than there are; then every seat goes by highest average.
+    if left < 0 {
+        for list in lists.iter_mut() {
+            list.quotient_seats = 0;
+        }
+        left = seats;
+    }
+
+    // When no list reaches the threshold every list competes for the rest.
+    let threshold = electoral_quotient * 8 / 10;
+    let any_eligible = lists.iter().any(|list| list.votes >= threshold);
+    while left > 0 {
+        let best = lists
+            .iter()
+            .enumerate()
+            .filter(|(_, list)| !any_eligible || list.votes >= threshold)
+            .max_by(|(_, a), (_, b)| {
+                let average =
+                    |list: &SeatList| (list.votes, list.quotient_seats + list.remainder_seats + 1);
+                let ((a_votes, a_divisor), (b_votes, b_divisor)) = (average(a), average(b));
+                (a_votes * b_divisor)
+                    .cmp(&(b_votes * a_divisor))
+                    .then(a.votes.cmp(&b.votes))
+                    .then(b.acronym.cmp(&a.acronym))
+            })
+            .map(|(index, _)| index);
+        let Some(best) = best else {
+            break;
+        };
+        lists[best].remainder_seats += 1;
+        left -= 1;
+    }
+
+    for list in lists.iter_mut() {
+        list.seats = list.quotient_seats + list.remainder_seats;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Allocates `seats` among lists with the given votes and returns each
+    /// list's (quotient seats, remainder seats), in the order given.
+    fn allocation(votes: &[(&str, i64)], seats: i64) -> Vec<(i64, i64)> {
+        let mut lists: Vec<SeatList> = votes
+            .iter()
+            .map(|(acronym, votes)| SeatList {
+                acronym: acronym.to_string(),
+                federation_id: None,
+                party_acronyms: vec![acronym.to_string()],
+                votes: *votes,
+                quotient_seats: 0,
+                remainder_seats: 0,
+                seats: 0,
+            })
+            .collect();
+        let valid_votes = votes.iter().map(|(_, votes)| votes).sum();
+        allocate(&mut lists, electoral_quotient(valid_votes, seats), seats);
+
+        lists
+            .iter()
+            .map(|list| {
+                assert_eq!(list.seats, list.quotient_seats + list.remainder_seats);
+                (list.quotient_seats, list.remainder_seats)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn rounds_the_electoral_quotient_at_one_half() {
+        assert_eq!(electoral_quotient(100_000, 10), 10_000);
+        assert_eq!(electoral_quotient(10, 4), 2);
+        assert_eq!(electoral_quotient(11, 4), 3);
+        assert_eq!(electoral_quotient(0, 4), 0);
+    }
+
+    #[test]
+    fn distributes_remainders_by_highest_average() {
+        // Quotient 10 000: 9 seats outright, and the last goes to B, whose
+        // average 25 000 / 3 beats A's and D's 7 500.
+        let seats = allocation(
+            &[
+                ("A", 30_000),
+                ("B", 25_000),
+                ("C", 20_000),
+                ("D", 15_000),
+                ("E", 10_000),
+            ],
+            10,
+        );
+        assert_eq!(seats, [(3, 0), (2, 1), (2, 0), (1, 0), (1, 0)]);
+    }
+
+    #[test]
+    fn remainders_need_80_percent_of_the_quotient() {
+        // Quotient 2 000, threshold 1 600: C's average of 1 500 is the
+        // highest after A's, but C does not compete for remainders.
+        let seats = allocation(&[("A", 6_000), ("B", 2_500), ("C", 1_500)], 5);
+        assert_eq!(seats, [(3, 1), (1, 0), (0, 0)]);
+    }
+
+    #[test]
+    fn every_list_competes_when_none_reaches_the_threshold() {
+        let seats = allocation(&[("A", 40), ("B", 30), ("C", 30)], 1);
+        assert_eq!(seats, [(0, 1), (0, 0), (0, 0)]);
+    }
+
+    #[test]
+    fn falls_back_to_highest_average_when_quotients_overshoot() {
+        // Quotient 1 would give each list a seat, one more than there are.
+        // Ties go to the list earlier in alphabetical order.
+        let seats = allocation(&[("C", 1), ("A", 1), ("B", 1)], 2);
+        assert_eq!(seats, [(0, 0), (0, 1), (0, 1)]);
+    }
+
+    #[test]
+    fn hands_out_every_seat() {
+        let votes = [("A", 523), ("B", 311), ("C", 97), ("D", 69)];
+        for seats in 1..=30 {
+            let total: i64 = allocation(&votes, seats)
+                .iter()
+                .map(|(quotient, remainder)| quotient + remainder)
+                .sum();
+            assert_eq!(total, seats);
+        }
+    }
+}
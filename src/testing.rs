@@ -0,0 +1,93 @@
+//! Fixtures shared by the database tests of the modules.
+
+use chrono::NaiveDate;
+use sqlx::SqlitePool;
+
+use crate::{
+    Candidate, Candidature, CandidaturePosition, DatabaseConfig, Election, Party, TallyMode, Voter,
+};
+
+pub(crate) const ACTOR: &str = "test";
+
+/// A migrated in-memory database. One connection, as every connection to
+/// `:memory:` is a new database.
+pub(crate) async fn test_pool() -> SqlitePool {
+    std::env::set_var(
+        "SECRET_KEY",
+        "5RMI18i7kVrig0n3sREaQZTGCIveilg3wXaijT1RH3zBWvt/9vw7lxVQ8IQpy5+afRD60iObOv8W1MFYSFf7Yg==",
+    );
+    std::env::set_var(
+        "BULLETIN_SIGNING_KEY",
+        "9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60",
+    );
+    let config = DatabaseConfig {
+        max_connections: 1,
+        ..DatabaseConfig::default()
+    };
+    let conn = config.connect("sqlite::memory:").await.unwrap();
+    crate::migrate(&conn).await.unwrap();
+    conn
+}
+
+pub(crate) async fn party(conn: &SqlitePool, number: i32) -> Party {
+    let mut party = Party::build(
+        format!("Partido {}", number),
+        format!("Partido {}", number),
+        format!("P{}", number),
+    );
+    party.number = Some(number);
+    party.create(conn, ACTOR).await.unwrap();
+    party
+}
+
+pub(crate) async fn candidate(
+    conn: &SqlitePool,
+    party: &Party,
+    birth_date: NaiveDate,
+) -> Candidate {
+    let mut candidate = Candidate::build("Ana".to_string(), "Souza".to_string());
+    candidate.birth_date = Some(birth_date);
+    candidate.party_id = Some(party.id.clone());
+    candidate.create(conn, ACTOR).await.unwrap();
+    candidate
+}
+
+/// An unsaved candidature of a candidate for their party.
+pub(crate) fn candidature(
+    candidate: &Candidate,
+    code: &str,
+    position: CandidaturePosition,
+    year: i32,
+) -> Candidature {
+    let mut candidature = Candidature::build(
+        candidate.party_id.clone().unwrap(),
+        candidate.id.clone(),
+        code.to_string(),
+        position,
+        String::new(),
+    );
+    candidature.year = year;
+    candidature
+}
+
+pub(crate) async fn voter(conn: &SqlitePool, district: Option<&str>) -> Voter {
+    let voter = Voter::build(
+        "Maria".to_string(),
+        "Silva".to_string(),
+        "Ana".to_string(),
+        "José".to_string(),
+        NaiveDate::from_ymd_opt(1990, 1, 1).unwrap(),
+        district.map(str::to_string),
+        district.map(|_| "0001".to_string()),
+    );
+    voter.create(conn, ACTOR).await.unwrap();
+    voter
+}
+
+/// A plain election of the year, already open.
+pub(crate) async fn open_election(conn: &SqlitePool, year: i32, round: i32) -> Election {
+    let mut election = Election::build(format!("Eleições {}", year), year, TallyMode::Plain);
+    election.round = round;
+    election.create(conn, ACTOR).await.unwrap();
+    election.open(conn, ACTOR).await.unwrap()
+}
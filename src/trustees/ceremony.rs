@@ -0,0 +1,271 @@
+//! Distributed key generation for election trustees (Pedersen's joint
+//! Feldman scheme). Every trustee deals a random polynomial of degree
+//! `threshold - 1`, publishes commitments to its coefficients and sends each
+//! peer an evaluation. A trustee's key share is the sum of the evaluations it
+//! receives; the election key is the sum of every dealer's constant term,
+//! which no single party ever learns.
+
+use anyhow::anyhow;
+use curve25519_dalek::{
+    constants::RISTRETTO_BASEPOINT_POINT, ristretto::RistrettoPoint, scalar::Scalar,
+    traits::Identity,
+};
+use sha2::{Digest, Sha512};
+
+use crate::elgamal;
+
+const SHARE_DOMAIN: &[u8] = b"bbox/ceremony/share/v1";
+
+pub struct Polynomial {
+    coefficients: Vec<Scalar>,
+}
+
+impl Polynomial {
+    pub fn random(threshold: usize) -> Polynomial {
+        let mut rng = rand::thread_rng();
+        Polynomial {
+            coefficients: (0..threshold).map(|_| Scalar::random(&mut rng)).collect(),
+        }
+    }
+
+    pub fn evaluate(&self, index: u64) -> Scalar {
+        let x = Scalar::from(index);
+        self.coefficients
+            .iter()
+            .rev()
+            .fold(Scalar::ZERO, |acc, coefficient| acc * x + coefficient)
+    }
+
+    pub fn commitments(&self) -> Vec<RistrettoPoint> {
+        self.coefficients
+            .iter()
+            .map(|coefficient| coefficient * RISTRETTO_BASEPOINT_POINT)
+            .collect()
+    }
+}
+
+/// Evaluates a dealer's committed polynomial "in the exponent" at `index`.
+pub fn committed_evaluation(commitments: &[RistrettoPoint], index: u64) -> RistrettoPoint {
+    let x = Scalar::from(index);
+    commitments
+        .iter()
+        .rev()
+        .fold(RistrettoPoint::identity(), |acc, commitment| {
+            acc * x + commitment
+        })
+}
+
+/// Feldman check that a received share lies on the dealer's polynomial.
+pub fn verify_share(commitments: &[RistrettoPoint], index: u64, share: &Scalar) -> bool {
+    share * RISTRETTO_BASEPOINT_POINT == committed_evaluation(commitments, index)
+}
+
+/// The public counterpart of trustee `index`'s key share, derivable by anyone
+/// from the published commitments.
+pub fn verification_key(dealers: &[Vec<RistrettoPoint>], index: u64) -> RistrettoPoint {
+    dealers
+        .iter()
+        .map(|commitments| committed_evaluation(commitments, index))
+        .sum()
+}
+
+pub fn election_public_key(dealers: &[Vec<RistrettoPoint>]) -> RistrettoPoint {
+    dealers.iter().map(|commitments| commitments[0]).sum()
+}
+
+/// Lagrange coefficient at zero for `index` within the set `indices`.
+pub fn lagrange_coefficient(index: u64, indices: &[u64]) -> Scalar {
+    let mut numerator = Scalar::ONE;
+    let mut denominator = Scalar::ONE;
+    for other in indices.iter().filter(|other| **other != index) {
+        numerator *= Scalar::from(*other);
+        denominator *= Scalar::from(*other) - Scalar::from(index);
+    }
+    numerator * denominator.invert()
+}
+
+fn share_pad(
+    ephemeral: &RistrettoPoint,
+    recipient: &RistrettoPoint,
+    shared: &RistrettoPoint,
+) -> [u8; 32] {
+    let mut hasher = Sha512::new();
+    hasher.update(SHARE_DOMAIN);
+    hasher.update(ephemeral.compress().as_bytes());
+    hasher.update(recipient.compress().as_bytes());
+    hasher.update(shared.compress().as_bytes());
+    let digest = hasher.finalize();
+
+    let mut pad = [0u8; 32];
+    pad.copy_from_slice(&digest[..32]);
+    pad
+}
+
+/// Encrypts a share to its recipient's public key so it can travel through
+/// the database. Returns the ephemeral key and the masked share, hex encoded.
+pub fn encrypt_share(recipient: &RistrettoPoint, share: &Scalar) -> (String, String) {
+    let ephemeral_secret = Scalar::random(&mut rand::thread_rng());
+    let ephemeral = ephemeral_secret * RISTRETTO_BASEPOINT_POINT;
+    let pad = share_pad(&ephemeral, recipient, &(ephemeral_secret * recipient));
+
+    let masked: Vec<u8> = share
+        .as_bytes()
+        .iter()
+        .zip(pad.iter())
+        .map(|(byte, mask)| byte ^ mask)
+        .collect();
+
+    (elgamal::encode_point(&ephemeral), hex::encode(masked))
+}
+
+pub fn decrypt_share(
+    secret: &Scalar,
+    ephemeral: &str,
+    ciphertext: &str,
+) -> Result<Scalar, anyhow::Error> {
+    let ephemeral = elgamal::decode_point(ephemeral)?;
+    let recipient = secret * RISTRETTO_BASEPOINT_POINT;
+    let pad = share_pad(&ephemeral, &recipient, &(secret * ephemeral));
+
+    let masked = hex::decode(ciphertext)?;
+    if masked.len() != 32 {
+        return Err(anyhow!("invalid share length"));
+    }
+    let mut bytes = [0u8; 32];
+    for (i, byte) in masked.iter().enumerate() {
+        bytes[i] = byte ^ pad[i];
+    }
+
+    Option::from(Scalar::from_canonical_bytes(bytes)).ok_or_else(|| anyhow!("invalid share"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::elgamal::{Ciphertext, DecryptionShare};
+
+    /// Runs the ceremony for trustees `1..=trustee_count` and returns the
+    /// dealers' commitments and each trustee's key share.
+    fn run_ceremony(
+        threshold: usize,
+        trustee_count: u64,
+    ) -> (Vec<Vec<RistrettoPoint>>, Vec<Scalar>) {
+        let polynomials: Vec<Polynomial> = (0..trustee_count)
+            .map(|_| Polynomial::random(threshold))
+            .collect();
+        let dealers: Vec<Vec<RistrettoPoint>> =
+            polynomials.iter().map(Polynomial::commitments).collect();
+
+        let key_shares = (1..=trustee_count)
+            .map(|index| {
+                polynomials
+                    .iter()
+                    .zip(dealers.iter())
+                    .map(|(polynomial, commitments)| {
+                        let share = polynomial.evaluate(index);
+                        assert!(verify_share(commitments, index, &share));
+                        share
+                    })
+                    .sum()
+            })
+            .collect();
+
+        (dealers, key_shares)
+    }
+
+    #[test]
+    fn evaluates_polynomials_at_known_points() {
+        let polynomial = Polynomial {
+            coefficients: vec![Scalar::from(3u64), Scalar::from(2u64), Scalar::from(1u64)],
+        };
+        // 3 + 2x + x²
+        assert_eq!(polynomial.evaluate(0), Scalar::from(3u64));
+        assert_eq!(polynomial.evaluate(1), Scalar::from(6u64));
+        assert_eq!(polynomial.evaluate(4), Scalar::from(27u64));
+        assert_eq!(
+            committed_evaluation(&polynomial.commitments(), 4),
+            Scalar::from(27u64) * RISTRETTO_BASEPOINT_POINT
+        );
+    }
+
+    #[test]
+    fn lagrange_coefficients_match_known_values() {
+        assert_eq!(lagrange_coefficient(1, &[1, 2]), Scalar::from(2u64));
+        assert_eq!(lagrange_coefficient(2, &[1, 2]), -Scalar::ONE);
+        // 3·2 / ((3-1)·(2-1)) = 3 for index 1 of {1, 2, 3}.
+        assert_eq!(lagrange_coefficient(1, &[1, 2, 3]), Scalar::from(3u64));
+        assert_eq!(lagrange_coefficient(4, &[4]), Scalar::ONE);
+    }
+
+    #[test]
+    fn rejects_shares_off_the_polynomial() {
+        let polynomial = Polynomial::random(2);
+        let commitments = polynomial.commitments();
+        let share = polynomial.evaluate(1);
+        assert!(verify_share(&commitments, 1, &share));
+        assert!(!verify_share(&commitments, 2, &share));
+        assert!(!verify_share(&commitments, 1, &(share + Scalar::ONE)));
+    }
+
+    #[test]
+    fn any_threshold_of_trustees_recovers_the_election_key() {
+        let (dealers, key_shares) = run_ceremony(2, 3);
+        let public_key = election_public_key(&dealers);
+
+        for (index, key_share) in (1..).zip(key_shares.iter()) {
+            assert_eq!(
+                key_share * RISTRETTO_BASEPOINT_POINT,
+                verification_key(&dealers, index)
+            );
+        }
+
+        for indices in [[1, 2], [1, 3], [2, 3]] {
+            let secret: Scalar = indices
+                .iter()
+                .map(|index| {
+                    lagrange_coefficient(*index, &indices) * key_shares[*index as usize - 1]
+                })
+                .sum();
+            assert_eq!(secret * RISTRETTO_BASEPOINT_POINT, public_key);
+        }
+
+        let alone = lagrange_coefficient(1, &[1]) * key_shares[0];
+        assert_ne!(alone * RISTRETTO_BASEPOINT_POINT, public_key);
+    }
+
+    #[test]
+    fn threshold_decryption_shares_combine_to_the_plaintext() {
+        let (dealers, key_shares) = run_ceremony(3, 5);
+        let tally = [1, 1, 0, 1]
+            .into_iter()
+            .map(|vote| Ciphertext::encrypt(&election_public_key(&dealers), vote))
+            .fold(Ciphertext::zero(), |tally, vote| tally + vote);
+
+        let indices = [2, 4, 5];
+        let combined: RistrettoPoint = indices
+            .iter()
+            .map(|index| {
+                let share = DecryptionShare::compute(&key_shares[*index as usize - 1], &tally);
+                assert!(share.verify(&verification_key(&dealers, *index), &tally));
+                lagrange_coefficient(*index, &indices) * share.share
+            })
+            .sum();
+        assert_eq!(tally.decrypt_with(&combined, 4), Some(3));
+    }
+
+    #[test]
+    fn shares_travel_encrypted_to_their_recipient() {
+        let (secret, recipient) = elgamal::generate_keypair();
+        let share = Scalar::random(&mut rand::thread_rng());
+        let (ephemeral, ciphertext) = encrypt_share(&recipient, &share);
+        assert_eq!(
+            decrypt_share(&secret, &ephemeral, &ciphertext).unwrap(),
+            share
+        );
+
+        let (other_secret, _) = elgamal::generate_keypair();
+        let opened = decrypt_share(&other_secret, &ephemeral, &ciphertext);
+        assert!(opened.map_or(true, |opened| opened != share));
+        assert!(decrypt_share(&secret, &ephemeral, &ciphertext[2..]).is_err());
+    }
+}
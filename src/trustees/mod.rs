@@ -0,0 +1,526 @@
+use anyhow::anyhow;
+use curve25519_dalek::{
+    constants::RISTRETTO_BASEPOINT_POINT, ristretto::RistrettoPoint, scalar::Scalar,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use uuid::Uuid;
+
+use crate::{
+    elgamal::{self, DecryptionShare, KeyProof},
+    AuditEvent, Election, ElectionStatus,
+};
+
+pub mod ceremony;
+
+/// A holder of part of an encrypted election's decryption key. Only the
+/// public half is stored; the secret stays in the trustee's key file.
+///
+/// In threshold elections `public_key` only protects the ceremony shares
+/// sent to the trustee, and decryptions are checked against
+/// `verification_key`, the public half of the key share it combined.
+///
+/// `proof_challenge` and `proof_response` prove the trustee knows the secret
+/// of `public_key`. Without them the last trustee of an n-of-n election could
+/// register the election key minus the others' keys and decrypt alone.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Trustee {
+    pub id: String,
+    pub election_id: String,
+    pub name: String,
+    pub public_key: String,
+    pub share_index: Option<i32>,
+    pub verification_key: Option<String>,
+    pub proof_challenge: Option<String>,
+    pub proof_response: Option<String>,
+}
+
+impl Trustee {
+    /// Registers the public half of `secret`, with a proof of possession
+    /// bound to the election.
+    pub fn build(election_id: String, name: String, secret: &Scalar) -> Trustee {
+        let proof = KeyProof::prove(secret, election_id.as_bytes());
+        Trustee {
+            id: Uuid::now_v7().to_string(),
+            election_id,
+            name,
+            public_key: elgamal::encode_point(&(secret * RISTRETTO_BASEPOINT_POINT)),
+            share_index: None,
+            verification_key: None,
+            proof_challenge: Some(elgamal::encode_scalar(&proof.challenge)),
+            proof_response: Some(elgamal::encode_scalar(&proof.response)),
+        }
+    }
+
+    /// Checks the proof that the trustee knows the secret of `public_key`.
+    pub fn verify_key_proof(&self) -> Result<(), anyhow::Error> {
+        let (Some(challenge), Some(response)) = (&self.proof_challenge, &self.proof_response)
+        else {
+            return Err(anyhow!(
+                "trustee {} has no proof of possession of its key",
+                self.name
+            ));
+        };
+        let proof = KeyProof {
+            challenge: elgamal::decode_scalar(challenge)?,
+            response: elgamal::decode_scalar(response)?,
+        };
+        if !proof.verify(
+            &elgamal::decode_point(&self.public_key)?,
+            self.election_id.as_bytes(),
+        ) {
+            return Err(anyhow!(
+                "invalid proof of possession for the key of trustee {}",
+                self.name
+            ));
+        }
+        Ok(())
+    }
+
+    pub async fn create(&self, conn: &SqlitePool, actor: &str) -> Result<(), anyhow::Error> {
+        let election = Election::find(conn, &self.election_id)
+            .await?
+            .ok_or_else(|| anyhow!("election not found"))?;
+        if election.status != ElectionStatus::Setup {
+            return Err(anyhow!("trustees can only be added during setup"));
+        }
+        self.verify_key_proof()?;
+
+        let mut tx = conn.begin().await?;
+        let mut trustee = self.clone();
+        if let Some(trustee_count) = election.trustee_count {
+            let registered = sqlx::query_scalar!(
+                r#"SELECT COUNT(id) AS "count!: i32" FROM trustees WHERE election_id = ?"#,
+                self.election_id
+            )
+            .fetch_one(&mut *tx)
+            .await?;
+            if registered >= trustee_count {
+                return Err(anyhow!(
+                    "all {} trustees are already registered",
+                    trustee_count
+                ));
+            }
+            trustee.share_index = Some(registered + 1);
+        }
+
+        sqlx::query!(
+            r#"
+            INSERT INTO trustees (id, election_id, name, public_key, share_index, proof_challenge, proof_response)
+            VALUES (?, ?, ?, ?, ?, ?, ?)
+            "#,
+            trustee.id,
+            trustee.election_id,
+            trustee.name,
+            trustee.public_key,
+            trustee.share_index,
+            trustee.proof_challenge,
+            trustee.proof_response,
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        AuditEvent::record(
+            &mut tx,
+            "trustee",
+            &trustee.id,
+            "create",
+            actor,
+            None,
+            serde_json::to_value(&trustee).ok(),
+        )
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    pub async fn find(conn: &SqlitePool, id: &str) -> Result<Option<Trustee>, sqlx::Error> {
+        sqlx::query_as!(
+            Trustee,
+            r#"
+            SELECT
+                id AS "id!: String",
+                election_id AS "election_id!: String",
+                name,
+                public_key,
+                share_index AS "share_index: i32",
+                verification_key,
+                proof_challenge,
+                proof_response
+            FROM
+                trustees
+            WHERE
+                id = ?
+            "#,
+            id
+        )
+        .fetch_optional(conn)
+        .await
+    }
+
+    pub async fn list(conn: &SqlitePool, election_id: &str) -> Result<Vec<Trustee>, sqlx::Error> {
+        sqlx::query_as!(
+            Trustee,
+            r#"
+            SELECT
+                id AS "id!: String",
+                election_id AS "election_id!: String",
+                name,
+                public_key,
+                share_index AS "share_index: i32",
+                verification_key,
+                proof_challenge,
+                proof_response
+            FROM
+                trustees
+            WHERE
+                election_id = ?
+            ORDER BY
+                share_index ASC,
+                created_at ASC
+            "#,
+            election_id
+        )
+        .fetch_all(conn)
+        .await
+    }
+
+    /// The key this trustee's decryption shares are proven against.
+    pub fn decryption_key(&self) -> Result<RistrettoPoint, anyhow::Error> {
+        elgamal::decode_point(self.verification_key.as_deref().unwrap_or(&self.public_key))
+    }
+
+    fn ensure_secret(&self, secret: &Scalar) -> Result<(), anyhow::Error> {
+        if elgamal::encode_point(&(secret * RISTRETTO_BASEPOINT_POINT)) != self.public_key {
+            return Err(anyhow!("secret key does not match trustee {}", self.name));
+        }
+        Ok(())
+    }
+
+    /// Loads the key ceremony for a threshold election this trustee can take
+    /// part in: still in setup, with every trustee registered.
+    async fn ceremony(&self, conn: &SqlitePool) -> Result<(Election, Vec<Trustee>), anyhow::Error> {
+        let election = Election::find(conn, &self.election_id)
+            .await?
+            .ok_or_else(|| anyhow!("election not found"))?;
+        let (Some(_), Some(trustee_count)) = (election.threshold, election.trustee_count) else {
+            return Err(anyhow!("election has no key ceremony"));
+        };
+        if election.status != ElectionStatus::Setup {
+            return Err(anyhow!("the key ceremony is over"));
+        }
+
+        let trustees = Trustee::list(conn, &election.id).await?;
+        if trustees.len() != trustee_count as usize {
+            return Err(anyhow!(
+                "{} of {} trustees registered",
+                trustees.len(),
+                trustee_count
+            ));
+        }
+
+        Ok((election, trustees))
+    }
+
+    /// First round of the key ceremony: commits to a random polynomial and
+    /// sends every trustee, this one included, its evaluation at their share
+    /// index. Returns the number of shares dealt.
+    pub async fn deal(&self, conn: &SqlitePool, secret: &Scalar) -> Result<usize, anyhow::Error> {
+        self.ensure_secret(secret)?;
+        let (election, trustees) = self.ceremony(conn).await?;
+
+        let dealt = sqlx::query!(
+            r#"SELECT dealer_id AS "dealer_id!: String" FROM ceremony_commitments WHERE dealer_id = ?"#,
+            self.id
+        )
+        .fetch_optional(conn)
+        .await?;
+        if dealt.is_some() {
+            return Err(anyhow!("trustee {} already dealt", self.name));
+        }
+
+        let threshold = election.threshold.unwrap_or_default() as usize;
+        let polynomial = ceremony::Polynomial::random(threshold);
+
+        let mut tx = conn.begin().await?;
+        for (degree, commitment) in polynomial.commitments().iter().enumerate() {
+            let degree = degree as i32;
+            let commitment = elgamal::encode_point(commitment);
+            sqlx::query!(
+                r#"
+                INSERT INTO ceremony_commitments (dealer_id, election_id, degree, commitment)
+                VALUES (?, ?, ?, ?)
+                "#,
+                self.id,
+                election.id,
+                degree,
+                commitment,
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        for recipient in trustees.iter() {
+            let index = recipient
+                .share_index
+                .ok_or_else(|| anyhow!("trustee {} has no share index", recipient.name))?;
+            let (ephemeral, ciphertext) = ceremony::encrypt_share(
+                &elgamal::decode_point(&recipient.public_key)?,
+                &polynomial.evaluate(index as u64),
+            );
+            sqlx::query!(
+                r#"
+                INSERT INTO ceremony_shares (dealer_id, recipient_id, election_id, ephemeral, ciphertext)
+                VALUES (?, ?, ?, ?, ?)
+                "#,
+                self.id,
+                recipient.id,
+                election.id,
+                ephemeral,
+                ciphertext,
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        AuditEvent::record(
+            &mut tx,
+            "trustee",
+            &self.id,
+            "deal",
+            &format!("trustee:{}", self.name),
+            None,
+            None,
+        )
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(trustees.len())
+    }
+
+    /// Second round of the key ceremony: checks every share received against
+    /// its dealer's commitments and combines them into this trustee's key
+    /// share, publishing its verification key. Dealers whose shares fail the
+    /// check are named in the error.
+    pub async fn finalize(
+        &self,
+        conn: &SqlitePool,
+        secret: &Scalar,
+    ) -> Result<Scalar, anyhow::Error> {
+        self.ensure_secret(secret)?;
+        let (_, trustees) = self.ceremony(conn).await?;
+        let index = self
+            .share_index
+            .ok_or_else(|| anyhow!("trustee {} has no share index", self.name))?
+            as u64;
+
+        let mut key_share = Scalar::ZERO;
+        let mut invalid = Vec::new();
+        for dealer in trustees.iter() {
+            let commitments = dealer.dealt_commitments(conn).await?;
+            if commitments.is_empty() {
+                return Err(anyhow!("waiting for trustee {} to deal", dealer.name));
+            }
+
+            let row = sqlx::query!(
+                r#"
+                SELECT
+                    ephemeral,
+                    ciphertext
+                FROM
+                    ceremony_shares
+                WHERE
+                    dealer_id = ? AND
+                    recipient_id = ?
+                "#,
+                dealer.id,
+                self.id
+            )
+            .fetch_optional(conn)
+            .await?
+            .ok_or_else(|| anyhow!("no share from trustee {}", dealer.name))?;
+
+            match ceremony::decrypt_share(secret, &row.ephemeral, &row.ciphertext) {
+                Ok(share) if ceremony::verify_share(&commitments, index, &share) => {
+                    key_share += share;
+                }
+                _ => invalid.push(dealer.name.clone()),
+            }
+        }
+        if !invalid.is_empty() {
+            return Err(anyhow!(
+                "invalid shares from trustees: {}",
+                invalid.join(", ")
+            ));
+        }
+
+        let verification_key = elgamal::encode_point(&(key_share * RISTRETTO_BASEPOINT_POINT));
+        let mut tx = conn.begin().await?;
+        sqlx::query!(
+            r#"
+            UPDATE trustees
+            SET verification_key = ?, updated_at = CURRENT_TIMESTAMP
+            WHERE id = ?
+            "#,
+            verification_key,
+            self.id,
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        let mut after = self.clone();
+        after.verification_key = Some(verification_key);
+        AuditEvent::record(
+            &mut tx,
+            "trustee",
+            &self.id,
+            "finalize",
+            &format!("trustee:{}", self.name),
+            serde_json::to_value(self).ok(),
+            serde_json::to_value(&after).ok(),
+        )
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(key_share)
+    }
+
+    async fn dealt_commitments(
+        &self,
+        conn: &SqlitePool,
+    ) -> Result<Vec<RistrettoPoint>, anyhow::Error> {
+        let commitments = sqlx::query_scalar!(
+            r#"
+            SELECT
+                commitment
+            FROM
+                ceremony_commitments
+            WHERE
+                dealer_id = ?
+            ORDER BY
+                degree ASC
+            "#,
+            self.id
+        )
+        .fetch_all(conn)
+        .await?;
+
+        commitments
+            .iter()
+            .map(|commitment| elgamal::decode_point(commitment))
+            .collect()
+    }
+
+    /// Commitments of every trustee of an election that has dealt, in share
+    /// index order.
+    pub async fn commitments(
+        conn: &SqlitePool,
+        election_id: &str,
+    ) -> Result<Vec<Vec<RistrettoPoint>>, anyhow::Error> {
+        let mut dealers = Vec::new();
+        for trustee in Trustee::list(conn, election_id).await? {
+            let commitments = trustee.dealt_commitments(conn).await?;
+            if !commitments.is_empty() {
+                dealers.push(commitments);
+            }
+        }
+        Ok(dealers)
+    }
+
+    /// Computes and stores this trustee's share of every tally of a closed
+    /// election. `secret` is the key share from the ceremony in threshold
+    /// elections. Returns the number of tallies processed.
+    pub async fn submit_decryptions(
+        &self,
+        conn: &SqlitePool,
+        secret: &Scalar,
+    ) -> Result<usize, anyhow::Error> {
+        if secret * RISTRETTO_BASEPOINT_POINT != self.decryption_key()? {
+            return Err(anyhow!("secret key does not match trustee {}", self.name));
+        }
+
+        let election = Election::find(conn, &self.election_id)
+            .await?
+            .ok_or_else(|| anyhow!("election not found"))?;
+        if election.status != ElectionStatus::Closed {
+            return Err(anyhow!("election is not closed"));
+        }
+
+        let tallies = Election::encrypted_tallies(conn, &election.id).await?;
+        let mut tx = conn.begin().await?;
+        for (candidature_id, ciphertext) in tallies.iter() {
+            let share = DecryptionShare::compute(secret, ciphertext);
+            let encoded_share = elgamal::encode_point(&share.share);
+            let challenge = elgamal::encode_scalar(&share.challenge);
+            let response = elgamal::encode_scalar(&share.response);
+            sqlx::query!(
+                r#"
+                INSERT INTO partial_decryptions (trustee_id, election_id, candidature_id, share, challenge, response)
+                VALUES (?, ?, ?, ?, ?, ?)
+                ON CONFLICT (trustee_id, candidature_id)
+                DO UPDATE SET share = excluded.share, challenge = excluded.challenge, response = excluded.response
+                "#,
+                self.id,
+                election.id,
+                candidature_id,
+                encoded_share,
+                challenge,
+                response,
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        AuditEvent::record(
+            &mut tx,
+            "trustee",
+            &self.id,
+            "decrypt",
+            &format!("trustee:{}", self.name),
+            None,
+            None,
+        )
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(tallies.len())
+    }
+
+    pub(crate) async fn decryption_share(
+        &self,
+        conn: &SqlitePool,
+        candidature_id: &str,
+    ) -> Result<Option<DecryptionShare>, anyhow::Error> {
+        let row = sqlx::query!(
+            r#"
+            SELECT
+                share,
+                challenge,
+                response
+            FROM
+                partial_decryptions
+            WHERE
+                trustee_id = ? AND
+                candidature_id = ?
+            "#,
+            self.id,
+            candidature_id
+        )
+        .fetch_optional(conn)
+        .await?;
+
+        row.map(|row| {
+            Ok(DecryptionShare {
+                share: elgamal::decode_point(&row.share)?,
+                challenge: elgamal::decode_scalar(&row.challenge)?,
+                response: elgamal::decode_scalar(&row.response)?,
+            })
+        })
+        .transpose()
+    }
+}
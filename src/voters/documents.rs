@@ -0,0 +1,136 @@
+use validator::ValidationError;
+
+/// Removes the punctuation documents are usually written with
+/// (`123.456.789-09`), leaving what gets stored and looked up.
+pub fn normalize_document(value: &str) -> String {
+    value
+        .chars()
+        .filter(|c| !matches!(c, '.' | '-' | '/' | ' '))
+        .collect()
+}
+
+fn digits(value: &str, length: usize) -> Option<Vec<u32>> {
+    let digits: Vec<u32> = value
+        .chars()
+        .map(|c| c.to_digit(10))
+        .collect::<Option<_>>()?;
+    (digits.len() == length).then_some(digits)
+}
+
+/// CPF: nine digits followed by two mod-11 check digits. Sequences of a
+/// single repeated digit pass the checksum but are never issued.
+pub fn validate_cpf(value: &str) -> Result<(), ValidationError> {
+    let Some(digits) = digits(value, 11) else {
+        return Err(ValidationError::new("cpf must have 11 digits"));
+    };
+    if digits.iter().all(|digit| *digit == digits[0]) {
+        return Err(ValidationError::new("invalid cpf"));
+    }
+
+    let check = |length: usize| {
+        let sum: u32 = digits[..length]
+            .iter()
+            .enumerate()
+            .map(|(i, digit)| digit * (length as u32 + 1 - i as u32))
+            .sum();
+        (sum * 10) % 11 % 10
+    };
+    if check(9) != digits[9] || check(10) != digits[10] {
+        return Err(ValidationError::new("invalid cpf"));
+    }
+
+    Ok(())
+}
+
+/// Título de eleitor: an eight-digit sequence, the two-digit code of the
+/// state it was issued in (01-28) and two mod-11 check digits. São Paulo
+/// (01) and Minas Gerais (02) use 1 where the remainder is 0.
+pub fn validate_voter_card(value: &str) -> Result<(), ValidationError> {
+    let Some(digits) = digits(value, 12) else {
+        return Err(ValidationError::new("voter card must have 12 digits"));
+    };
+    let state = digits[8] * 10 + digits[9];
+    if !(1..=28).contains(&state) {
+        return Err(ValidationError::new("invalid voter card state"));
+    }
+
+    let check = |sum: u32| match sum % 11 {
+        10 => 0,
+        0 if state <= 2 => 1,
+        remainder => remainder,
+    };
+    let first = check(
+        digits[..8]
+            .iter()
+            .enumerate()
+            .map(|(i, digit)| digit * (i as u32 + 2))
+            .sum(),
+    );
+    let second = check(digits[8] * 7 + digits[9] * 8 + first * 9);
+    if first != digits[10] || second != digits[11] {
+        return Err(ValidationError::new("invalid voter card"));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizes_punctuated_documents() {
+        assert_eq!(normalize_document("529.982.247-25"), "52998224725");
+        assert_eq!(normalize_document("0043 5687 0906"), "004356870906");
+        assert_eq!(normalize_document("12.345.678/0001-95"), "12345678000195");
+    }
+
+    #[test]
+    fn accepts_valid_cpfs() {
+        for cpf in ["52998224725", "11144477735", "39053344705"] {
+            assert!(validate_cpf(cpf).is_ok(), "{}", cpf);
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_cpfs() {
+        for cpf in [
+            "52998224724",
+            "52998224715",
+            "11111111111",
+            "00000000000",
+            "5299822472",
+            "529982247250",
+            "529.982.247-25",
+            "5299822472a",
+        ] {
+            assert!(validate_cpf(cpf).is_err(), "{}", cpf);
+        }
+    }
+
+    #[test]
+    fn accepts_valid_voter_cards() {
+        // Goiás (09), then a São Paulo (01) card whose first remainder is 0,
+        // which São Paulo writes as 1, and the same sequence from Bahia (03).
+        for voter_card in ["004356870906", "100000010116", "100000010302"] {
+            assert!(validate_voter_card(voter_card).is_ok(), "{}", voter_card);
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_voter_cards() {
+        for voter_card in [
+            "004356870907",
+            "004356870916",
+            // A remainder of 0 is 1 in São Paulo, not 0.
+            "100000010108",
+            // States run from 01 to 28.
+            "004356870006",
+            "004356872906",
+            "00435687090",
+            "0043568709066",
+        ] {
+            assert!(validate_voter_card(voter_card).is_err(), "{}", voter_card);
+        }
+    }
+}
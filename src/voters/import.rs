@@ -0,0 +1,430 @@
+use std::collections::{HashMap, HashSet};
+
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use validator::{Validate, ValidationError, ValidationErrors};
+
+use super::{normalize_document, validate_cpf, validate_voter_card, Voter, BIRTH_DATE_FORMAT};
+
+/// One line of a voter roll. Columns are matched by header name.
+#[derive(Debug, Deserialize, Validate)]
+struct VoterRecord {
+    #[validate(custom(function = "validate_cpf"))]
+    national_id: String,
+    #[serde(default)]
+    #[validate(custom(function = "validate_voter_card"))]
+    voter_card: Option<String>,
+    #[validate(length(min = 1, max = 100), custom(function = "validate_name"))]
+    first_name: String,
+    #[validate(length(min = 1, max = 100), custom(function = "validate_name"))]
+    last_name: String,
+    #[validate(length(min = 1, max = 200), custom(function = "validate_name"))]
+    mother_name: String,
+    #[validate(length(min = 1, max = 200), custom(function = "validate_name"))]
+    father_name: String,
+    #[validate(custom(function = "validate_birth_date"))]
+    birth_date: String,
+    #[serde(default)]
+    district: Option<String>,
+    #[serde(default)]
+    section: Option<String>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct ImportError {
+    /// Line in the file, counting the header as line 1.
+    pub line: usize,
+    pub field: Option<String>,
+    pub message: String,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct ImportReport {
+    pub rows: usize,
+    pub imported: usize,
+    pub dry_run: bool,
+    pub errors: Vec<ImportError>,
+}
+
+fn validate_name(name: &str) -> Result<(), ValidationError> {
+    let valid = name
+        .chars()
+        .all(|c| c.is_alphabetic() || " '-.".contains(c))
+        && name.chars().any(char::is_alphabetic);
+    if valid {
+        Ok(())
+    } else {
+        Err(ValidationError::new("invalid name"))
+    }
+}
+
+fn validate_birth_date(birth_date: &str) -> Result<(), ValidationError> {
+    let Ok(date) = NaiveDate::parse_from_str(birth_date, BIRTH_DATE_FORMAT) else {
+        return Err(ValidationError::new("birth date must be DD/MM/YYYY"));
+    };
+    let today = chrono::Utc::now().date_naive();
+    if date > today || date < NaiveDate::from_ymd_opt(1900, 1, 1).unwrap() {
+        return Err(ValidationError::new("birth date is out of range"));
+    }
+    Ok(())
+}
+
+fn field_errors(line: usize, errors: &ValidationErrors) -> Vec<ImportError> {
+    let mut fields: Vec<_> = errors.field_errors().into_iter().collect();
+    fields.sort_by_key(|(field, _)| field.to_string());
+    fields
+        .into_iter()
+        .flat_map(|(field, errors)| {
+            errors.iter().map(move |error| ImportError {
+                line,
+                field: Some(field.to_string()),
+                message: error.code.to_string(),
+            })
+        })
+        .collect()
+}
+
+impl VoterRecord {
+    fn trim(self) -> VoterRecord {
+        let optional = |value: Option<String>| {
+            value
+                .map(|value| value.trim().to_string())
+                .filter(|value| !value.is_empty())
+        };
+        VoterRecord {
+            national_id: normalize_document(self.national_id.trim()),
+            voter_card: optional(self.voter_card).map(|value| normalize_document(&value)),
+            first_name: self.first_name.trim().to_string(),
+            last_name: self.last_name.trim().to_string(),
+            mother_name: self.mother_name.trim().to_string(),
+            father_name: self.father_name.trim().to_string(),
+            birth_date: self.birth_date.trim().to_string(),
+            district: optional(self.district),
+            section: optional(self.section),
+        }
+    }
+
+    fn into_voter(self) -> Voter {
+        let mut voter = Voter::build(
+            self.first_name,
+            self.last_name,
+            self.mother_name,
+            self.father_name,
+            NaiveDate::parse_from_str(&self.birth_date, BIRTH_DATE_FORMAT)
+                .expect("birth date is validated"),
+            self.district,
+            self.section,
+        );
+        voter.national_id = Some(self.national_id);
+        voter.voter_card = self.voter_card;
+        voter
+    }
+}
+
+impl Voter {
+    /// Imports a CSV voter roll. Every row is validated and checked for
+    /// duplicate national ids and voter cards, both within the file and
+    /// against existing voters; if any row fails nothing is written and the report lists every
+    /// error. `dry_run` stops after validation.
+    pub async fn import_csv(
+        conn: &SqlitePool,
+        input: &[u8],
+        actor: &str,
+        dry_run: bool,
+    ) -> Result<ImportReport, anyhow::Error> {
+        // Rolls exported from spreadsheets in pt-BR locales use semicolons.
+        let header = input
+            .split(|byte| *byte == b'\n')
+            .next()
+            .unwrap_or_default();
+        let delimiter = if header.contains(&b';') && !header.contains(&b',') {
+            b';'
+        } else {
+            b','
+        };
+        let mut reader = csv::ReaderBuilder::new()
+            .delimiter(delimiter)
+            .trim(csv::Trim::Headers)
+            .from_reader(input);
+
+        let mut voters = Vec::new();
+        let mut errors = Vec::new();
+        let mut seen: HashMap<String, usize> = HashMap::new();
+        let mut seen_cards: HashMap<String, usize> = HashMap::new();
+        let mut rows = 0;
+
+        // Documents already registered, loaded once rather than per row.
+        let mut registered: HashSet<String> = HashSet::new();
+        let mut registered_cards: HashSet<String> = HashSet::new();
+        for row in sqlx::query!("SELECT national_id, voter_card FROM voters")
+            .fetch_all(conn)
+            .await?
+        {
+            registered.extend(row.national_id);
+            registered_cards.extend(row.voter_card);
+        }
+
+        for (index, record) in reader.deserialize::<VoterRecord>().enumerate() {
+            let line = index + 2;
+            rows += 1;
+            let record = match record {
+                Ok(record) => record.trim(),
+                Err(reason) => {
+                    errors.push(ImportError {
+                        line,
+                        field: None,
+                        message: reason.to_string(),
+                    });
+                    continue;
+                }
+            };
+            if let Err(reason) = record.validate() {
+                errors.extend(field_errors(line, &reason));
+                continue;
+            }
+
+            if let Some(first) = seen.get(&record.national_id) {
+                errors.push(ImportError {
+                    line,
+                    field: Some("national_id".to_string()),
+                    message: format!("duplicate of line {}", first),
+                });
+                continue;
+            }
+            seen.insert(record.national_id.clone(), line);
+
+            if let Some(voter_card) = &record.voter_card {
+                if let Some(first) = seen_cards.get(voter_card) {
+                    errors.push(ImportError {
+                        line,
+                        field: Some("voter_card".to_string()),
+                        message: format!("duplicate of line {}", first),
+                    });
+                    continue;
+                }
+                seen_cards.insert(voter_card.clone(), line);
+            }
+
+            let collision = if registered.contains(&record.national_id) {
+                Some("national_id")
+            } else if record
+                .voter_card
+                .as_ref()
+                .is_some_and(|voter_card| registered_cards.contains(voter_card))
+            {
+                Some("voter_card")
+            } else {
+                None
+            };
+            if let Some(field) = collision {
+                errors.push(ImportError {
+                    line,
+                    field: Some(field.to_string()),
+                    message: "voter already registered".to_string(),
+                });
+                continue;
+            }
+
+            voters.push(record.into_voter());
+        }
+
+        if !errors.is_empty() || dry_run {
+            return Ok(ImportReport {
+                rows,
+                imported: 0,
+                dry_run,
+                errors,
+            });
+        }
+
+        let mut tx = conn.begin().await?;
+        for voter in voters.iter() {
+            voter.insert(&mut tx, actor).await?;
+        }
+        tx.commit().await?;
+
+        Ok(ImportReport {
+            rows,
+            imported: voters.len(),
+            dry_run,
+            errors,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DatabaseConfig;
+
+    const HEADER: &str =
+        "national_id,voter_card,first_name,last_name,mother_name,father_name,birth_date,district,section";
+
+    async fn test_pool() -> SqlitePool {
+        std::env::set_var(
+            "SECRET_KEY",
+            "5RMI18i7kVrig0n3sREaQZTGCIveilg3wXaijT1RH3zBWvt/9vw7lxVQ8IQpy5+afRD60iObOv8W1MFYSFf7Yg==",
+        );
+        // One connection, as every connection to `:memory:` is a new database.
+        let config = DatabaseConfig {
+            max_connections: 1,
+            ..DatabaseConfig::default()
+        };
+        let conn = config.connect("sqlite::memory:").await.unwrap();
+        crate::migrate(&conn).await.unwrap();
+        conn
+    }
+
+    fn roll(rows: &[&str]) -> Vec<u8> {
+        let mut csv = HEADER.to_string();
+        for row in rows {
+            csv.push('\n');
+            csv.push_str(row);
+        }
+        csv.into_bytes()
+    }
+
+    #[tokio::test]
+    async fn imports_a_valid_roll() {
+        let conn = test_pool().await;
+        let input = roll(&[
+            "529.982.247-25,0043 5687 0906,Ana,Souza,Maria Souza,José Souza,01/02/1980,001,0001",
+            "11144477735,,João,D'Ávila,Rita D'Ávila,Paulo D'Ávila,31/12/1999,,",
+        ]);
+
+        let report = Voter::import_csv(&conn, &input, "test", false)
+            .await
+            .unwrap();
+        assert_eq!(report.rows, 2);
+        assert_eq!(report.imported, 2);
+        assert!(report.errors.is_empty());
+
+        let voter = Voter::find_by_national_id(&conn, "52998224725")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(voter.voter_card.as_deref(), Some("004356870906"));
+        assert_eq!(voter.district.as_deref(), Some("001"));
+        let voter = Voter::find_by_national_id(&conn, "11144477735")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(voter.voter_card, None);
+        assert_eq!(voter.district, None);
+    }
+
+    #[tokio::test]
+    async fn reads_semicolon_separated_rolls() {
+        let conn = test_pool().await;
+        let input = format!(
+            "{}\n{}",
+            HEADER.replace(',', " ; "),
+            " 52998224725 ;; Ana ; Souza ; Maria ; José ; 01/02/1980 ;; "
+        );
+
+        let report = Voter::import_csv(&conn, input.as_bytes(), "test", false)
+            .await
+            .unwrap();
+        assert!(report.errors.is_empty(), "{:?}", report.errors);
+        assert_eq!(report.imported, 1);
+    }
+
+    #[tokio::test]
+    async fn reports_every_error_and_writes_nothing() {
+        let conn = test_pool().await;
+        let input = roll(&[
+            "52998224725,,Ana,Souza,Maria,José,01/02/1980,,",
+            "52998224724,,Bruno,Lima,Carla,Diego,01/02/1980,,",
+            "11144477735,,Carla,L1ma,Carla,Diego,1980-02-01,,",
+            "529.982.247-25,,Ana,Souza,Maria,José,01/02/1980,,",
+            "39053344705,,Diego",
+        ]);
+
+        let report = Voter::import_csv(&conn, &input, "test", false)
+            .await
+            .unwrap();
+        assert_eq!(report.rows, 5);
+        assert_eq!(report.imported, 0);
+        let errors: Vec<(usize, Option<&str>)> = report
+            .errors
+            .iter()
+            .map(|error| (error.line, error.field.as_deref()))
+            .collect();
+        assert_eq!(
+            errors,
+            [
+                (3, Some("national_id")),
+                (4, Some("birth_date")),
+                (4, Some("last_name")),
+                (5, Some("national_id")),
+                (6, None),
+            ]
+        );
+        assert_eq!(report.errors[3].message, "duplicate of line 2");
+
+        assert!(Voter::find_by_national_id(&conn, "52998224725")
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn dry_runs_validate_without_writing() {
+        let conn = test_pool().await;
+        let input = roll(&["52998224725,,Ana,Souza,Maria,José,01/02/1980,,"]);
+
+        let report = Voter::import_csv(&conn, &input, "test", true)
+            .await
+            .unwrap();
+        assert!(report.dry_run);
+        assert!(report.errors.is_empty());
+        assert_eq!(report.imported, 0);
+        assert!(Voter::find_by_national_id(&conn, "52998224725")
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn rejects_voters_already_registered() {
+        let conn = test_pool().await;
+        let input = roll(&["52998224725,004356870906,Ana,Souza,Maria,José,01/02/1980,,"]);
+        let report = Voter::import_csv(&conn, &input, "test", false)
+            .await
+            .unwrap();
+        assert_eq!(report.imported, 1);
+
+        let input = roll(&[
+            "52998224725,,Ana,Souza,Maria,José,01/02/1980,,",
+            "11144477735,004356870906,Bruno,Lima,Carla,Diego,01/02/1980,,",
+        ]);
+        let report = Voter::import_csv(&conn, &input, "test", false)
+            .await
+            .unwrap();
+        assert_eq!(report.imported, 0);
+        let errors: Vec<(usize, Option<&str>)> = report
+            .errors
+            .iter()
+            .map(|error| (error.line, error.field.as_deref()))
+            .collect();
+        assert_eq!(errors, [(2, Some("national_id")), (3, Some("voter_card"))]);
+        assert!(report
+            .errors
+            .iter()
+            .all(|error| error.message == "voter already registered"));
+    }
+
+    #[test]
+    fn validates_names_and_birth_dates() {
+        assert!(validate_name("Ana Maria D'Ávila-Souza Jr.").is_ok());
+        assert!(validate_name("R2-D2").is_err());
+        assert!(validate_name("--").is_err());
+
+        assert!(validate_birth_date("29/02/2000").is_ok());
+        assert!(validate_birth_date("29/02/2001").is_err());
+        assert!(validate_birth_date("2000-01-01").is_err());
+        assert!(validate_birth_date("31/12/1899").is_err());
+        assert!(validate_birth_date("01/01/2999").is_err());
+    }
+}
@@ -1,15 +1,54 @@
-use serde::{Deserialize, Serialize};
-use sqlx::SqlitePool;
+mod documents;
+mod import;
+
+use chrono::NaiveDate;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use sqlx::{SqliteConnection, SqlitePool};
 use uuid::Uuid;
+use validator::Validate;
+
+use crate::AuditEvent;
+
+pub use documents::{normalize_document, validate_cpf, validate_voter_card};
+pub use import::{ImportError, ImportReport};
+
+/// How birth dates are written in voter rolls and the API, as on Brazilian
+/// documents. The database stores them as ISO dates.
+pub const BIRTH_DATE_FORMAT: &str = "%d/%m/%Y";
+
+fn serialize_birth_date<S: Serializer>(date: &NaiveDate, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&date.format(BIRTH_DATE_FORMAT).to_string())
+}
+
+fn deserialize_birth_date<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<NaiveDate, D::Error> {
+    let value = String::deserialize(deserializer)?;
+    NaiveDate::parse_from_str(&value, BIRTH_DATE_FORMAT).map_err(serde::de::Error::custom)
+}
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Validate)]
 pub struct Voter {
     pub id: String,
     pub first_name: String,
     pub last_name: String,
     pub mother_name: String,
     pub father_name: String,
-    pub birth_date: String,
+    #[serde(
+        serialize_with = "serialize_birth_date",
+        deserialize_with = "deserialize_birth_date"
+    )]
+    pub birth_date: NaiveDate,
+    /// Electoral district (zona) and polling section (seção) the voter is
+    /// registered in.
+    pub district: Option<String>,
+    pub section: Option<String>,
+    /// Civil registry number (CPF) the voter is deduplicated by, digits only.
+    #[validate(custom(function = "validate_cpf"))]
+    pub national_id: Option<String>,
+    /// Título de eleitor, digits only.
+    #[validate(custom(function = "validate_voter_card"))]
+    pub voter_card: Option<String>,
 }
 
 impl Voter {
@@ -18,7 +57,9 @@ impl Voter {
         last_name: String,
         mother_name: String,
         father_name: String,
-        birth_date: String,
+        birth_date: NaiveDate,
+        district: Option<String>,
+        section: Option<String>,
     ) -> Voter {
         Voter {
             id: Uuid::now_v7().to_string(),
@@ -27,24 +68,142 @@ impl Voter {
             mother_name,
             father_name,
             birth_date,
+            district,
+            section,
+            national_id: None,
+            voter_card: None,
         }
     }
-    pub async fn create<'a>(&self, conn: &'a SqlitePool) -> Result<(), sqlx::Error> {
-        sqlx::query(
+
+    pub async fn create(&self, conn: &SqlitePool, actor: &str) -> Result<(), anyhow::Error> {
+        self.validate()?;
+
+        let mut tx = conn.begin().await?;
+        self.insert(&mut tx, actor).await?;
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn insert(&self, conn: &mut SqliteConnection, actor: &str) -> Result<(), sqlx::Error> {
+        sqlx::query!(
             r#"
-            INSERT INTO voters (id, first_name, last_name, mother_name, father_name, birth_date)
-            VALUES (?, ?, ?, ?, ?, ?)
+            INSERT INTO voters (id, first_name, last_name, mother_name, father_name, birth_date, district, section, national_id, voter_card)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
             "#,
+            self.id,
+            self.first_name,
+            self.last_name,
+            self.mother_name,
+            self.father_name,
+            self.birth_date,
+            self.district,
+            self.section,
+            self.national_id,
+            self.voter_card,
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        AuditEvent::record(
+            conn,
+            "voter",
+            &self.id,
+            "create",
+            actor,
+            None,
+            serde_json::to_value(self).ok(),
         )
-        .bind(&self.id)
-        .bind(&self.first_name)
-        .bind(&self.last_name)
-        .bind(&self.mother_name)
-        .bind(&self.father_name)
-        .bind(&self.birth_date)
-        .execute(conn)
         .await?;
 
         Ok(())
     }
+
+    pub async fn find(conn: &SqlitePool, id: &str) -> Result<Option<Voter>, sqlx::Error> {
+        sqlx::query_as!(
+            Voter,
+            r#"
+            SELECT
+                id AS "id!: String",
+                first_name,
+                last_name,
+                mother_name,
+                father_name,
+                birth_date,
+                district,
+                section,
+                national_id,
+                voter_card
+            FROM
+                voters
+            WHERE
+                id = ?
+            "#,
+            id
+        )
+        .fetch_optional(conn)
+        .await
+    }
+
+    /// Looks a voter up by CPF, with or without punctuation.
+    pub async fn find_by_national_id(
+        conn: &SqlitePool,
+        national_id: &str,
+    ) -> Result<Option<Voter>, sqlx::Error> {
+        let national_id = normalize_document(national_id);
+        sqlx::query_as!(
+            Voter,
+            r#"
+            SELECT
+                id AS "id!: String",
+                first_name,
+                last_name,
+                mother_name,
+                father_name,
+                birth_date,
+                district,
+                section,
+                national_id,
+                voter_card
+            FROM
+                voters
+            WHERE
+                national_id = ?
+            "#,
+            national_id
+        )
+        .fetch_optional(conn)
+        .await
+    }
+
+    /// Looks a voter up by título de eleitor, with or without punctuation.
+    pub async fn find_by_voter_card(
+        conn: &SqlitePool,
+        voter_card: &str,
+    ) -> Result<Option<Voter>, sqlx::Error> {
+        let voter_card = normalize_document(voter_card);
+        sqlx::query_as!(
+            Voter,
+            r#"
+            SELECT
+                id AS "id!: String",
+                first_name,
+                last_name,
+                mother_name,
+                father_name,
+                birth_date,
+                district,
+                section,
+                national_id,
+                voter_card
+            FROM
+                voters
+            WHERE
+                voter_card = ?
+            "#,
+            voter_card
+        )
+        .fetch_optional(conn)
+        .await
+    }
 }
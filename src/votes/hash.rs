@@ -0,0 +1,58 @@
+use chrono::SecondsFormat;
+
+use super::{Vote, VoteKind};
+use crate::encoding::CanonicalEncoder;
+
+/// Hash version written by `Vote::build`. Rows keep the version they were
+/// created with in `votes.hash_version`, so older encodings stay verifiable.
+pub const CURRENT_HASH_VERSION: i32 = 2;
+
+const DOMAIN_V2: &str = "bbox/vote/v2";
+
+/// v1: the original unframed concatenation. Only used to verify old rows.
+fn encode_v1(vote: &Vote, previous: &Vote) -> Vec<u8> {
+    let mut buf = Vec::new();
+    buf.extend_from_slice(vote.voter_id.as_deref().unwrap_or("").as_bytes());
+    buf.extend_from_slice(vote.candidature_id.as_deref().unwrap_or("").as_bytes());
+    buf.extend_from_slice(previous.hash.as_bytes());
+    buf.extend_from_slice(previous.created_at.to_string().as_bytes());
+    buf
+}
+
+/// v2: every field of the vote except the hash itself, in a fixed order.
+///
+/// Fields added to `Vote` after v2 must be optional and are skipped when
+/// absent, which keeps the encoding of existing rows unchanged.
+pub(crate) fn encode_v2(vote: &Vote) -> Vec<u8> {
+    let year = vote.year.to_string();
+    let created_at = vote.created_at.to_rfc3339_opts(SecondsFormat::Nanos, true);
+    let position = vote.candidature_position.to_string();
+    let kind = vote.kind.to_string();
+    let kind = (vote.kind != VoteKind::Candidature).then_some(kind.as_str());
+
+    let mut encoder = CanonicalEncoder::new(DOMAIN_V2);
+    encoder
+        .field("id", &vote.id)
+        .optional_field("voter_id", vote.voter_id.as_deref())
+        .optional_field("candidature_id", vote.candidature_id.as_deref())
+        .field("candidature_position", &position)
+        .field("previous_hash", &vote.previous_hash)
+        .field("year", &year)
+        .field("created_at", &created_at)
+        .optional_field("ballot_token", vote.ballot_token.as_deref())
+        .optional_field("election_id", vote.election_id.as_deref())
+        .optional_field("encrypted_ballot", vote.encrypted_ballot.as_deref())
+        .optional_field("kind", kind)
+        .optional_field("district", vote.district.as_deref())
+        .optional_field("section", vote.section.as_deref())
+        .optional_field("key_id", vote.key_id.as_deref());
+    encoder.finish()
+}
+
+pub(crate) fn encode(vote: &Vote, previous: &Vote) -> Result<Vec<u8>, anyhow::Error> {
+    match vote.hash_version {
+        1 => Ok(encode_v1(vote, previous)),
+        2 => Ok(encode_v2(vote)),
+        version => Err(anyhow::anyhow!("unknown hash version: {}", version)),
+    }
+}
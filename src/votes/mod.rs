@@ -1,247 +1,922 @@
-use std::{collections::HashMap, env};
+mod hash;
+pub(crate) mod participation;
+
+use std::{collections::BTreeMap, env, fmt};
 
 use anyhow::anyhow;
-use chrono::Datelike;
-use hmac::{Hmac, Mac};
+use chrono::{DateTime, Datelike, SubsecRound, TimeDelta, Utc};
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
-use sha2::Sha256;
 use sqlx::{Row, SqlitePool};
 use uuid::Uuid;
 
-use crate::{Candidature, CandidaturePosition};
+use crate::{
+    elgamal::{Ciphertext, EncodedCiphertext},
+    encoding, BallotKey, BallotToken, Candidature, CandidaturePosition, Cursor, Election,
+    ElectionStatus, Page, PageRequest, Repository, SortOrder, TallyMode,
+};
+
+pub use hash::CURRENT_HASH_VERSION;
+pub use participation::AlreadyVoted;
+
+const GENESIS_ID: &str = "00000000-0000-0000-0000-000000000000";
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Default)]
+pub enum VoteKind {
+    #[default]
+    #[serde(rename = "candidature")]
+    Candidature,
+    #[serde(rename = "blank")]
+    Blank,
+    #[serde(rename = "null")]
+    Null,
+}
+
+impl fmt::Display for VoteKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self {
+            VoteKind::Candidature => "candidature",
+            VoteKind::Blank => "blank",
+            VoteKind::Null => "null",
+        };
+        write!(f, "{}", kind)
+    }
+}
+
+impl From<String> for VoteKind {
+    fn from(kind: String) -> VoteKind {
+        match kind.as_str() {
+            "blank" => VoteKind::Blank,
+            "null" => VoteKind::Null,
+            _ => VoteKind::Candidature,
+        }
+    }
+}
+
+/// No candidature of the position has the code the ballot was cast for.
+/// Null votes must be asked for explicitly, so a mistyped code is rejected
+/// rather than silently counted as one.
+#[derive(Debug)]
+pub struct CandidatureNotFound;
+
+impl fmt::Display for CandidatureNotFound {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "candidature not found")
+    }
+}
+
+impl std::error::Error for CandidatureNotFound {}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum VoteSort {
+    #[serde(rename = "votes")]
+    Votes,
+    #[serde(rename = "code")]
+    Code,
+    #[serde(rename = "name")]
+    Name,
+}
+
+impl VoteSort {
+    fn column(&self) -> &'static str {
+        match self {
+            VoteSort::Votes => "sort_votes",
+            VoteSort::Code => "sort_code",
+            VoteSort::Name => "sort_name",
+        }
+    }
+}
+
+/// Filters of `Vote::list`. Without a year or election it counts the
+/// current year.
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct VoteFilter {
+    pub candidature_position: Option<CandidaturePosition>,
+    /// Party id or acronym.
+    pub party: Option<String>,
+    pub year: Option<i32>,
+    pub district: Option<String>,
+    /// Polling section within `district`.
+    pub section: Option<String>,
+    pub election: Option<String>,
+    /// Part of the candidate's civil or ballot name.
+    pub search: Option<String>,
+    pub sort: Option<VoteSort>,
+}
+
+impl VoteFilter {
+    /// The election `Vote::list` counts for this filter: the one asked for,
+    /// or the current election of the year.
+    pub async fn election(&self, conn: &SqlitePool) -> Result<Option<Election>, sqlx::Error> {
+        match &self.election {
+            Some(election_id) => Election::find(conn, election_id).await,
+            None => {
+                let year = self.year.unwrap_or_else(|| chrono::Utc::now().year());
+                Election::current(conn, year).await
+            }
+        }
+    }
+
+    /// The election counted and the year of its candidatures. An election
+    /// asked for by id must exist.
+    async fn scope(&self, conn: &SqlitePool) -> Result<(Option<Election>, i32), anyhow::Error> {
+        let election = self.election(conn).await?;
+        if self.election.is_some() && election.is_none() {
+            return Err(anyhow!("election not found"));
+        }
+        let year = self
+            .year
+            .or(election.as_ref().map(|election| election.year))
+            .unwrap_or_else(|| chrono::Utc::now().year());
+        Ok((election, year))
+    }
+}
+
+/// Who is casting a ballot: an identified voter, or an anonymous holder of a
+/// blind-signed ballot token obtained through `BallotToken::issue`.
+pub enum VoteCredential {
+    Voter(String),
+    BallotToken(BallotToken),
+}
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Vote {
     pub id: String,
-    pub voter_id: String,
-    pub candidature_id: String,
+    pub voter_id: Option<String>,
+    pub candidature_id: Option<String>,
     pub candidature_position: CandidaturePosition,
     pub hash: String,
     pub previous_hash: String,
+    pub hash_version: i32,
     pub year: i32,
     pub created_at: chrono::DateTime<chrono::Utc>,
+    pub ballot_token: Option<String>,
+    pub election_id: Option<String>,
+    /// JSON map of candidature id to ElGamal ciphertext, one-hot over every
+    /// candidature of the position. Set instead of `candidature_id` when the
+    /// election tallies encrypted ballots.
+    pub encrypted_ballot: Option<String>,
+    pub kind: VoteKind,
+    /// Polling section of the voter, copied at casting time. Ballots cast
+    /// with a token are not tied to a voter and have none.
+    pub district: Option<String>,
+    pub section: Option<String>,
+    /// Fingerprint of the `SECRET_KEY` the hash was made with.
+    pub key_id: Option<String>,
 }
 
 impl Vote {
-    pub async fn build<'a>(
-        conn: &'a SqlitePool,
-        voter_id: String,
-        code: String,
+    /// Builds a vote of `kind`. Votes for a candidature take its `code`;
+    /// blank and null votes take none. Voters and candidatures are read
+    /// through `repository`; elections and ballot keys from `conn`.
+    pub async fn build(
+        conn: &SqlitePool,
+        repository: &dyn Repository,
+        credential: VoteCredential,
+        kind: VoteKind,
+        code: Option<String>,
         candidature_position: CandidaturePosition,
     ) -> Result<Self, anyhow::Error> {
-        let row = sqlx::query(
-            r#"
-            SELECT
-                id,
-                party_id,
-                candidate_id,
-                code,
-                position,
-                year,
-                image_url
-            FROM
-                candidatures WHERE code = ? AND
-                position = ?
-            "#,
-        )
-        .bind(&code)
-        .bind(&candidature_position.to_string())
-        .fetch_one(conn)
-        .await;
-
-        if let Err(reason) = row {
-            return Err(anyhow!("candidature not found: {}", reason));
+        let current_year = chrono::Utc::now().year();
+        let election = Election::current(conn, current_year).await?;
+        if let Some(election) = &election {
+            if election.status != ElectionStatus::Open {
+                return Err(anyhow!("election is not open"));
+            }
         }
 
-        let row = row?;
-        let candidature = Candidature {
-            id: row.get(0),
-            party_id: row.get(1),
-            candidate_id: row.get(2),
-            code: row.get(3),
-            position: candidature_position,
-            year: row.get(5),
-            image_url: row.get(6),
+        let (voter_id, ballot_token, district, section) = match credential {
+            VoteCredential::Voter(voter_id) => {
+                let participated = repository
+                    .has_participated(
+                        &voter_id,
+                        &candidature_position,
+                        election.as_ref(),
+                        current_year,
+                    )
+                    .await?;
+                if participated {
+                    return Err(AlreadyVoted.into());
+                }
+                let voter = repository
+                    .find_voter(&voter_id)
+                    .await?
+                    .ok_or_else(|| anyhow!("voter not found"))?;
+                if let Some(election) = &election {
+                    election.ensure_voting_age(voter.birth_date)?;
+                }
+                (Some(voter_id), None, voter.district, voter.section)
+            }
+            VoteCredential::BallotToken(token) => {
+                Vote::ensure_token_spendable(
+                    conn,
+                    &token,
+                    &candidature_position,
+                    election.as_ref(),
+                )
+                .await?;
+                (None, Some(token.id()), None, None)
+            }
         };
-        let current_year = chrono::Utc::now().year();
-        let row = sqlx::query(
+
+        let candidature = match (&kind, code) {
+            (VoteKind::Candidature, Some(code)) => Some(
+                repository
+                    .find_candidature(
+                        &code,
+                        &candidature_position,
+                        district.as_deref(),
+                        current_year,
+                        election.as_ref().map(|election| election.id.as_str()),
+                    )
+                    .await?
+                    .ok_or(CandidatureNotFound)?,
+            ),
+            (VoteKind::Candidature, None) => {
+                return Err(anyhow!("candidature_code is required"));
+            }
+            (_, Some(_)) => {
+                return Err(anyhow!("blank and null votes take no candidature_code"));
+            }
+            (_, None) => None,
+        };
+
+        let (candidature_id, encrypted_ballot) = match (&election, candidature) {
+            (Some(election), candidature) if election.tally_mode == TallyMode::Encrypted => {
+                let candidature = candidature.ok_or_else(|| {
+                    anyhow!("blank and null votes are not accepted in encrypted elections")
+                })?;
+                let ballot = Vote::encrypt_ballot(repository, election, &candidature).await?;
+                (None, Some(serde_json::to_string(&ballot)?))
+            }
+            (_, candidature) => (candidature.map(|candidature| candidature.id), None),
+        };
+
+        // Linked, dated and signed by `Vote::create`, once it holds the
+        // head of the chain.
+        Ok(Vote {
+            id: Uuid::now_v7().to_string(),
+            voter_id,
+            candidature_id,
+            candidature_position,
+            hash: String::new(),
+            previous_hash: String::new(),
+            hash_version: CURRENT_HASH_VERSION,
+            year: current_year,
+            created_at: chrono::Utc::now().trunc_subsecs(6),
+            ballot_token,
+            election_id: election.map(|election| election.id),
+            encrypted_ballot,
+            kind,
+            district,
+            section,
+            key_id: None,
+        })
+    }
+
+    /// Links the vote to the head of the chain and signs it. The timestamp
+    /// is taken now, in microseconds, the finest precision every storage
+    /// backend keeps, and kept after the head's so the chain's creation
+    /// order is also its link order. Backends call this while holding the
+    /// lock that serializes writers to the chain.
+    pub fn link(&mut self, previous_hash: String, previous_created_at: DateTime<Utc>) {
+        let now = Utc::now().trunc_subsecs(6);
+        self.previous_hash = previous_hash;
+        self.created_at = now.max(previous_created_at + TimeDelta::microseconds(1));
+        self.sign();
+    }
+
+    /// Hashes the vote with the current encoding and `SECRET_KEY`.
+    pub fn sign(&mut self) {
+        let secret_key = env::var("SECRET_KEY").expect("SECRET_KEY must be set");
+        self.hash_version = CURRENT_HASH_VERSION;
+        self.key_id = Some(encoding::key_id(&secret_key));
+        self.hash = encoding::sign(&secret_key, &hash::encode_v2(self));
+    }
+
+    /// Encrypts a one-hot ballot over all candidatures running for the
+    /// position: 1 for the chosen one and 0 for every other, so the stored
+    /// ballot does not reveal the choice.
+    async fn encrypt_ballot(
+        repository: &dyn Repository,
+        election: &Election,
+        candidature: &Candidature,
+    ) -> Result<BTreeMap<String, EncodedCiphertext>, anyhow::Error> {
+        let public_key = election.public_key_point()?;
+        let running = repository
+            .list_candidatures(&candidature.position, election.year)
+            .await?;
+
+        let mut ballot = BTreeMap::new();
+        for id in running.into_iter().map(|running| running.id) {
+            let message = u64::from(id == candidature.id);
+            ballot.insert(id, Ciphertext::encrypt(&public_key, message).encode());
+        }
+
+        Ok(ballot)
+    }
+
+    /// Hash of the newest vote, which the next vote links to.
+    pub async fn last_hash(conn: &SqlitePool) -> Result<String, sqlx::Error> {
+        sqlx::query_scalar!(
             r#"
             SELECT
-                id,
-                voter_id,
-                candidature_id,
-                hash,
-                year,
-                previous_hash,
-                created_at
+                hash
             FROM
                 votes
-            WHERE
-                voter_id = ? AND
-                candidature_position = ? AND
-                year = ?
-            "#,
+            ORDER BY
+                created_at DESC
+            LIMIT 1
+            "#
         )
-        .bind(&voter_id)
-        .bind(&candidature.position.to_string())
-        .bind(&current_year)
         .fetch_one(conn)
-        .await;
+        .await
+    }
 
-        if let Ok(_) = row {
+    async fn ensure_token_spendable(
+        conn: &SqlitePool,
+        token: &BallotToken,
+        position: &CandidaturePosition,
+        election: Option<&Election>,
+    ) -> Result<(), anyhow::Error> {
+        let election = election.ok_or_else(|| anyhow!("election is not open"))?;
+        let key = BallotKey::find(conn, &token.key_id)
+            .await?
+            .ok_or_else(|| anyhow!("ballot key not found"))?;
+        if key.position != *position || key.election_id.as_deref() != Some(election.id.as_str()) {
             return Err(anyhow!(
-                "voter already voted for this position in this year"
+                "ballot token was not issued for this position in this election"
             ));
         }
+        key.verify(token)?;
 
-        // get last vote from database
-        let row = sqlx::query(
+        let token_id = token.id();
+        let spent = sqlx::query!(
+            r#"SELECT id AS "id!: String" FROM votes WHERE ballot_token = ?"#,
+            token_id
+        )
+        .fetch_optional(conn)
+        .await?;
+        if spent.is_some() {
+            return Err(anyhow!("ballot token already spent"));
+        }
+
+        Ok(())
+    }
+
+    /// Stores the vote at the head of the chain. The transaction takes the
+    /// write lock before reading the head, as `BEGIN IMMEDIATE` would, so
+    /// concurrent votes wait for each other (up to the busy timeout) instead
+    /// of linking to the same predecessor.
+    pub async fn create(&mut self, conn: &SqlitePool) -> Result<(), anyhow::Error> {
+        let mut tx = conn.begin().await?;
+        // sqlx only issues a deferred BEGIN; a write that touches no row
+        // takes the lock all the same.
+        sqlx::query!("UPDATE votes SET hash = hash WHERE 0")
+            .execute(&mut *tx)
+            .await?;
+        let head = sqlx::query!(
             r#"
             SELECT
-                id,
-                voter_id,
-                candidature_id,
                 hash,
-                previous_hash,
-                created_at
+                created_at AS "created_at: DateTime<Utc>"
             FROM
                 votes
             ORDER BY
                 created_at DESC
             LIMIT 1
-            "#,
+            "#
         )
-        .fetch_one(conn)
+        .fetch_one(&mut *tx)
         .await?;
+        self.link(head.hash, head.created_at);
 
-        let last_vote = Vote {
-            id: row.get(0),
-            voter_id: row.get(1),
-            candidature_id: row.get(2),
-            candidature_position: CandidaturePosition::Councilor,
-            hash: row.get(3),
-            previous_hash: row.get(4),
-            created_at: row.get(5),
-            year: 0,
-        };
+        if let Some(voter_id) = &self.voter_id {
+            participation::record(
+                &mut tx,
+                voter_id,
+                &self.candidature_position,
+                self.election_id.as_deref(),
+                self.year,
+            )
+            .await?;
+        }
 
-        let secret_key = env::var("SECRET_KEY").expect("SECRET_KEY must be set");
-        let mut mac = Hmac::<Sha256>::new_from_slice(secret_key.as_bytes()).unwrap();
-        mac.update(voter_id.as_bytes());
-        mac.update(candidature.id.as_bytes());
-        mac.update(last_vote.hash.as_bytes());
-        mac.update(last_vote.created_at.to_string().as_bytes());
+        let position = self.candidature_position.to_string();
+        let kind = self.kind.to_string();
+        sqlx::query!(
+            r#"
+            INSERT INTO votes (id, voter_id, candidature_id, candidature_position, hash, previous_hash, hash_version, year, created_at, ballot_token, election_id, encrypted_ballot, kind, district, section, key_id)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#,
+            self.id,
+            self.voter_id,
+            self.candidature_id,
+            position,
+            self.hash,
+            self.previous_hash,
+            self.hash_version,
+            self.year,
+            self.created_at,
+            self.ballot_token,
+            self.election_id,
+            self.encrypted_ballot,
+            kind,
+            self.district,
+            self.section,
+            self.key_id,
+        )
+        .execute(&mut *tx)
+        .await?;
 
-        let result = mac.finalize();
-        let code_bytes = result.into_bytes();
-        let hash = hex::encode(code_bytes);
+        if let (Some(election_id), Some(encrypted_ballot)) =
+            (&self.election_id, &self.encrypted_ballot)
+        {
+            let ballot: BTreeMap<String, EncodedCiphertext> =
+                serde_json::from_str(encrypted_ballot)?;
+            Election::accumulate(&mut tx, election_id, &ballot).await?;
+        }
 
-        let vote = Vote {
-            id: Uuid::now_v7().to_string(),
-            voter_id,
-            candidature_id: candidature.id,
-            candidature_position: candidature.position,
-            hash,
-            previous_hash: last_vote.hash,
-            year: current_year,
-            created_at: chrono::Utc::now(),
-        };
+        tx.commit().await?;
 
-        Ok(vote)
+        Ok(())
     }
 
-    pub async fn create<'a>(&self, conn: &'a SqlitePool) -> Result<(), sqlx::Error> {
-        sqlx::query(
+    /// Data migration seeding the vote every chain starts from, dated the
+    /// year the database is set up. A no-op once it exists.
+    pub async fn ensure_genesis(conn: &SqlitePool) -> Result<(), sqlx::Error> {
+        let year = chrono::Utc::now().year();
+        sqlx::query!(
             r#"
-            INSERT INTO votes (id, voter_id, candidature_id, candidature_position, hash, previous_hash, year, created_at)
-            VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+            INSERT INTO votes (id, candidature_position, hash, previous_hash, year)
+            SELECT ?1, 'GENESIS', 'GENESIS', 'GENESIS', ?2
+            WHERE NOT EXISTS (SELECT 1 FROM votes WHERE id = ?1)
             "#,
+            GENESIS_ID,
+            year,
         )
-        .bind(&self.id)
-        .bind(&self.voter_id)
-        .bind(&self.candidature_id)
-        .bind(&self.candidature_position.to_string())
-        .bind(&self.hash)
-        .bind(&self.previous_hash)
-        .bind(&self.year)
-        .bind(&self.created_at)
         .execute(conn)
         .await?;
 
         Ok(())
     }
 
-    // get group by candidate name and count votes
-    pub async fn list<'a>(
-        conn: &'a SqlitePool,
-        candidature_position: CandidaturePosition,
-    ) -> Result<Vec<Value>, sqlx::Error> {
-        let current_year = chrono::Utc::now().year();
-        let rows = sqlx::query(
+    /// Walks the chain from the genesis vote, checking that every vote links
+    /// to its predecessor and that its hash matches the encoding recorded in
+    /// `hash_version`. Returns the number of votes verified.
+    pub async fn verify_chain(conn: &SqlitePool) -> Result<usize, anyhow::Error> {
+        Vote::verify(&Vote::chain(conn).await?)
+    }
+
+    /// Every vote in creation order, genesis vote first.
+    pub async fn chain(conn: &SqlitePool) -> Result<Vec<Vote>, sqlx::Error> {
+        let rows = sqlx::query!(
             r#"
             SELECT
-                c.first_name,
-                c.last_name,
-                c.id,
-                ca.code,
-                ca.position,
-                ca.year,
-                ca.image_url,
-                p.name,
-                p.acronym,
-                p.id,
-                COUNT(v.id) as votes
+                id AS "id!: String",
+                voter_id AS "voter_id: String",
+                candidature_id AS "candidature_id: String",
+                candidature_position,
+                hash,
+                previous_hash,
+                hash_version AS "hash_version: i32",
+                year AS "year: i32",
+                created_at AS "created_at: chrono::DateTime<chrono::Utc>",
+                ballot_token,
+                election_id AS "election_id: String",
+                encrypted_ballot,
+                kind,
+                district,
+                section,
+                key_id
             FROM
-                votes v
-            JOIN
-                candidatures ca ON v.candidature_id = ca.id
-            JOIN
-                candidates c ON ca.candidate_id = c.id
-            JOIN
-                parties p ON ca.party_id = p.id
-            WHERE
-                v.candidature_position = ? AND
-                v.year = ?
-            GROUP BY
-                ca.id
+                votes
             ORDER BY
-                votes DESC
-            "#,
+                created_at ASC
+            "#
         )
-        .bind(candidature_position.to_string())
-        .bind(current_year)
         .fetch_all(conn)
         .await?;
 
+        Ok(rows
+            .into_iter()
+            .map(|row| Vote {
+                id: row.id,
+                voter_id: row.voter_id,
+                candidature_id: row.candidature_id,
+                candidature_position: CandidaturePosition::from(row.candidature_position),
+                hash: row.hash,
+                previous_hash: row.previous_hash,
+                hash_version: row.hash_version,
+                year: row.year,
+                created_at: row.created_at,
+                ballot_token: row.ballot_token,
+                election_id: row.election_id,
+                encrypted_ballot: row.encrypted_ballot,
+                kind: VoteKind::from(row.kind),
+                district: row.district,
+                section: row.section,
+                key_id: row.key_id,
+            })
+            .collect())
+    }
+
+    /// Checks a chain in creation order, genesis vote first, whatever
+    /// storage it was read from.
+    pub fn verify(votes: &[Vote]) -> Result<usize, anyhow::Error> {
+        let secret_key = env::var("SECRET_KEY").expect("SECRET_KEY must be set");
+        let mut previous: Option<&Vote> = None;
+        let mut verified = 0;
+
+        for vote in votes {
+            let Some(last) = previous else {
+                if vote.id != GENESIS_ID {
+                    return Err(anyhow!("chain does not start at the genesis vote"));
+                }
+                previous = Some(vote);
+                continue;
+            };
+
+            if vote.previous_hash != last.hash {
+                return Err(anyhow!(
+                    "vote {} does not link to vote {}",
+                    vote.id,
+                    last.id
+                ));
+            }
+
+            let expected = encoding::sign(&secret_key, &hash::encode(vote, last)?);
+            if expected != vote.hash {
+                return Err(anyhow!("vote {} has an invalid hash", vote.id));
+            }
+
+            verified += 1;
+            previous = Some(vote);
+        }
+
+        Ok(verified)
+    }
+
+    // get group by candidate name and count votes
+    //
+    // Encrypted elections have no per-vote candidature to group by; their
+    // totals come from `election_results` once the trustees have decrypted
+    // them, and are empty until then.
+    pub async fn list(
+        conn: &SqlitePool,
+        filter: &VoteFilter,
+        page: &PageRequest,
+    ) -> Result<Page<Value>, anyhow::Error> {
+        let cursor = page.cursor()?;
+        let (election, year) = filter.scope(conn).await?;
+        // Counts are scoped to the resolved election rather than the filter,
+        // so a year with a runoff does not add both rounds together.
+        let election_id = election.as_ref().map(|election| election.id.clone());
+
+        // Encrypted elections are only decrypted as a whole, so they have no
+        // counts per district.
+        let (counts, tally_election) = match &election {
+            Some(election) if election.tally_mode == TallyMode::Encrypted => (
+                r#"
+                SELECT
+                    candidature_id,
+                    votes
+                FROM
+                    election_results
+                WHERE
+                    election_id = ?7 AND
+                    ?4 IS NULL AND
+                    ?8 IS NULL
+                "#,
+                Some(election.id.clone()),
+            ),
+            _ => (
+                r#"
+                SELECT
+                    candidature_id,
+                    COUNT(id) AS votes
+                FROM
+                    votes
+                WHERE
+                    year = ?2 AND
+                    (?5 IS NULL OR election_id = ?5) AND
+                    (?4 IS NULL OR district = ?4) AND
+                    (?8 IS NULL OR section = ?8)
+                GROUP BY
+                    candidature_id
+                "#,
+                None,
+            ),
+        };
+        let sort = filter.sort.unwrap_or(VoteSort::Votes);
+        let default_order = match sort {
+            VoteSort::Votes => SortOrder::Desc,
+            _ => SortOrder::Asc,
+        };
+        let (keyset, tail) = page.keyset(sort.column(), default_order, 9);
+
+        let rows = page
+            .bind_page(
+                sqlx::query(&format!(
+                    r#"
+                    SELECT
+                        *
+                    FROM (
+                        SELECT
+                            c.first_name,
+                            c.last_name,
+                            c.id AS candidate_id,
+                            ca.code,
+                            ca.position,
+                            ca.year,
+                            ca.image_url,
+                            p.name AS party_name,
+                            p.acronym AS party_acronym,
+                            p.id AS party_id,
+                            t.votes,
+                            ca.id AS id,
+                            COALESCE(c.ballot_name, c.first_name || ' ' || c.last_name) AS sort_name,
+                            ca.code AS sort_code,
+                            t.votes AS sort_votes
+                        FROM
+                            ({}) t
+                        JOIN
+                            candidatures ca ON t.candidature_id = ca.id
+                        JOIN
+                            candidates c ON ca.candidate_id = c.id
+                        JOIN
+                            parties p ON ca.party_id = p.id
+                        WHERE
+                            (?1 IS NULL OR ca.position = ?1) AND
+                            ca.year = ?2 AND
+                            (?3 IS NULL OR p.id = ?3 OR p.acronym = ?3) AND
+                            (?6 IS NULL OR
+                                c.first_name || ' ' || c.last_name LIKE '%' || ?6 || '%' OR
+                                c.ballot_name LIKE '%' || ?6 || '%')
+                    )
+                    WHERE
+                        {}
+                    {}
+                    "#,
+                    counts, keyset, tail
+                ))
+                .bind(
+                    filter
+                        .candidature_position
+                        .as_ref()
+                        .map(|position| position.to_string()),
+                )
+                .bind(year)
+                .bind(&filter.party)
+                .bind(&filter.district)
+                .bind(election_id)
+                .bind(&filter.search)
+                .bind(tally_election)
+                .bind(&filter.section),
+                cursor,
+            )
+            .fetch_all(conn)
+            .await?;
         let mut cs = Vec::new();
 
+        // Dynamic like `Candidature::list`, so columns are read by name.
         for row in rows {
             let value = json!(
                 {
                     "candidate": {
-                        "first_name": row.get::<String, usize>(0),
-                        "last_name": row.get::<String, usize>(1),
-                        "id": row.get::<String, usize>(2),
+                        "first_name": row.get::<String, _>("first_name"),
+                        "last_name": row.get::<String, _>("last_name"),
+                        "id": row.get::<String, _>("candidate_id"),
                     },
                     "candidature": {
-                        "code": row.get::<String, usize>(3),
-                        "position": row.get::<String, usize>(4),
-                        "year": row.get::<i32, usize>(5),
-                        "image_url": row.get::<String, usize>(6),
+                        "code": row.get::<String, _>("code"),
+                        "position": row.get::<String, _>("position"),
+                        "year": row.get::<i32, _>("year"),
+                        "image_url": row.get::<String, _>("image_url"),
                     },
                     "party": {
-                        "name": row.get::<String, usize>(7),
-                        "acronym": row.get::<String, usize>(8),
-                        "id": row.get::<String, usize>(9),
+                        "name": row.get::<String, _>("party_name"),
+                        "acronym": row.get::<String, _>("party_acronym"),
+                        "id": row.get::<String, _>("party_id"),
                     },
-                    "votes": row.get::<i64, usize>(10),
+                    "votes": row.get::<i64, _>("votes"),
                 }
             );
+            let key = match sort {
+                VoteSort::Votes => json!(row.get::<i64, &str>("sort_votes")),
+                _ => Value::String(row.get(sort.column())),
+            };
+            let cursor = Cursor {
+                key,
+                id: row.get("id"),
+            };
+
+            cs.push((value, cursor));
+        }
+
+        Ok(page.page(cs))
+    }
+
+    /// Blank and null votes of each position under `filter`, which `list`
+    /// leaves out as they have no candidature. Filters on candidatures
+    /// (party, search) do not apply to them.
+    pub async fn count_blank_and_null(
+        conn: &SqlitePool,
+        filter: &VoteFilter,
+    ) -> Result<Vec<(CandidaturePosition, VoteKind, i64)>, anyhow::Error> {
+        let (election, year) = filter.scope(conn).await?;
+        let election_id = election.map(|election| election.id);
+        let position = filter
+            .candidature_position
+            .as_ref()
+            .map(|position| position.to_string());
+
+        let rows = sqlx::query!(
+            r#"
+            SELECT
+                candidature_position,
+                kind,
+                COUNT(id) AS "votes!: i64"
+            FROM
+                votes
+            WHERE
+                kind IN ('blank', 'null') AND
+                year = ?1 AND
+                (?2 IS NULL OR election_id = ?2) AND
+                (?3 IS NULL OR district = ?3) AND
+                (?4 IS NULL OR section = ?4) AND
+                (?5 IS NULL OR candidature_position = ?5)
+            GROUP BY
+                candidature_position,
+                kind
+            "#,
+            year,
+            election_id,
+            filter.district,
+            filter.section,
+            position
+        )
+        .fetch_all(conn)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| {
+                (
+                    CandidaturePosition::from(row.candidature_position),
+                    VoteKind::from(row.kind),
+                    row.votes,
+                )
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+    use crate::{
+        testing::{self, test_pool},
+        SqliteRepository, Voter,
+    };
+
+    async fn cast(conn: &SqlitePool, voter: &Voter, code: &str) -> Result<Vote, anyhow::Error> {
+        let mut vote = Vote::build(
+            conn,
+            &SqliteRepository::new(conn.clone()),
+            VoteCredential::Voter(voter.id.clone()),
+            VoteKind::Candidature,
+            Some(code.to_string()),
+            CandidaturePosition::President,
+        )
+        .await?;
+        vote.create(conn).await?;
+        Ok(vote)
+    }
+
+    async fn counts(conn: &SqlitePool, filter: &VoteFilter) -> Vec<i64> {
+        Vote::list(conn, filter, &PageRequest::default())
+            .await
+            .unwrap()
+            .items
+            .iter()
+            .map(|row| row["votes"].as_i64().unwrap())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn counts_each_round_on_its_own() {
+        let conn = test_pool().await;
+        let year = chrono::Utc::now().year();
+        let party = testing::party(&conn, 55).await;
+        let candidate =
+            testing::candidate(&conn, &party, NaiveDate::from_ymd_opt(1960, 1, 1).unwrap()).await;
+
+        let first_round = testing::open_election(&conn, year, 1).await;
+        testing::candidature(&candidate, "55", CandidaturePosition::President, year)
+            .create(&conn, testing::ACTOR)
+            .await
+            .unwrap();
+        cast(&conn, &testing::voter(&conn, None).await, "55")
+            .await
+            .unwrap();
+        cast(&conn, &testing::voter(&conn, None).await, "55")
+            .await
+            .unwrap();
+        first_round.close(&conn, testing::ACTOR).await.unwrap();
+
+        let runoff = testing::open_election(&conn, year, 2).await;
+        cast(&conn, &testing::voter(&conn, None).await, "55")
+            .await
+            .unwrap();
+
+        assert_eq!(counts(&conn, &VoteFilter::default()).await, vec![1]);
+        let filter = VoteFilter {
+            election: Some(first_round.id.clone()),
+            ..VoteFilter::default()
+        };
+        assert_eq!(counts(&conn, &filter).await, vec![2]);
+        let filter = VoteFilter {
+            election: Some(runoff.id.clone()),
+            ..VoteFilter::default()
+        };
+        assert_eq!(counts(&conn, &filter).await, vec![1]);
+    }
+
+    #[tokio::test]
+    async fn codes_select_the_candidature_of_the_current_cycle() {
+        let conn = test_pool().await;
+        let year = chrono::Utc::now().year();
+        let party = testing::party(&conn, 55).await;
+        let birth_date = NaiveDate::from_ymd_opt(1960, 1, 1).unwrap();
+
+        let last_cycle = testing::candidature(
+            &testing::candidate(&conn, &party, birth_date).await,
+            "55",
+            CandidaturePosition::President,
+            year - 4,
+        );
+        last_cycle.create(&conn, testing::ACTOR).await.unwrap();
+        testing::open_election(&conn, year, 1).await;
+        let current = testing::candidature(
+            &testing::candidate(&conn, &party, birth_date).await,
+            "55",
+            CandidaturePosition::President,
+            year,
+        );
+        current.create(&conn, testing::ACTOR).await.unwrap();
+
+        let vote = cast(&conn, &testing::voter(&conn, None).await, "55")
+            .await
+            .unwrap();
+        assert_eq!(vote.candidature_id, Some(current.id.clone()));
+        assert_eq!(counts(&conn, &VoteFilter::default()).await, vec![1]);
+    }
+
+    #[tokio::test]
+    async fn a_second_vote_of_the_voter_is_already_voted() {
+        let conn = test_pool().await;
+        let year = chrono::Utc::now().year();
+        let party = testing::party(&conn, 55).await;
+        let candidate =
+            testing::candidate(&conn, &party, NaiveDate::from_ymd_opt(1960, 1, 1).unwrap()).await;
+        testing::open_election(&conn, year, 1).await;
+        testing::candidature(&candidate, "55", CandidaturePosition::President, year)
+            .create(&conn, testing::ACTOR)
+            .await
+            .unwrap();
+        let voter = testing::voter(&conn, None).await;
 
-            cs.push(value);
+        // Both ballots pass the early check, as two concurrent requests
+        // would; only the participations index stands between them.
+        let repository = SqliteRepository::new(conn.clone());
+        let mut ballots = Vec::new();
+        for _ in 0..2 {
+            let vote = Vote::build(
+                &conn,
+                &repository,
+                VoteCredential::Voter(voter.id.clone()),
+                VoteKind::Candidature,
+                Some("55".to_string()),
+                CandidaturePosition::President,
+            )
+            .await
+            .unwrap();
+            ballots.push(vote);
         }
+        ballots[0].create(&conn).await.unwrap();
+        let reason = ballots[1].create(&conn).await.unwrap_err();
+        assert!(reason.is::<AlreadyVoted>());
 
-        Ok(cs)
+        let reason = cast(&conn, &voter, "55").await.unwrap_err();
+        assert!(reason.is::<AlreadyVoted>());
+        assert_eq!(counts(&conn, &VoteFilter::default()).await, vec![1]);
+        let participations = sqlx::query("SELECT voter_id FROM participations")
+            .fetch_all(&conn)
+            .await
+            .unwrap();
+        assert_eq!(participations.len(), 1);
     }
 }
@@ -0,0 +1,129 @@
+use std::fmt;
+
+use sqlx::{SqliteConnection, SqlitePool};
+
+use super::Vote;
+use crate::{CandidaturePosition, Election};
+
+/// Raised by the `participations_one_per_year` trigger, which stops a
+/// participation outside an election and one in an election of the same
+/// year from coexisting.
+const TRIGGER_MESSAGE: &str = "voter already participated in this year";
+
+/// The voter already cast a ballot, or took a ballot token, for the position
+/// in the election.
+#[derive(Debug)]
+pub struct AlreadyVoted;
+
+impl fmt::Display for AlreadyVoted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "voter already voted for this position in this election")
+    }
+}
+
+impl std::error::Error for AlreadyVoted {}
+
+/// Records that the voter took part for the position, in the transaction of
+/// the vote or token issuance itself. The unique index and trigger on
+/// participations are what stop two concurrent requests of the same voter;
+/// their violations are reported as `AlreadyVoted`.
+pub(crate) async fn record(
+    conn: &mut SqliteConnection,
+    voter_id: &str,
+    position: &CandidaturePosition,
+    election_id: Option<&str>,
+    year: i32,
+) -> Result<(), anyhow::Error> {
+    let position = position.to_string();
+    let recorded = sqlx::query!(
+        r#"
+        INSERT INTO participations (voter_id, position, election_id, year, round)
+        VALUES (?1, ?2, ?3, ?4, COALESCE((SELECT round FROM elections WHERE id = ?3), 1))
+        "#,
+        voter_id,
+        position,
+        election_id,
+        year,
+    )
+    .execute(&mut *conn)
+    .await;
+
+    match recorded {
+        Ok(_) => Ok(()),
+        Err(sqlx::Error::Database(reason))
+            if reason.is_unique_violation() || reason.message() == TRIGGER_MESSAGE =>
+        {
+            Err(AlreadyVoted.into())
+        }
+        Err(reason) => Err(reason.into()),
+    }
+}
+
+impl Vote {
+    /// Early check before building a ballot, so the voter gets the error
+    /// before anything is signed. Rows recorded without an election count
+    /// for every election of their year.
+    pub async fn has_participated(
+        conn: &SqlitePool,
+        voter_id: &str,
+        position: &CandidaturePosition,
+        election: Option<&Election>,
+        year: i32,
+    ) -> Result<bool, sqlx::Error> {
+        let position = position.to_string();
+        let election_id = election.map(|election| &election.id);
+        let round = election.map_or(1, |election| election.round);
+        let row = sqlx::query!(
+            r#"
+            SELECT
+                voter_id AS "voter_id!: String"
+            FROM
+                participations
+            WHERE
+                voter_id = ?1 AND
+                position = ?2 AND
+                round = ?5 AND
+                (election_id = ?3 OR (election_id IS NULL AND year = ?4))
+            "#,
+            voter_id,
+            position,
+            election_id,
+            year,
+            round
+        )
+        .fetch_optional(conn)
+        .await?;
+
+        Ok(row.is_some())
+    }
+
+    /// Whether the voter cast a ballot, or took a ballot token, for the
+    /// position in any election of the year.
+    pub async fn has_voted(
+        conn: &SqlitePool,
+        voter_id: &str,
+        position: &CandidaturePosition,
+        year: i32,
+    ) -> Result<bool, sqlx::Error> {
+        let position = position.to_string();
+        let row = sqlx::query!(
+            r#"
+            SELECT
+                voter_id AS "voter_id!: String"
+            FROM
+                participations
+            WHERE
+                voter_id = ? AND
+                position = ? AND
+                year = ?
+            "#,
+            voter_id,
+            position,
+            year
+        )
+        .fetch_optional(conn)
+        .await?;
+
+        Ok(row.is_some())
+    }
+}
@@ -0,0 +1,349 @@
+//! Round-trips the `Repository` trait against both backends. The SQLite
+//! tests run on a throwaway database file; the PostgreSQL ones need
+//! `POSTGRES_URL` and are ignored unless run with `make test-postgres`.
+
+use std::collections::BTreeMap;
+
+use bbox::{
+    elgamal::{self, Ciphertext, DecryptionShare},
+    migrate, AlreadyVoted, Candidate, Candidature, CandidaturePosition, DatabaseConfig, Election,
+    Party, PostgresRepository, Repository, SqliteRepository, TallyMode, Vote, VoteKind, Voter,
+    CURRENT_HASH_VERSION,
+};
+use chrono::{Datelike, NaiveDate, SubsecRound};
+use rand::Rng;
+use sqlx::SqlitePool;
+use uuid::Uuid;
+
+const ACTOR: &str = "repository-test";
+
+fn set_secret_key() {
+    std::env::set_var(
+        "SECRET_KEY",
+        "5RMI18i7kVrig0n3sREaQZTGCIveilg3wXaijT1RH3zBWvt/9vw7lxVQ8IQpy5+afRD60iObOv8W1MFYSFf7Yg==",
+    );
+}
+
+async fn sqlite_pool() -> SqlitePool {
+    set_secret_key();
+    let path = std::env::temp_dir().join(format!("bbox-repository-{}.sqlite3", Uuid::now_v7()));
+    let conn = DatabaseConfig::default()
+        .connect(&format!("sqlite://{}", path.display()))
+        .await
+        .unwrap();
+    migrate(&conn).await.unwrap();
+    conn
+}
+
+async fn sqlite_repository() -> SqliteRepository {
+    SqliteRepository::new(sqlite_pool().await)
+}
+
+async fn postgres_repository() -> PostgresRepository {
+    set_secret_key();
+    let database_url = std::env::var("POSTGRES_URL").expect("POSTGRES_URL must be set");
+    PostgresRepository::connect(&database_url).await.unwrap()
+}
+
+fn random_cpf() -> String {
+    let mut rng = rand::thread_rng();
+    let mut digits: Vec<u32> = (0..9).map(|_| rng.gen_range(0..10)).collect();
+    for length in [9, 10] {
+        let sum: u32 = digits
+            .iter()
+            .enumerate()
+            .map(|(i, digit)| digit * (length as u32 + 1 - i as u32))
+            .sum();
+        digits.push((sum * 10) % 11 % 10);
+    }
+    digits.iter().map(|digit| digit.to_string()).collect()
+}
+
+/// Registers a party under a two-digit number no other party has, as
+/// councilor codes are the number followed by three digits. PostgreSQL
+/// databases outlive a test run, so the number is tried at random.
+async fn create_party(repository: &dyn Repository) -> Party {
+    let mut rng = rand::thread_rng();
+    for _ in 0..50 {
+        let mut party = Party::build(
+            "Partido de Teste".to_string(),
+            "Created by the repository tests".to_string(),
+            "PT".to_string(),
+        );
+        party.number = Some(rng.gen_range(10..100));
+        if repository.create_party(&party, ACTOR).await.is_ok() {
+            return party;
+        }
+    }
+    panic!("no free party number");
+}
+
+async fn create_voter(repository: &dyn Repository) -> Voter {
+    let mut voter = Voter::build(
+        "Bruno".to_string(),
+        "Lima".to_string(),
+        "Carla".to_string(),
+        "Diego".to_string(),
+        NaiveDate::from_ymd_opt(1990, 1, 1).unwrap(),
+        Some("001".to_string()),
+        Some("0001".to_string()),
+    );
+    voter.national_id = Some(random_cpf());
+    repository.create_voter(&voter, ACTOR).await.unwrap();
+    voter
+}
+
+/// A councilor candidature of a new party and candidate, in district 001.
+async fn create_candidature(repository: &dyn Repository) -> Candidature {
+    let party = create_party(repository).await;
+
+    let mut candidate = Candidate::build("Ana".to_string(), "Souza".to_string());
+    candidate.birth_date = NaiveDate::from_ymd_opt(1970, 1, 1);
+    candidate.party_id = Some(party.id.clone());
+    repository
+        .create_candidate(&candidate, ACTOR)
+        .await
+        .unwrap();
+
+    let mut candidature = Candidature::build(
+        party.id.clone(),
+        candidate.id.clone(),
+        format!("{}001", party.number.unwrap()),
+        CandidaturePosition::Councilor,
+        String::new(),
+    );
+    candidature.district = Some("001".to_string());
+    repository
+        .create_candidature(&candidature, None, ACTOR)
+        .await
+        .unwrap();
+    candidature
+}
+
+fn build_vote(voter: &Voter, candidature: &Candidature) -> Vote {
+    Vote {
+        id: Uuid::now_v7().to_string(),
+        voter_id: Some(voter.id.clone()),
+        candidature_id: Some(candidature.id.clone()),
+        candidature_position: candidature.position.clone(),
+        hash: String::new(),
+        previous_hash: String::new(),
+        hash_version: CURRENT_HASH_VERSION,
+        year: chrono::Utc::now().year(),
+        created_at: chrono::Utc::now().trunc_subsecs(6),
+        ballot_token: None,
+        election_id: None,
+        encrypted_ballot: None,
+        kind: VoteKind::Candidature,
+        district: voter.district.clone(),
+        section: voter.section.clone(),
+        key_id: None,
+    }
+}
+
+async fn registry_round_trips(repository: &dyn Repository) {
+    let candidature = create_candidature(repository).await;
+    let party = repository
+        .find_party(&candidature.party_id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(party.acronym, "PT");
+    let candidate = repository
+        .find_candidate(&candidature.candidate_id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(candidate.first_name, "Ana");
+
+    let found = repository
+        .find_candidature(
+            &candidature.code,
+            &candidature.position,
+            Some("001"),
+            candidature.year,
+            None,
+        )
+        .await
+        .unwrap();
+    assert_eq!(found.map(|found| found.id), Some(candidature.id.clone()));
+    let missing = repository
+        .find_candidature(
+            &candidature.code,
+            &candidature.position,
+            Some("002"),
+            candidature.year,
+            None,
+        )
+        .await
+        .unwrap();
+    assert!(missing.is_none());
+    let last_cycle = repository
+        .find_candidature(
+            &candidature.code,
+            &candidature.position,
+            Some("001"),
+            candidature.year - 1,
+            None,
+        )
+        .await
+        .unwrap();
+    assert!(last_cycle.is_none());
+    let running = repository
+        .list_candidatures(&candidature.position, candidature.year)
+        .await
+        .unwrap();
+    assert!(running.iter().any(|running| running.id == candidature.id));
+
+    let voter = create_voter(repository).await;
+    assert!(repository.find_voter(&voter.id).await.unwrap().is_some());
+    let national_id = voter.national_id.clone().unwrap();
+    let punctuated = format!(
+        "{}.{}.{}-{}",
+        &national_id[..3],
+        &national_id[3..6],
+        &national_id[6..9],
+        &national_id[9..]
+    );
+    let found = repository
+        .find_voter_by_national_id(&punctuated)
+        .await
+        .unwrap();
+    assert_eq!(found.map(|found| found.id), Some(voter.id));
+}
+
+async fn votes_chain_once_per_voter(repository: &dyn Repository) {
+    let candidature = create_candidature(repository).await;
+    let voter = create_voter(repository).await;
+    let year = chrono::Utc::now().year();
+    assert!(!repository
+        .has_voted(&voter.id, &candidature.position, year)
+        .await
+        .unwrap());
+    assert!(!repository
+        .has_participated(&voter.id, &candidature.position, None, year)
+        .await
+        .unwrap());
+
+    let previous_hash = repository.last_vote_hash().await.unwrap();
+    let mut vote = build_vote(&voter, &candidature);
+    repository.create_vote(&mut vote).await.unwrap();
+    assert_eq!(vote.previous_hash, previous_hash);
+    assert_eq!(repository.last_vote_hash().await.unwrap(), vote.hash);
+    assert!(repository
+        .has_voted(&voter.id, &candidature.position, year)
+        .await
+        .unwrap());
+    assert!(repository
+        .has_participated(&voter.id, &candidature.position, None, year)
+        .await
+        .unwrap());
+
+    let mut again = build_vote(&voter, &candidature);
+    let reason = repository.create_vote(&mut again).await.unwrap_err();
+    assert!(reason.is::<AlreadyVoted>());
+    assert_eq!(repository.last_vote_hash().await.unwrap(), vote.hash);
+
+    let votes = repository.list_votes().await.unwrap();
+    assert_eq!(Vote::verify(&votes).unwrap(), votes.len() - 1);
+}
+
+/// Votes cast at once must still form a single chain, each linked to the
+/// one before it.
+async fn concurrent_votes_form_one_chain(repository: &dyn Repository) {
+    let candidature = create_candidature(repository).await;
+    let mut voters = Vec::new();
+    for _ in 0..8 {
+        voters.push(create_voter(repository).await);
+    }
+
+    let casts = voters.iter().map(|voter| {
+        let mut vote = build_vote(voter, &candidature);
+        async move { repository.create_vote(&mut vote).await }
+    });
+    for cast in futures_util::future::join_all(casts).await {
+        cast.unwrap();
+    }
+
+    let votes = repository.list_votes().await.unwrap();
+    assert_eq!(Vote::verify(&votes).unwrap(), votes.len() - 1);
+}
+
+/// Encrypted ballots add up in the tallies of their election, in the same
+/// transaction as the vote.
+async fn encrypted_votes_add_up(repository: &dyn Repository, election_id: &str) {
+    let candidature = create_candidature(repository).await;
+    let (secret, public_key) = elgamal::generate_keypair();
+
+    for _ in 0..3 {
+        let voter = create_voter(repository).await;
+        let ballot = BTreeMap::from([(
+            candidature.id.clone(),
+            Ciphertext::encrypt(&public_key, 1).encode(),
+        )]);
+        let mut vote = build_vote(&voter, &candidature);
+        vote.candidature_id = None;
+        vote.election_id = Some(election_id.to_string());
+        vote.encrypted_ballot = Some(serde_json::to_string(&ballot).unwrap());
+        repository.create_vote(&mut vote).await.unwrap();
+    }
+
+    let tallies = repository.encrypted_tallies(election_id).await.unwrap();
+    assert_eq!(tallies.len(), 1);
+    let (candidature_id, tally) = &tallies[0];
+    assert_eq!(candidature_id, &candidature.id);
+    let share = DecryptionShare::compute(&secret, tally).share;
+    assert_eq!(tally.decrypt_with(&share, 10), Some(3));
+}
+
+#[tokio::test]
+async fn sqlite_registry_round_trips() {
+    registry_round_trips(&sqlite_repository().await).await;
+}
+
+#[tokio::test]
+async fn sqlite_votes_chain_once_per_voter() {
+    votes_chain_once_per_voter(&sqlite_repository().await).await;
+}
+
+#[tokio::test]
+async fn sqlite_concurrent_votes_form_one_chain() {
+    concurrent_votes_form_one_chain(&sqlite_repository().await).await;
+}
+
+#[tokio::test]
+async fn sqlite_encrypted_votes_add_up() {
+    let conn = sqlite_pool().await;
+    let election = Election::build(
+        "Eleição de teste".to_string(),
+        chrono::Utc::now().year(),
+        TallyMode::Encrypted,
+    );
+    election.create(&conn, ACTOR).await.unwrap();
+    encrypted_votes_add_up(&SqliteRepository::new(conn), &election.id).await;
+}
+
+#[tokio::test]
+#[ignore = "needs POSTGRES_URL; run with make test-postgres"]
+async fn postgres_registry_round_trips() {
+    registry_round_trips(&postgres_repository().await).await;
+}
+
+#[tokio::test]
+#[ignore = "needs POSTGRES_URL; run with make test-postgres"]
+async fn postgres_votes_chain_once_per_voter() {
+    votes_chain_once_per_voter(&postgres_repository().await).await;
+}
+
+#[tokio::test]
+#[ignore = "needs POSTGRES_URL; run with make test-postgres"]
+async fn postgres_concurrent_votes_form_one_chain() {
+    concurrent_votes_form_one_chain(&postgres_repository().await).await;
+}
+
+#[tokio::test]
+#[ignore = "needs POSTGRES_URL; run with make test-postgres"]
+async fn postgres_encrypted_votes_add_up() {
+    let election_id = Uuid::now_v7().to_string();
+    encrypted_votes_add_up(&postgres_repository().await, &election_id).await;
+}